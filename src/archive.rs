@@ -0,0 +1,141 @@
+//! Directories compressed as a tar archive.
+//! The archive is packed or unpacked by a background thread connected with a pipe,
+//! so it is never held in memory or written to disk as a whole.
+
+use std::io;
+use std::io::{ErrorKind, PipeReader, PipeWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Reads a tar archive of a directory, packed while it is being read.
+/// Entry paths are relative to the directory.
+pub struct TarReader {
+    pipe: PipeReader,
+    pos: u64,
+    packer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl TarReader {
+    pub fn new(dir: &Path) -> io::Result<TarReader> {
+        let (pipe, writer) = io::pipe()?;
+        let dir = dir.to_path_buf();
+        let packer = thread::spawn(move || {
+            let mut builder = tar::Builder::new(writer);
+            // Keep symbolic links as links, like the tar tool does
+            builder.follow_symlinks(false);
+            builder.append_dir_all("", &dir)?;
+            builder.finish()
+        });
+        Ok(TarReader {
+            pipe,
+            pos: 0,
+            packer: Some(packer),
+        })
+    }
+}
+
+impl Read for TarReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.pipe.read(buf)?;
+        // The pipe is closed also when packing fails, so the archive may be incomplete
+        if count == 0 && !buf.is_empty() {
+            if let Some(packer) = self.packer.take() {
+                join(packer)?;
+            }
+        }
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+/// Allows only querying the position, which is the number of bytes read so far
+impl Seek for TarReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.pos),
+            _ => Err(not_seekable()),
+        }
+    }
+}
+
+/// Unpacks a tar archive written to it into a directory.
+/// The directory is created if it doesn't exist.
+pub struct TarWriter {
+    pipe: Option<PipeWriter>,
+    pos: u64,
+    unpacker: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl TarWriter {
+    pub fn new(dir: &Path) -> io::Result<TarWriter> {
+        let (mut reader, pipe) = io::pipe()?;
+        let dir = dir.to_path_buf();
+        let unpacker = thread::spawn(move || {
+            tar::Archive::new(&mut reader).unpack(&dir)?;
+            // Consume the padding after the end of the archive, so writing it doesn't fail
+            io::copy(&mut reader, &mut io::sink())?;
+            Ok(())
+        });
+        Ok(TarWriter {
+            pipe: Some(pipe),
+            pos: 0,
+            unpacker: Some(unpacker),
+        })
+    }
+
+    /// Closes the archive and waits until it is unpacked.
+    /// Returns an error if the archive is invalid or could not be unpacked.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.pipe = None;
+        match self.unpacker.take() {
+            Some(unpacker) => join(unpacker),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for TarWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let pipe = self.pipe.as_mut().ok_or(ErrorKind::BrokenPipe)?;
+        match pipe.write(buf) {
+            Ok(count) => {
+                self.pos += count as u64;
+                Ok(count)
+            }
+            // The unpacker closed the pipe, report why
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                self.pipe = None;
+                match self.unpacker.take() {
+                    Some(unpacker) => join(unpacker).and(Err(e)),
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Allows only querying the position, which is the number of bytes written so far
+impl Seek for TarWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.pos),
+            _ => Err(not_seekable()),
+        }
+    }
+}
+
+fn join(handle: JoinHandle<io::Result<()>>) -> io::Result<()> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("tar thread panicked")))
+}
+
+fn not_seekable() -> io::Error {
+    io::Error::new(ErrorKind::Unsupported, "Tar streams are not seekable")
+}
@@ -0,0 +1,96 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use twox_hash::XxHash3_64;
+
+/// Checksum of the uncompressed data stored after each data frame
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum Checksum {
+    #[default]
+    None,
+    /// 32-bit CRC, hardware accelerated on most CPUs
+    Crc32,
+    /// 64-bit xxHash3, usually faster than CRC32 and with fewer collisions
+    Xxh3,
+}
+
+const ID_NONE: u8 = 0;
+const ID_CRC32: u8 = 1;
+const ID_XXH3: u8 = 2;
+
+impl Checksum {
+    /// Number of bytes the checksum adds to each data frame
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32 => 4,
+            Checksum::Xxh3 => 8,
+        }
+    }
+
+    /// Computes the checksum of the data. Returns 0 if there is no checksum.
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32 => crc32fast::hash(data) as u64,
+            Checksum::Xxh3 => XxHash3_64::oneshot(data),
+        }
+    }
+
+    /// Writes a value returned by [`Checksum::compute`] in [`Checksum::encoded_len`] bytes
+    pub(crate) fn write<W: Write>(&self, value: u64, output: &mut W) -> io::Result<()> {
+        match self {
+            Checksum::None => Ok(()),
+            Checksum::Crc32 => output.write_u32::<LittleEndian>(value as u32),
+            Checksum::Xxh3 => output.write_u64::<LittleEndian>(value),
+        }
+    }
+
+    /// Reads a value written by [`Checksum::write`]
+    pub(crate) fn read<R: Read>(&self, input: &mut R) -> io::Result<u64> {
+        match self {
+            Checksum::None => Ok(0),
+            Checksum::Crc32 => input.read_u32::<LittleEndian>().map(u64::from),
+            Checksum::Xxh3 => input.read_u64::<LittleEndian>(),
+        }
+    }
+
+    /// Checks that the data has the expected checksum
+    pub(crate) fn verify(&self, data: &[u8], expected: u64) -> io::Result<()> {
+        let actual = self.compute(data);
+        if actual != expected {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?} checksum mismatch: expected {:x}, computed {:x}",
+                    self, expected, actual
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Identifier of the checksum stored in header frames
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Checksum::None => ID_NONE,
+            Checksum::Crc32 => ID_CRC32,
+            Checksum::Xxh3 => ID_XXH3,
+        }
+    }
+
+    /// Reads an identifier written by [`Checksum::to_byte`]
+    pub(crate) fn from_byte(id: u8) -> io::Result<Checksum> {
+        match id {
+            ID_NONE => Ok(Checksum::None),
+            ID_CRC32 => Ok(Checksum::Crc32),
+            ID_XXH3 => Ok(Checksum::Xxh3),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown checksum {} in header", id),
+            )),
+        }
+    }
+}
@@ -1,25 +1,97 @@
-use crate::codec::{Decoder, Encoder};
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
 use brotlic_sys::{
     BrotliDecoderAttachDictionary, BrotliDecoderCreateInstance, BrotliDecoderDecompress,
-    BrotliDecoderDecompressStream, BrotliDecoderDestroyInstance, BrotliDecoderHasMoreOutput,
-    BrotliEncoderAttachPreparedDictionary, BrotliEncoderCompress, BrotliEncoderCompressStream,
-    BrotliEncoderCreateInstance, BrotliEncoderDestroyInstance,
+    BrotliDecoderDecompressStream, BrotliDecoderDestroyInstance, BrotliDecoderErrorString,
+    BrotliDecoderGetErrorCode, BrotliDecoderParameter_BROTLI_DECODER_PARAM_LARGE_WINDOW,
+    BrotliDecoderResult, BrotliDecoderResult_BROTLI_DECODER_RESULT_ERROR,
+    BrotliDecoderResult_BROTLI_DECODER_RESULT_NEEDS_MORE_INPUT,
+    BrotliDecoderResult_BROTLI_DECODER_RESULT_SUCCESS, BrotliDecoderSetParameter,
+    BrotliDecoderState, BrotliEncoderAttachPreparedDictionary, BrotliEncoderCompress,
+    BrotliEncoderCompressStream, BrotliEncoderCreateInstance, BrotliEncoderDestroyInstance,
     BrotliEncoderDestroyPreparedDictionary, BrotliEncoderHasMoreOutput,
-    BrotliEncoderMaxCompressedSize, BrotliEncoderMode_BROTLI_MODE_GENERIC,
-    BrotliEncoderOperation_BROTLI_OPERATION_FINISH, BrotliEncoderParameter_BROTLI_PARAM_MODE,
-    BrotliEncoderParameter_BROTLI_PARAM_QUALITY, BrotliEncoderPrepareDictionary,
-    BrotliEncoderPreparedDictionary, BrotliEncoderSetParameter,
-    BrotliSharedDictionaryType_BROTLI_SHARED_DICTIONARY_RAW, BROTLI_DEFAULT_WINDOW,
-    BROTLI_MAX_QUALITY,
+    BrotliEncoderMaxCompressedSize, BrotliEncoderMode, BrotliEncoderMode_BROTLI_MODE_FONT,
+    BrotliEncoderMode_BROTLI_MODE_GENERIC, BrotliEncoderMode_BROTLI_MODE_TEXT,
+    BrotliEncoderOperation_BROTLI_OPERATION_FINISH,
+    BrotliEncoderParameter_BROTLI_PARAM_LARGE_WINDOW, BrotliEncoderParameter_BROTLI_PARAM_LGWIN,
+    BrotliEncoderParameter_BROTLI_PARAM_MODE, BrotliEncoderParameter_BROTLI_PARAM_QUALITY,
+    BrotliEncoderPrepareDictionary, BrotliEncoderPreparedDictionary, BrotliEncoderSetParameter,
+    BrotliEncoderState, BrotliSharedDictionaryType_BROTLI_SHARED_DICTIONARY_RAW,
+    BROTLI_DEFAULT_WINDOW, BROTLI_MAX_QUALITY, BROTLI_MAX_WINDOW_BITS,
 };
-use std::ffi::c_int;
+use clap::ValueEnum;
+use std::ffi::{c_int, CStr};
 use std::io::ErrorKind;
 use std::{io, ptr};
 
-pub struct BrotliCompressor(pub i32);
-pub struct BrotliDecompressor;
+/// Smallest window log accepted by brotli
+pub const MIN_WINDOW: u32 = brotlic_sys::BROTLI_MIN_WINDOW_BITS;
+/// Largest window log accepted by brotli. Windows above 24 use the large window extension,
+/// which must be enabled also in the decoder.
+pub const MAX_WINDOW: u32 = brotlic_sys::BROTLI_LARGE_MAX_WINDOW_BITS;
+
+/// Kind of the input the brotli encoder is tuned for
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BrotliMode {
+    #[default]
+    Generic,
+    /// UTF-8 text, e.g. HTML
+    Text,
+    /// WOFF 2.0 fonts
+    Font,
+}
+
+impl BrotliMode {
+    fn to_raw(self) -> BrotliEncoderMode {
+        match self {
+            BrotliMode::Generic => BrotliEncoderMode_BROTLI_MODE_GENERIC,
+            BrotliMode::Text => BrotliEncoderMode_BROTLI_MODE_TEXT,
+            BrotliMode::Font => BrotliEncoderMode_BROTLI_MODE_FONT,
+        }
+    }
+}
+
+/// Returns true if streams compressed with the window log need the large window decoder
+pub fn is_large_window(window: Option<u32>) -> bool {
+    window.is_some_and(|window| window > BROTLI_MAX_WINDOW_BITS)
+}
+
+/// Brotli can use a dictionary; without one, [`BrotliCompressor`] and [`BrotliDecompressor`]
+/// are used instead of their dictionary variants
+const DICT_CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: true,
+    self_describing: false,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+pub struct BrotliCompressor {
+    quality: i32,
+    window: u32,
+    mode: BrotliMode,
+}
+
+impl BrotliCompressor {
+    /// Uses the default window of 4 MiB if the window log is not given
+    pub fn new(quality: i32, window: Option<u32>, mode: BrotliMode) -> Self {
+        BrotliCompressor {
+            quality,
+            window: window.unwrap_or(BROTLI_DEFAULT_WINDOW),
+            mode,
+        }
+    }
+}
+
+/// Decompresses streams compressed with any window log if `large_window` is set,
+/// otherwise only those with window logs up to 24
+pub struct BrotliDecompressor {
+    pub large_window: bool,
+}
 
 impl Encoder for BrotliCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        DICT_CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         unsafe { BrotliEncoderMaxCompressedSize(uncompressed_len) }
     }
@@ -32,9 +104,9 @@ impl Encoder for BrotliCompressor {
 
         let result = unsafe {
             BrotliEncoderCompress(
-                self.0,
-                BROTLI_DEFAULT_WINDOW as c_int,
-                BrotliEncoderMode_BROTLI_MODE_GENERIC,
+                self.quality,
+                self.window as c_int,
+                self.mode.to_raw(),
                 input_len,
                 input_ptr,
                 &mut output_len,
@@ -44,13 +116,30 @@ impl Encoder for BrotliCompressor {
         if result != 0 {
             Ok(output_len)
         } else {
-            Err(io::Error::new(ErrorKind::Other, "Failed to compress"))
+            // Brotli reports no reason, but only these inputs make it fail
+            Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Failed to compress: brotli rejected window log {} \
+                     or the output buffer of {} bytes is too small",
+                    self.window,
+                    dest.len()
+                ),
+            ))
         }
     }
 }
 
 impl Decoder for BrotliDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        DICT_CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        // The one-shot decoder doesn't support the large window extension
+        if self.large_window {
+            return decompress_stream(src, dest, true, None);
+        }
         let input_ptr = src.as_ptr();
         let input_len = src.len();
         let output_ptr = dest.as_mut_ptr();
@@ -58,21 +147,56 @@ impl Decoder for BrotliDecompressor {
 
         let result =
             unsafe { BrotliDecoderDecompress(input_len, input_ptr, &mut output_len, output_ptr) };
-        if result != 0 {
+        if result == BrotliDecoderResult_BROTLI_DECODER_RESULT_SUCCESS {
             Ok(output_len)
         } else {
-            Err(io::Error::new(ErrorKind::Other, "Failed to decompress"))
+            // The one-shot decoder reports every failure the same way,
+            // decoding again with the streaming one tells why
+            decompress_stream(src, dest, false, None)
         }
     }
 }
 
+/// Describes why the streaming decoder didn't finish the stream
+unsafe fn decoder_error(
+    instance: *mut BrotliDecoderState,
+    result: BrotliDecoderResult,
+) -> io::Error {
+    if result == BrotliDecoderResult_BROTLI_DECODER_RESULT_ERROR {
+        let code = BrotliDecoderGetErrorCode(instance);
+        let name = CStr::from_ptr(BrotliDecoderErrorString(code)).to_string_lossy();
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Failed to decompress: brotli error {} ({})",
+                name.trim_start_matches('_'),
+                code
+            ),
+        )
+    } else if result == BrotliDecoderResult_BROTLI_DECODER_RESULT_NEEDS_MORE_INPUT {
+        io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "Failed to decompress: the stream is truncated",
+        )
+    } else {
+        io::Error::new(ErrorKind::InvalidInput, "Output buffer too small")
+    }
+}
+
 pub struct BrotliDictCompressor {
     quality: u32,
+    window: u32,
+    mode: BrotliMode,
     dict: *mut BrotliEncoderPreparedDictionary,
 }
 
 impl BrotliDictCompressor {
-    pub fn new(quality: u32, dict: &[u8]) -> Self {
+    pub fn new(
+        quality: u32,
+        window: Option<u32>,
+        mode: BrotliMode,
+        dict: &[u8],
+    ) -> io::Result<Self> {
         let dict_data = dict.to_vec();
 
         unsafe {
@@ -87,10 +211,21 @@ impl BrotliDictCompressor {
             );
 
             if dict.is_null() {
-                panic!("BrotliEncoderPrepareDictionary returned NULL")
+                return Err(io::Error::new(
+                    ErrorKind::OutOfMemory,
+                    format!(
+                        "Failed to prepare the brotli dictionary of {} bytes",
+                        dict_data.len()
+                    ),
+                ));
             }
 
-            BrotliDictCompressor { quality, dict }
+            Ok(BrotliDictCompressor {
+                quality,
+                window: window.unwrap_or(BROTLI_DEFAULT_WINDOW),
+                mode,
+                dict,
+            })
         }
     }
 }
@@ -104,6 +239,10 @@ impl Drop for BrotliDictCompressor {
 }
 
 impl Encoder for BrotliDictCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        DICT_CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         unsafe { BrotliEncoderMaxCompressedSize(uncompressed_len) }
     }
@@ -112,121 +251,216 @@ impl Encoder for BrotliDictCompressor {
         unsafe {
             let instance = BrotliEncoderCreateInstance(None, None, ptr::null_mut());
             if instance.is_null() {
-                panic!(
-                    "BrotliEncoderCreateInstance returned NULL: failed to allocate or initialize"
-                );
+                return Err(io::Error::new(
+                    ErrorKind::OutOfMemory,
+                    "Failed to create the brotli encoder",
+                ));
             }
+            let result = self.compress_with_instance(instance, src, dest);
+            BrotliEncoderDestroyInstance(instance);
+            result
+        }
+    }
+}
 
-            if BrotliEncoderSetParameter(
+impl BrotliDictCompressor {
+    unsafe fn compress_with_instance(
+        &self,
+        instance: *mut BrotliEncoderState,
+        src: &[u8],
+        dest: &mut [u8],
+    ) -> io::Result<usize> {
+        if BrotliEncoderSetParameter(
+            instance,
+            BrotliEncoderParameter_BROTLI_PARAM_MODE,
+            self.mode.to_raw(),
+        ) == 0
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported brotli mode {:?}", self.mode),
+            ));
+        };
+        if BrotliEncoderSetParameter(
+            instance,
+            BrotliEncoderParameter_BROTLI_PARAM_LARGE_WINDOW,
+            is_large_window(Some(self.window)) as u32,
+        ) == 0
+            || BrotliEncoderSetParameter(
                 instance,
-                BrotliEncoderParameter_BROTLI_PARAM_MODE,
-                BrotliEncoderMode_BROTLI_MODE_GENERIC as u32,
+                BrotliEncoderParameter_BROTLI_PARAM_LGWIN,
+                self.window,
             ) == 0
-            {
-                panic!("Failed to set compression mode");
-            };
-            if BrotliEncoderSetParameter(
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported brotli window log {}", self.window),
+            ));
+        }
+        if BrotliEncoderSetParameter(
+            instance,
+            BrotliEncoderParameter_BROTLI_PARAM_QUALITY,
+            self.quality,
+        ) == 0
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported brotli quality {}", self.quality),
+            ));
+        }
+
+        if BrotliEncoderAttachPreparedDictionary(instance, self.dict) == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Failed to attach the brotli dictionary with window log {}",
+                    self.window
+                ),
+            ));
+        };
+
+        let mut input_ptr = src.as_ptr();
+        let mut input_len = src.len();
+        let mut output_ptr = dest.as_mut_ptr();
+        let mut output_len = dest.len();
+        let mut total_out = 0;
+
+        loop {
+            if BrotliEncoderCompressStream(
                 instance,
-                BrotliEncoderParameter_BROTLI_PARAM_QUALITY,
-                self.quality,
+                BrotliEncoderOperation_BROTLI_OPERATION_FINISH,
+                &mut input_len,
+                &mut input_ptr,
+                &mut output_len,
+                &mut output_ptr,
+                &mut total_out,
             ) == 0
             {
-                panic!("Failed to set compression quality");
-            }
-
-            if BrotliEncoderAttachPreparedDictionary(instance, self.dict) == 0 {
-                panic!("BrotliEncoderAttachPreparedDictionary failed")
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "Failed to compress: brotli encoder failed after writing {} bytes",
+                        total_out
+                    ),
+                ));
             };
 
-            let mut input_ptr = src.as_ptr();
-            let mut input_len = src.len();
-            let mut output_ptr = dest.as_mut_ptr();
-            let mut output_len = dest.len();
-            let mut total_out = 0;
-
-            loop {
-                if BrotliEncoderCompressStream(
-                    instance,
-                    BrotliEncoderOperation_BROTLI_OPERATION_FINISH,
-                    &mut input_len,
-                    &mut input_ptr,
-                    &mut output_len,
-                    &mut output_ptr,
-                    &mut total_out,
-                ) == 0
-                {
-                    return Err(io::Error::new(ErrorKind::Other, "Failed to compress"));
-                };
-
-                if BrotliEncoderHasMoreOutput(instance) == 0 {
-                    break;
-                }
+            if BrotliEncoderHasMoreOutput(instance) == 0 {
+                break;
             }
+            if output_len == 0 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Output buffer too small",
+                ));
+            }
+        }
+        Ok(total_out)
+    }
+}
 
-            BrotliEncoderDestroyInstance(instance);
-            Ok(total_out)
+/// Decompresses with the streaming decoder, which unlike the one-shot one
+/// can use a dictionary and the large window extension
+fn decompress_stream(
+    src: &[u8],
+    dest: &mut [u8],
+    large_window: bool,
+    dict: Option<&[u8]>,
+) -> io::Result<usize> {
+    unsafe {
+        let instance = BrotliDecoderCreateInstance(None, None, ptr::null_mut());
+        if instance.is_null() {
+            return Err(io::Error::new(
+                ErrorKind::OutOfMemory,
+                "Failed to create the brotli decoder",
+            ));
         }
+        let result = decompress_with_instance(instance, src, dest, large_window, dict);
+        BrotliDecoderDestroyInstance(instance);
+        result
     }
 }
 
+unsafe fn decompress_with_instance(
+    instance: *mut BrotliDecoderState,
+    src: &[u8],
+    dest: &mut [u8],
+    large_window: bool,
+    dict: Option<&[u8]>,
+) -> io::Result<usize> {
+    if large_window
+        && BrotliDecoderSetParameter(
+            instance,
+            BrotliDecoderParameter_BROTLI_DECODER_PARAM_LARGE_WINDOW,
+            1,
+        ) == 0
+    {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "The brotli decoder doesn't support the large window",
+        ));
+    }
+
+    if let Some(dict) = dict {
+        if BrotliDecoderAttachDictionary(
+            instance,
+            BrotliSharedDictionaryType_BROTLI_SHARED_DICTIONARY_RAW,
+            dict.len(),
+            dict.as_ptr(),
+        ) == 0
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Failed to attach the brotli dictionary of {} bytes",
+                    dict.len()
+                ),
+            ));
+        };
+    }
+
+    let mut input_ptr = src.as_ptr();
+    let mut input_len = src.len();
+    let mut output_ptr = dest.as_mut_ptr();
+    let mut output_len = dest.len();
+    let mut total_out = 0;
+
+    // The whole input and output are given at once, so a single call either finishes the stream
+    // or fails because the output is too small or the input is truncated
+    let result = BrotliDecoderDecompressStream(
+        instance,
+        &mut input_len,
+        &mut input_ptr,
+        &mut output_len,
+        &mut output_ptr,
+        &mut total_out,
+    );
+    if result != BrotliDecoderResult_BROTLI_DECODER_RESULT_SUCCESS {
+        return Err(decoder_error(instance, result));
+    }
+    Ok(total_out)
+}
+
 pub struct BrotliDictDecompressor {
     dict: Vec<u8>,
+    large_window: bool,
 }
 
 impl BrotliDictDecompressor {
-    pub fn new(dict: &[u8]) -> Self {
+    pub fn new(dict: &[u8], large_window: bool) -> Self {
         BrotliDictDecompressor {
             dict: dict.to_vec(),
+            large_window,
         }
     }
 }
 
 impl Decoder for BrotliDictDecompressor {
-    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
-        unsafe {
-            let instance = BrotliDecoderCreateInstance(None, None, ptr::null_mut());
-            if instance.is_null() {
-                panic!(
-                    "BrotliDecoderCreateInstance returned NULL: failed to allocate or initialize"
-                );
-            }
-
-            if BrotliDecoderAttachDictionary(
-                instance,
-                BrotliSharedDictionaryType_BROTLI_SHARED_DICTIONARY_RAW,
-                self.dict.len(),
-                self.dict.as_ptr(),
-            ) == 0
-            {
-                panic!("BrotliDecoderAttachDictionary failed")
-            };
-
-            let mut input_ptr = src.as_ptr();
-            let mut input_len = src.len();
-            let mut output_ptr = dest.as_mut_ptr();
-            let mut output_len = dest.len();
-            let mut total_out = 0;
-
-            loop {
-                if BrotliDecoderDecompressStream(
-                    instance,
-                    &mut input_len,
-                    &mut input_ptr,
-                    &mut output_len,
-                    &mut output_ptr,
-                    &mut total_out,
-                ) == 0
-                {
-                    return Err(io::Error::new(ErrorKind::Other, "Failed to decompress"));
-                };
-
-                if BrotliDecoderHasMoreOutput(instance) == 0 {
-                    break;
-                }
-            }
+    fn capabilities(&self) -> CodecCapabilities {
+        DICT_CAPABILITIES
+    }
 
-            BrotliDecoderDestroyInstance(instance);
-            Ok(total_out)
-        }
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        decompress_stream(src, dest, self.large_window, Some(&self.dict))
     }
 }
@@ -0,0 +1,53 @@
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
+use bzip2::{Action, Compress, Compression, Decompress, Status};
+use std::io;
+use std::io::ErrorKind;
+
+pub struct Bzip2Compressor(pub u32);
+
+impl Bzip2Compressor {
+    pub(crate) fn new(compression: i32) -> Self {
+        Bzip2Compressor(compression.clamp(1, 9) as u32)
+    }
+}
+
+pub struct Bzip2Decompressor;
+
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: true,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+impl Encoder for Bzip2Compressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        uncompressed_len + uncompressed_len / 100 + 600
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let mut compress = Compress::new(Compression::new(self.0), 0);
+        match compress.compress(src, dest, Action::Finish)? {
+            Status::StreamEnd => Ok(compress.total_out() as usize),
+            _ => Err(io::Error::new(ErrorKind::Other, "Failed to compress")),
+        }
+    }
+}
+
+impl Decoder for Bzip2Decompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let mut decompress = Decompress::new(false);
+        match decompress.decompress(src, dest)? {
+            Status::StreamEnd => Ok(decompress.total_out() as usize),
+            _ => Err(io::Error::new(ErrorKind::Other, "Failed to decompress")),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::codec::{Decoder, Encoder};
 use std::io;
+use std::io::ErrorKind;
 
 pub struct Copy;
 
@@ -9,6 +10,12 @@ impl Encoder for Copy {
     }
 
     fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        if dest.len() < src.len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Output buffer too small",
+            ));
+        }
         dest[0..src.len()].copy_from_slice(src);
         Ok(src.len())
     }
@@ -16,6 +23,12 @@ impl Encoder for Copy {
 
 impl Decoder for Copy {
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        if dest.len() < src.len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Output buffer too small",
+            ));
+        }
         dest[0..src.len()].copy_from_slice(src);
         Ok(src.len())
     }
@@ -1,22 +1,100 @@
-use crate::codec::{Decoder, Encoder};
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
 use lz4::block::CompressionMode;
+use lz4::liblz4::{
+    LZ4StreamDecode, LZ4_createStreamDecode, LZ4_decompress_safe_continue, LZ4_freeStreamDecode,
+    LZ4_setStreamDecode,
+};
+use std::ffi::c_int;
+#[cfg(feature = "lz4-dict")]
+use std::ffi::{c_char, c_void};
 use std::io;
+use std::io::{Cursor, ErrorKind, Read, Seek, Write};
+
+/// Equivalent of `LZ4_MAX_INPUT_SIZE`
+const MAX_INPUT_SIZE: usize = 0x7E00_0000;
+
+/// LZ4 blocks can reference a dictionary; without one, [`Lz4Compressor`] and [`Lz4Decompressor`]
+/// are used instead of their dictionary variants
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: true,
+    self_describing: false,
+    max_block_size: Some(MAX_INPUT_SIZE),
+    multi_threaded: false,
+};
+
+/// Compressing with a dictionary needs the `lz4-dict` feature
+const ENCODER_CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: cfg!(feature = "lz4-dict"),
+    ..CAPABILITIES
+};
+
+/// Highest acceleration of the fast mode, equivalent of `LZ4_ACCELERATION_MAX`
+pub const MAX_ACCELERATION: u32 = 65537;
 
 pub struct Lz4Compressor(pub CompressionMode);
 
 impl Lz4Compressor {
-    pub(crate) fn new(compression: i32) -> Lz4Compressor {
-        match compression {
-            ..0 => Lz4Compressor(CompressionMode::FAST(-compression)),
-            0 => Lz4Compressor(CompressionMode::DEFAULT),
-            _ => Lz4Compressor(CompressionMode::HIGHCOMPRESSION(compression)),
+    /// Creates a compressor for the given level:
+    /// - 0 selects the fast mode, which can be made even faster and weaker with an acceleration
+    ///   from 2 to [`MAX_ACCELERATION`]; acceleration 1 is the default fast mode,
+    /// - 1 to 12 select the levels of the slower high compression (HC) mode.
+    ///
+    /// Negative levels, which used to select the acceleration, are rejected.
+    pub(crate) fn new(compression: i32, acceleration: Option<u32>) -> io::Result<Lz4Compressor> {
+        let invalid_input = |message: String| io::Error::new(ErrorKind::InvalidInput, message);
+        match (compression, acceleration) {
+            (..0, _) => Err(invalid_input(format!(
+                "lz4 level {} is not supported, use level 0 with acceleration {} instead",
+                compression, -compression
+            ))),
+            (0, None | Some(1)) => Ok(Lz4Compressor(CompressionMode::DEFAULT)),
+            (0, Some(acceleration @ 2..=MAX_ACCELERATION)) => {
+                Ok(Lz4Compressor(CompressionMode::FAST(acceleration as i32)))
+            }
+            (0, Some(acceleration)) => Err(invalid_input(format!(
+                "lz4 acceleration must be between 1 and {}, got {}",
+                MAX_ACCELERATION, acceleration
+            ))),
+            (1..=12, None) => Ok(Lz4Compressor(CompressionMode::HIGHCOMPRESSION(compression))),
+            (1..=12, Some(_)) => Err(invalid_input(
+                "lz4 acceleration applies only to the fast mode at level 0".to_owned(),
+            )),
+            _ => Err(invalid_input(format!(
+                "lz4 level {} is not supported, the highest level is 12",
+                compression
+            ))),
         }
     }
 }
 
 pub struct Lz4Decompressor;
 
+const FRAME_CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: true,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+/// Size of blocks within an LZ4 frame, as selected by `BlockSize::Default`
+const FRAME_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Compresses each chunk into a standalone LZ4 frame
+pub struct Lz4FrameCompressor(pub u32);
+
+impl Lz4FrameCompressor {
+    pub(crate) fn new(compression: i32) -> Lz4FrameCompressor {
+        Lz4FrameCompressor(compression.clamp(0, 16) as u32)
+    }
+}
+
+pub struct Lz4FrameDecompressor;
+
 impl Encoder for Lz4Compressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        ENCODER_CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         lz4::block::compress_bound(uncompressed_len).unwrap()
     }
@@ -27,7 +105,226 @@ impl Encoder for Lz4Compressor {
 }
 
 impl Decoder for Lz4Decompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
         lz4::block::decompress_to_buffer(src, Some(dest.len() as i32), dest)
     }
 }
+
+impl Encoder for Lz4FrameCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        FRAME_CAPABILITIES
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        // Incompressible blocks are stored as is, so each block adds only its size and checksum.
+        // The frame adds a header of at most 19 bytes, the end mark and the content checksum.
+        let block_count = uncompressed_len.div_ceil(FRAME_BLOCK_SIZE).max(1);
+        uncompressed_len + block_count * 8 + 19 + 8
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let w = Cursor::new(dest);
+        let mut encoder = lz4::EncoderBuilder::new().level(self.0).build(w)?;
+        encoder.write_all(src)?;
+        let (mut w, result) = encoder.finish();
+        result?;
+        Ok(w.stream_position()? as usize)
+    }
+}
+
+impl Decoder for Lz4FrameDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        FRAME_CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let mut decoder = lz4::Decoder::new(src)?;
+        decoder.read_exact(dest)?;
+        Ok(dest.len())
+    }
+}
+
+// Part of the liblz4 linked by lz4-sys, but not declared by it.
+// Declared to match liblz4 1.10 bundled by lz4-sys; the lz4-dict feature allows building without them.
+#[cfg(feature = "lz4-dict")]
+extern "C" {
+    fn LZ4_createStream() -> *mut c_void;
+    fn LZ4_freeStream(stream: *mut c_void) -> c_int;
+    fn LZ4_loadDict(stream: *mut c_void, dictionary: *const c_char, dict_size: c_int) -> c_int;
+    fn LZ4_compress_fast_continue(
+        stream: *mut c_void,
+        src: *const c_char,
+        dst: *mut c_char,
+        src_size: c_int,
+        dst_capacity: c_int,
+        acceleration: c_int,
+    ) -> c_int;
+    fn LZ4_createStreamHC() -> *mut c_void;
+    fn LZ4_freeStreamHC(stream: *mut c_void) -> c_int;
+    fn LZ4_resetStreamHC_fast(stream: *mut c_void, compression_level: c_int);
+    fn LZ4_loadDictHC(stream: *mut c_void, dictionary: *const c_char, dict_size: c_int) -> c_int;
+    fn LZ4_compress_HC_continue(
+        stream: *mut c_void,
+        src: *const c_char,
+        dst: *mut c_char,
+        src_size: c_int,
+        dst_capacity: c_int,
+    ) -> c_int;
+}
+
+/// LZ4 can reference at most the last 64 kB of a dictionary
+const MAX_DICT_SIZE: usize = 64 * 1024;
+
+/// Compresses each chunk as an LZ4 block referencing the dictionary.
+/// The dictionary is loaded again before each chunk, so chunks stay independent.
+#[cfg(feature = "lz4-dict")]
+pub struct Lz4DictCompressor {
+    mode: CompressionMode,
+    dict: Vec<u8>,
+    /// `LZ4_stream_t` in the fast mode or `LZ4_streamHC_t` in the HC mode
+    stream: *mut c_void,
+}
+
+#[cfg(feature = "lz4-dict")]
+impl Lz4DictCompressor {
+    /// Accepts the same levels and accelerations as [`Lz4Compressor::new`]
+    pub(crate) fn new(
+        compression: i32,
+        acceleration: Option<u32>,
+        dict: &[u8],
+    ) -> io::Result<Lz4DictCompressor> {
+        let mode = Lz4Compressor::new(compression, acceleration)?.0;
+        let stream = unsafe {
+            match mode {
+                CompressionMode::HIGHCOMPRESSION(_) => LZ4_createStreamHC(),
+                _ => LZ4_createStream(),
+            }
+        };
+        if stream.is_null() {
+            return Err(out_of_memory());
+        }
+        let dict = dict[dict.len().saturating_sub(MAX_DICT_SIZE)..].to_vec();
+        Ok(Lz4DictCompressor { mode, dict, stream })
+    }
+}
+
+#[cfg(feature = "lz4-dict")]
+impl Drop for Lz4DictCompressor {
+    fn drop(&mut self) {
+        unsafe {
+            match self.mode {
+                CompressionMode::HIGHCOMPRESSION(_) => LZ4_freeStreamHC(self.stream),
+                _ => LZ4_freeStream(self.stream),
+            };
+        }
+    }
+}
+
+#[cfg(feature = "lz4-dict")]
+impl Encoder for Lz4DictCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        lz4::block::compress_bound(uncompressed_len).unwrap()
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        if src.len() > MAX_INPUT_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "Input too large"));
+        }
+        let dict = self.dict.as_ptr() as *const c_char;
+        let dict_len = self.dict.len() as c_int;
+        let src_ptr = src.as_ptr() as *const c_char;
+        let dest_ptr = dest.as_mut_ptr() as *mut c_char;
+        let src_len = src.len() as c_int;
+        let dest_len = dest.len().min(c_int::MAX as usize) as c_int;
+        let result = unsafe {
+            match self.mode {
+                CompressionMode::HIGHCOMPRESSION(level) => {
+                    LZ4_resetStreamHC_fast(self.stream, level);
+                    LZ4_loadDictHC(self.stream, dict, dict_len);
+                    LZ4_compress_HC_continue(self.stream, src_ptr, dest_ptr, src_len, dest_len)
+                }
+                CompressionMode::FAST(acceleration) => {
+                    LZ4_loadDict(self.stream, dict, dict_len);
+                    LZ4_compress_fast_continue(
+                        self.stream,
+                        src_ptr,
+                        dest_ptr,
+                        src_len,
+                        dest_len,
+                        acceleration,
+                    )
+                }
+                CompressionMode::DEFAULT => {
+                    LZ4_loadDict(self.stream, dict, dict_len);
+                    LZ4_compress_fast_continue(self.stream, src_ptr, dest_ptr, src_len, dest_len, 1)
+                }
+            }
+        };
+        if result <= 0 {
+            return Err(io::Error::new(ErrorKind::Other, "Failed to compress"));
+        }
+        Ok(result as usize)
+    }
+}
+
+/// Decompresses LZ4 blocks referencing the dictionary
+pub struct Lz4DictDecompressor {
+    dict: Vec<u8>,
+    stream: *mut LZ4StreamDecode,
+}
+
+impl Lz4DictDecompressor {
+    pub(crate) fn new(dict: &[u8]) -> io::Result<Lz4DictDecompressor> {
+        let dict = dict[dict.len().saturating_sub(MAX_DICT_SIZE)..].to_vec();
+        let stream = unsafe { LZ4_createStreamDecode() };
+        if stream.is_null() {
+            return Err(out_of_memory());
+        }
+        Ok(Lz4DictDecompressor { dict, stream })
+    }
+}
+
+impl Drop for Lz4DictDecompressor {
+    fn drop(&mut self) {
+        unsafe { LZ4_freeStreamDecode(self.stream) };
+    }
+}
+
+impl Decoder for Lz4DictDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        // Setting the dictionary resets the stream, so each chunk is decoded independently
+        let result = unsafe {
+            LZ4_setStreamDecode(self.stream, self.dict.as_ptr(), self.dict.len() as c_int);
+            LZ4_decompress_safe_continue(
+                self.stream,
+                src.as_ptr(),
+                dest.as_mut_ptr(),
+                src.len().min(c_int::MAX as usize) as c_int,
+                dest.len().min(c_int::MAX as usize) as c_int,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Decompression failed. Input invalid or too long?",
+            ));
+        }
+        Ok(result as usize)
+    }
+}
+
+fn out_of_memory() -> io::Error {
+    io::Error::new(ErrorKind::OutOfMemory, "Failed to allocate the LZ4 stream")
+}
@@ -1,4 +1,4 @@
-use crate::codec::{Decoder, Encoder};
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
 use std::ffi::c_int;
 use std::io::ErrorKind;
 
@@ -18,7 +18,18 @@ impl LzavCompressor {
 
 pub struct LzavDecompressor;
 
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: false,
+    max_block_size: Some(c_int::MAX as usize),
+    multi_threaded: false,
+};
+
 impl Encoder for LzavCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         unsafe {
             match self.0 {
@@ -29,6 +40,12 @@ impl Encoder for LzavCompressor {
     }
 
     fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> std::io::Result<usize> {
+        if dest.len() < self.compressed_len_bound(src.len()) {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "lzav output buffer too small",
+            ));
+        }
         unsafe {
             let count = match self.0 {
                 CompressionLevel::Default => lzav::compress_default(
@@ -44,10 +61,15 @@ impl Encoder for LzavCompressor {
                     dest.len() as c_int,
                 )
             };
-            if count == 0 {
+            // LZAV reports no reason, but fails only for empty or too large inputs
+            if count <= 0 {
                 Err(std::io::Error::new(
-                    ErrorKind::Other,
-                    "lzav compress failed",
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "lzav failed to compress {} bytes into a buffer of {} bytes",
+                        src.len(),
+                        dest.len()
+                    ),
                 ))
             } else {
                 Ok(count as usize)
@@ -57,6 +79,10 @@ impl Encoder for LzavCompressor {
 }
 
 impl Decoder for LzavDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> std::io::Result<usize> {
         unsafe {
             let count = lzav::decompress(
@@ -65,10 +91,10 @@ impl Decoder for LzavDecompressor {
                 src.len() as c_int,
                 dest.len() as c_int,
             );
-            if count == 0 {
+            if count <= 0 {
                 Err(std::io::Error::new(
-                    ErrorKind::Other,
-                    "lzav decompress failed",
+                    ErrorKind::InvalidData,
+                    format!("lzav decompress failed: {}", error_message(count)),
                 ))
             } else {
                 Ok(count as usize)
@@ -76,3 +102,25 @@ impl Decoder for LzavDecompressor {
         }
     }
 }
+
+// Error codes of `lzav_decompress`, as defined in lzav.h; the lzav crate exports only functions
+const LZAV_E_PARAMS: c_int = -1;
+const LZAV_E_SRCOOB: c_int = -2;
+const LZAV_E_DSTOOB: c_int = -3;
+const LZAV_E_REFOOB: c_int = -4;
+const LZAV_E_DSTLEN: c_int = -5;
+const LZAV_E_UNKFMT: c_int = -6;
+
+/// Describes the error codes returned by `lzav_decompress`
+fn error_message(code: c_int) -> String {
+    match code {
+        0 => "no data decompressed".to_owned(),
+        LZAV_E_PARAMS => "invalid arguments".to_owned(),
+        LZAV_E_SRCOOB => "the compressed data is truncated".to_owned(),
+        LZAV_E_DSTOOB => "the output buffer is too small".to_owned(),
+        LZAV_E_REFOOB => "a back reference points before the start of the data".to_owned(),
+        LZAV_E_DSTLEN => "the decompressed length differs from the expected one".to_owned(),
+        LZAV_E_UNKFMT => "unknown stream format".to_owned(),
+        _ => format!("error code {}", code),
+    }
+}
@@ -1,12 +1,47 @@
-use crate::codec::{Decoder, Encoder};
-use lzma_sys::lzma_stream_buffer_bound;
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
+use lzma_sys::{lzma_stream_buffer_bound, LZMA_PRESET_EXTREME};
 use std::io;
 use std::io::{Cursor, Read, Seek, Write};
+use xz2::stream::{LzmaOptions, Stream};
 
 pub struct LzmaCompressor(pub u32);
 pub struct LzmaDecompressor;
 
+/// Compresses each chunk into the legacy .lzma format of LZMA Utils, also known as lzma_alone
+pub struct LzmaAloneCompressor(pub u32);
+pub struct LzmaAloneDecompressor;
+
+impl LzmaCompressor {
+    /// Creates a compressor using the xz preset 0-9, optionally in the extreme variant
+    pub(crate) fn new(compression: i32, extreme: bool) -> Self {
+        let preset = compression.clamp(0, 9) as u32;
+        if extreme {
+            LzmaCompressor(preset | LZMA_PRESET_EXTREME)
+        } else {
+            LzmaCompressor(preset)
+        }
+    }
+}
+
+impl LzmaAloneCompressor {
+    /// Creates a compressor using the xz preset 0-9, optionally in the extreme variant
+    pub(crate) fn new(compression: i32, extreme: bool) -> Self {
+        LzmaAloneCompressor(LzmaCompressor::new(compression, extreme).0)
+    }
+}
+
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: true,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
 impl Encoder for LzmaCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         unsafe { lzma_stream_buffer_bound(uncompressed_len) }
     }
@@ -21,6 +56,10 @@ impl Encoder for LzmaCompressor {
 }
 
 impl Decoder for LzmaDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
         let r = Cursor::new(src);
         let mut decoder = xz2::read::XzDecoder::new(r);
@@ -28,3 +67,39 @@ impl Decoder for LzmaDecompressor {
         Ok(dest.len())
     }
 }
+
+impl Encoder for LzmaAloneCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        // Unlike LZMA2 used by xz, LZMA1 can't store incompressible data as is,
+        // so it expands it slightly. The header takes 13 bytes and the end marker up to 6.
+        uncompressed_len + uncompressed_len / 32 + 64
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let options = LzmaOptions::new_preset(self.0)?;
+        let stream = Stream::new_lzma_encoder(&options)?;
+        let w = Cursor::new(dest);
+        let mut encoder = xz2::write::XzEncoder::new_stream(w, stream);
+        encoder.write_all(src)?;
+        let mut w = encoder.finish()?;
+        Ok(w.stream_position()? as usize)
+    }
+}
+
+impl Decoder for LzmaAloneDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let stream = Stream::new_lzma_decoder(u64::MAX)?;
+        let r = Cursor::new(src);
+        let mut decoder = xz2::read::XzDecoder::new_stream(r, stream);
+        decoder.read_exact(dest)?;
+        Ok(dest.len())
+    }
+}
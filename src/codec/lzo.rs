@@ -0,0 +1,84 @@
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
+use rust_lzo::{LZOContext, LZOError};
+use std::io;
+use std::io::ErrorKind;
+
+/// LZO1X-1, the fastest variant of LZO, compressing with the work memory kept between chunks
+pub struct LzoCompressor(LZOContext);
+
+impl LzoCompressor {
+    pub(crate) fn new() -> Self {
+        LzoCompressor(LZOContext::new())
+    }
+}
+
+pub struct LzoDecompressor;
+
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: false,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+impl Encoder for LzoCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    /// Worst case expansion of LZO1X, as documented by the LZO library
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        uncompressed_len + uncompressed_len / 16 + 64 + 3
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        if dest.len() < self.compressed_len_bound(src.len()) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Output buffer too small",
+            ));
+        }
+        let (compressed, result) = self.0.compress_to_slice(src, dest);
+        if result != LZOError::OK {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!("Failed to compress: {}", error_message(result)),
+            ));
+        }
+        Ok(compressed.len())
+    }
+}
+
+impl Decoder for LzoDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        let (decompressed, result) = LZOContext::decompress_to_slice(src, dest);
+        if result != LZOError::OK {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to decompress: {}", error_message(result)),
+            ));
+        }
+        Ok(decompressed.len())
+    }
+}
+
+/// Describes the errors returned by the LZO functions
+fn error_message(error: LZOError) -> &'static str {
+    match error {
+        LZOError::OK => "no error",
+        LZOError::ERROR => "the data is corrupted",
+        LZOError::OUT_OF_MEMORY => "out of memory",
+        LZOError::NOT_COMPRESSIBLE => "the data is not compressible",
+        LZOError::INPUT_OVERRUN => "the compressed data is truncated",
+        LZOError::OUTPUT_OVERRUN => "the output buffer is too small",
+        LZOError::LOOKBEHIND_OVERRUN => "a back reference points before the start of the data",
+        LZOError::EOF_NOT_FOUND => "the compressed data has no end marker",
+        LZOError::INPUT_NOT_CONSUMED => "the compressed data has trailing bytes",
+        LZOError::NOT_YET_IMPLEMENTED => "the operation is not implemented",
+        LZOError::INVALID_ARGUMENT => "invalid arguments",
+    }
+}
@@ -1,18 +1,49 @@
 use std::io;
 
 pub mod brotli;
+pub mod bzip2;
 pub mod copy;
 pub mod lz4;
 pub mod lzav;
 pub mod lzma;
+pub mod lzo;
+pub mod registry;
 pub mod snappy;
+pub mod zlib;
 pub mod zstd;
 
+/// Static properties of a codec, used to validate options and to describe it to the user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CodecCapabilities {
+    /// Can make use of a dictionary passed with `--dict`
+    pub dictionary: bool,
+    /// Each compressed frame starts with a header identifying its format
+    pub self_describing: bool,
+    /// Largest chunk the codec can process in one call, if limited
+    pub max_block_size: Option<usize>,
+    /// Uses more than one thread to process a chunk
+    pub multi_threaded: bool,
+}
+
 pub trait Encoder {
+    fn capabilities(&self) -> CodecCapabilities {
+        CodecCapabilities::default()
+    }
+    /// Largest chunk that can be passed to `compress`.
+    /// Limited by the codec and by the frame length limit of [`crate::MAX_FRAME_LEN`].
+    fn max_chunk_size(&self) -> usize {
+        match self.capabilities().max_block_size {
+            Some(max_block_size) => max_block_size.min(crate::MAX_FRAME_LEN),
+            None => crate::MAX_FRAME_LEN,
+        }
+    }
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize;
     fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize>;
 }
 
 pub trait Decoder {
+    fn capabilities(&self) -> CodecCapabilities {
+        CodecCapabilities::default()
+    }
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize>;
 }
@@ -0,0 +1,261 @@
+//! Properties and factories of all supported codecs, kept together in one entry per codec.
+//! Adding a codec takes a variant of [`Algorithm`] and an entry in [`CODECS`].
+
+use crate::codec::{brotli, bzip2, copy, lz4, lzav, lzma, lzo, zlib, zstd, Decoder, Encoder};
+use crate::{Algorithm, CodecOptions};
+use std::ops::RangeInclusive;
+
+/// Creates an encoder for the compression level, optionally using the dictionary
+pub type EncoderFactory = fn(i32, Option<&[u8]>, &CodecOptions) -> anyhow::Result<Box<dyn Encoder>>;
+
+/// Creates a decoder, optionally using the dictionary
+pub type DecoderFactory = fn(Option<&[u8]>, &CodecOptions) -> anyhow::Result<Box<dyn Decoder>>;
+
+/// Registry entry of a codec
+pub struct Codec {
+    pub algorithm: Algorithm,
+    /// Identifier of the algorithm recorded in the header of compressed streams.
+    /// Must never change or be reused, 0 marks streams without a recorded algorithm.
+    pub id: u8,
+    /// Extension of compressed files, without the dot
+    pub extension: &'static str,
+    /// All compression levels accepted by the codec.
+    /// Codecs without compression levels accept only 0.
+    pub level_range: fn() -> RangeInclusive<i32>,
+    /// Compression levels used by benchmarks, a representative subset of `level_range`
+    pub benchmark_levels: fn() -> Vec<i32>,
+    /// Levels of the fast, default and best [`crate::Preset`]
+    pub preset_levels: [i32; 3],
+    /// Chunk size giving a good balance of compression ratio and speed
+    pub recommended_chunk_size: usize,
+    /// Favors speed over the compression ratio, with LZ77 matching only and no entropy coding
+    pub fast: bool,
+    pub new_encoder: EncoderFactory,
+    pub new_decoder: DecoderFactory,
+}
+
+pub static CODECS: [Codec; 12] = [
+    Codec {
+        algorithm: Algorithm::Copy,
+        id: 1,
+        extension: "bak",
+        level_range: || 0..=0,
+        benchmark_levels: || vec![0],
+        preset_levels: [0, 0, 0],
+        recommended_chunk_size: 16 * 1024,
+        fast: true,
+        new_encoder: |_, _, _| Ok(Box::new(copy::Copy)),
+        new_decoder: |_, _| Ok(Box::new(copy::Copy)),
+    },
+    Codec {
+        algorithm: Algorithm::Lz4,
+        id: 2,
+        extension: "lz4",
+        level_range: || 0..=12,
+        benchmark_levels: || Vec::from_iter(0..=12),
+        preset_levels: [0, 0, 12],
+        recommended_chunk_size: 64 * 1024,
+        fast: true,
+        new_encoder: |level, dict, options| match dict {
+            #[cfg(feature = "lz4-dict")]
+            Some(dict) => Ok(Box::new(lz4::Lz4DictCompressor::new(
+                level,
+                options.lz4_acceleration,
+                dict,
+            )?)),
+            _ => Ok(Box::new(lz4::Lz4Compressor::new(
+                level,
+                options.lz4_acceleration,
+            )?)),
+        },
+        new_decoder: |dict, _| match dict {
+            None => Ok(Box::new(lz4::Lz4Decompressor)),
+            Some(dict) => Ok(Box::new(lz4::Lz4DictDecompressor::new(dict)?)),
+        },
+    },
+    Codec {
+        algorithm: Algorithm::Lz4Frame,
+        id: 3,
+        extension: "lz4f",
+        level_range: || 0..=12,
+        benchmark_levels: || Vec::from_iter(0..=12),
+        preset_levels: [0, 0, 12],
+        recommended_chunk_size: 64 * 1024,
+        fast: true,
+        new_encoder: |level, _, _| Ok(Box::new(lz4::Lz4FrameCompressor::new(level))),
+        new_decoder: |_, _| Ok(Box::new(lz4::Lz4FrameDecompressor)),
+    },
+    Codec {
+        algorithm: Algorithm::Zstd,
+        id: 4,
+        extension: "zstd",
+        level_range: || ::zstd::zstd_safe::min_c_level()..=::zstd::zstd_safe::max_c_level(),
+        benchmark_levels: || Vec::from_iter((-7..=-1).chain(1..=19)),
+        preset_levels: [-3, 3, zstd::MAX_REGULAR_LEVEL],
+        recommended_chunk_size: 256 * 1024,
+        fast: false,
+        new_encoder: |level, dict, options| {
+            let dict = zstd::dictionary_with_id(dict, options.zstd_dict_id)?;
+            Ok(Box::new(zstd::new_compressor(
+                level,
+                dict.as_deref(),
+                options.zstd_long,
+                options.zstd_ultra,
+            )?))
+        },
+        new_decoder: |dict, options| {
+            let dict = zstd::dictionary_with_id(dict, options.zstd_dict_id)?;
+            Ok(Box::new(zstd::new_decompressor(
+                dict.as_deref(),
+                options.zstd_long,
+            )?))
+        },
+    },
+    Codec {
+        algorithm: Algorithm::Brotli,
+        id: 5,
+        extension: "br",
+        level_range: || 0..=11,
+        benchmark_levels: || Vec::from_iter(1..=8),
+        preset_levels: [1, 6, 11],
+        recommended_chunk_size: 256 * 1024,
+        fast: false,
+        new_encoder: |level, dict, options| match dict {
+            None => Ok(Box::new(brotli::BrotliCompressor::new(
+                level,
+                options.brotli_window,
+                options.brotli_mode,
+            ))),
+            Some(dict) => Ok(Box::new(brotli::BrotliDictCompressor::new(
+                level as u32,
+                options.brotli_window,
+                options.brotli_mode,
+                dict,
+            )?)),
+        },
+        new_decoder: |dict, options| {
+            let large_window = brotli::is_large_window(options.brotli_window);
+            match dict {
+                None => Ok(Box::new(brotli::BrotliDecompressor { large_window })),
+                Some(dict) => Ok(Box::new(brotli::BrotliDictDecompressor::new(
+                    dict,
+                    large_window,
+                ))),
+            }
+        },
+    },
+    Codec {
+        algorithm: Algorithm::Snappy,
+        id: 6,
+        extension: "sz",
+        level_range: || 0..=0,
+        benchmark_levels: || vec![0],
+        preset_levels: [0, 0, 0],
+        recommended_chunk_size: 64 * 1024,
+        fast: true,
+        new_encoder: |_, _, _| Ok(Box::new(snap::raw::Encoder::new())),
+        new_decoder: |_, _| Ok(Box::new(snap::raw::Decoder::new())),
+    },
+    Codec {
+        algorithm: Algorithm::Lzma,
+        id: 7,
+        extension: "xz",
+        level_range: || 0..=9,
+        benchmark_levels: || Vec::from_iter(0..=9),
+        preset_levels: [0, 6, 9],
+        recommended_chunk_size: 1024 * 1024,
+        fast: false,
+        new_encoder: |level, _, options| {
+            Ok(Box::new(lzma::LzmaCompressor::new(
+                level,
+                options.lzma_extreme,
+            )))
+        },
+        new_decoder: |_, _| Ok(Box::new(lzma::LzmaDecompressor)),
+    },
+    Codec {
+        algorithm: Algorithm::Lzav,
+        id: 8,
+        extension: "lzav",
+        level_range: || 0..=1,
+        benchmark_levels: || vec![0, 1],
+        preset_levels: [0, 0, 1],
+        recommended_chunk_size: 64 * 1024,
+        fast: true,
+        new_encoder: |level, _, _| Ok(Box::new(lzav::LzavCompressor::new(level as u32))),
+        new_decoder: |_, _| Ok(Box::new(lzav::LzavDecompressor)),
+    },
+    Codec {
+        algorithm: Algorithm::Bzip2,
+        id: 9,
+        extension: "bz2",
+        level_range: || 1..=9,
+        benchmark_levels: || Vec::from_iter(1..=9),
+        preset_levels: [1, 9, 9],
+        recommended_chunk_size: 900 * 1000,
+        fast: false,
+        new_encoder: |level, _, _| Ok(Box::new(bzip2::Bzip2Compressor::new(level))),
+        new_decoder: |_, _| Ok(Box::new(bzip2::Bzip2Decompressor)),
+    },
+    Codec {
+        algorithm: Algorithm::Zlib,
+        id: 10,
+        extension: "zz",
+        level_range: || 0..=9,
+        benchmark_levels: || Vec::from_iter(1..=9),
+        preset_levels: [1, 6, 9],
+        recommended_chunk_size: 128 * 1024,
+        fast: false,
+        new_encoder: |level, _, _| Ok(Box::new(zlib::ZlibCompressor::new(level))),
+        new_decoder: |_, _| Ok(Box::new(zlib::ZlibDecompressor::new())),
+    },
+    Codec {
+        algorithm: Algorithm::LzmaAlone,
+        id: 11,
+        extension: "lzma",
+        level_range: || 0..=9,
+        benchmark_levels: || Vec::from_iter(0..=9),
+        preset_levels: [0, 6, 9],
+        recommended_chunk_size: 1024 * 1024,
+        fast: false,
+        new_encoder: |level, _, options| {
+            Ok(Box::new(lzma::LzmaAloneCompressor::new(
+                level,
+                options.lzma_extreme,
+            )))
+        },
+        new_decoder: |_, _| Ok(Box::new(lzma::LzmaAloneDecompressor)),
+    },
+    Codec {
+        algorithm: Algorithm::Lzo,
+        id: 12,
+        extension: "lzo",
+        level_range: || 0..=0,
+        benchmark_levels: || vec![0],
+        preset_levels: [0, 0, 0],
+        recommended_chunk_size: 64 * 1024,
+        fast: true,
+        new_encoder: |_, _, _| Ok(Box::new(lzo::LzoCompressor::new())),
+        new_decoder: |_, _| Ok(Box::new(lzo::LzoDecompressor)),
+    },
+];
+
+/// Returns the registry entry of the algorithm
+pub fn codec(algorithm: Algorithm) -> &'static Codec {
+    CODECS
+        .iter()
+        .find(|codec| codec.algorithm == algorithm)
+        .expect("every algorithm has a registry entry")
+}
+
+/// Returns the registry entry of the codec with the identifier recorded in stream headers
+pub fn find_by_id(id: u8) -> Option<&'static Codec> {
+    CODECS.iter().find(|codec| codec.id == id)
+}
+
+/// Returns the registry entry of the codec writing files with the given extension, ignoring case
+pub fn find_by_extension(extension: &str) -> Option<&'static Codec> {
+    CODECS
+        .iter()
+        .find(|codec| codec.extension.eq_ignore_ascii_case(extension))
+}
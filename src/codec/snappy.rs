@@ -1,8 +1,19 @@
-use crate::codec::{Decoder, Encoder};
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
 use std::io;
 use std::io::ErrorKind;
 
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: false,
+    max_block_size: Some(u32::MAX as usize),
+    multi_threaded: false,
+};
+
 impl Encoder for snap::raw::Encoder {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
         snap::raw::max_compress_len(uncompressed_len)
     }
@@ -14,6 +25,10 @@ impl Encoder for snap::raw::Encoder {
 }
 
 impl Decoder for snap::raw::Decoder {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
         snap::raw::Decoder::decompress(self, src, dest)
             .map_err(|e| io::Error::new(ErrorKind::Other, e))
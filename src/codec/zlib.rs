@@ -0,0 +1,63 @@
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use std::io;
+use std::io::ErrorKind;
+
+/// Deflate with the zlib header and adler32 trailer, as used by HTTP `deflate` encoding and PNG
+pub struct ZlibCompressor(Compress);
+
+impl ZlibCompressor {
+    pub(crate) fn new(compression: i32) -> Self {
+        let level = Compression::new(compression.clamp(0, 9) as u32);
+        ZlibCompressor(Compress::new(level, true))
+    }
+}
+
+pub struct ZlibDecompressor(Decompress);
+
+impl ZlibDecompressor {
+    pub(crate) fn new() -> Self {
+        ZlibDecompressor(Decompress::new(true))
+    }
+}
+
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: false,
+    self_describing: true,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+impl Encoder for ZlibCompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        // The fast levels of miniz_oxide don't fall back to stored blocks,
+        // so allow for 9 bits per literal, plus block headers and the zlib wrapper
+        uncompressed_len + uncompressed_len / 8 + 64
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        self.0.reset();
+        match self.0.compress(src, dest, FlushCompress::Finish)? {
+            Status::StreamEnd => Ok(self.0.total_out() as usize),
+            _ => Err(io::Error::new(ErrorKind::Other, "Failed to compress")),
+        }
+    }
+}
+
+impl Decoder for ZlibDecompressor {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
+    fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        self.0.reset(true);
+        match self.0.decompress(src, dest, FlushDecompress::Finish)? {
+            Status::StreamEnd => Ok(self.0.total_out() as usize),
+            _ => Err(io::Error::new(ErrorKind::Other, "Failed to decompress")),
+        }
+    }
+}
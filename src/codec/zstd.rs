@@ -1,8 +1,178 @@
-use crate::codec::{Decoder, Encoder};
+use crate::codec::{CodecCapabilities, Decoder, Encoder};
+use std::borrow::Cow;
+use std::ffi::CStr;
 use std::io;
+use std::io::ErrorKind;
+use zstd::bulk::{Compressor, Decompressor};
 use zstd::zstd_safe;
+use zstd::zstd_safe::zstd_sys;
+use zstd::zstd_safe::{CParameter, DParameter};
+
+const CAPABILITIES: CodecCapabilities = CodecCapabilities {
+    dictionary: true,
+    self_describing: true,
+    max_block_size: None,
+    multi_threaded: false,
+};
+
+/// Magic number at the start of dictionaries in the zstd format, followed by the dictionary id
+const DICT_MAGIC: [u8; 4] = 0xEC30A437_u32.to_le_bytes();
+
+/// Replaces the id of a dictionary in the zstd format, which is also written to frame headers.
+/// Returns the dictionary unchanged if no id is given.
+pub fn dictionary_with_id(
+    dict: Option<&[u8]>,
+    dict_id: Option<u32>,
+) -> io::Result<Option<Cow<'_, [u8]>>> {
+    let Some(dict_id) = dict_id else {
+        return Ok(dict.map(Cow::Borrowed));
+    };
+    let invalid_input = |message: &str| io::Error::new(ErrorKind::InvalidInput, message);
+    let Some(dict) = dict else {
+        return Err(invalid_input(
+            "Setting the dictionary id requires a dictionary",
+        ));
+    };
+    if dict_id == 0 {
+        return Err(invalid_input(
+            "Dictionary id 0 is reserved for no dictionary",
+        ));
+    }
+    if dict.len() < 8 || dict[0..4] != DICT_MAGIC {
+        return Err(invalid_input(
+            "The dictionary id can be set only for dictionaries in the zstd format",
+        ));
+    }
+    let mut dict = dict.to_vec();
+    dict[4..8].copy_from_slice(&dict_id.to_le_bytes());
+    Ok(Some(Cow::Owned(dict)))
+}
+
+/// Parameters of the COVER dictionary training algorithm.
+/// Parameters left as `None` are chosen by training with several values
+/// and keeping the best dictionary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoverParams {
+    /// Length of the segments selected for the dictionary
+    pub k: Option<u32>,
+    /// Length of the substrings, called dmers, the segments are scored by
+    pub d: Option<u32>,
+    /// Number of values of k tried if k is not given
+    pub steps: Option<u32>,
+}
+
+/// Trains a dictionary of at most `dict_size` bytes from the samples with the COVER algorithm.
+/// Returns the dictionary and the parameters it was trained with, including the chosen ones.
+pub fn train_cover<S: AsRef<[u8]>>(
+    samples: &[S],
+    dict_size: usize,
+    params: CoverParams,
+) -> io::Result<(Vec<u8>, CoverParams)> {
+    let sample_sizes: Vec<usize> = samples.iter().map(|s| s.as_ref().len()).collect();
+    let samples: Vec<u8> = samples.iter().flat_map(|s| s.as_ref()).copied().collect();
+    let sample_count = u32::try_from(sample_sizes.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Too many samples"))?;
+    let mut parameters = zstd_sys::ZDICT_cover_params_t {
+        k: params.k.unwrap_or(0),
+        d: params.d.unwrap_or(0),
+        steps: params.steps.unwrap_or(0),
+        nbThreads: 1,
+        splitPoint: 0.0,
+        shrinkDict: 0,
+        shrinkDictMaxRegression: 0,
+        zParams: zstd_sys::ZDICT_params_t {
+            compressionLevel: 0,
+            notificationLevel: 0,
+            dictID: 0,
+        },
+    };
+    let mut dict = vec![0u8; dict_size];
+    // Parameters that are set are kept, the others are optimized
+    let result = unsafe {
+        zstd_sys::ZDICT_optimizeTrainFromBuffer_cover(
+            dict.as_mut_ptr().cast(),
+            dict.len(),
+            samples.as_ptr().cast(),
+            sample_sizes.as_ptr(),
+            sample_count,
+            &mut parameters,
+        )
+    };
+    if unsafe { zstd_sys::ZDICT_isError(result) } != 0 {
+        let name = unsafe { CStr::from_ptr(zstd_sys::ZDICT_getErrorName(result)) };
+        return Err(io::Error::other(name.to_string_lossy().into_owned()));
+    }
+    dict.truncate(result);
+    let params = CoverParams {
+        k: Some(parameters.k),
+        d: Some(parameters.d),
+        steps: Some(parameters.steps),
+    };
+    Ok((dict, params))
+}
+
+/// Highest level allowed without ultra mode, as in the zstd command line tool
+pub const MAX_REGULAR_LEVEL: i32 = 19;
+
+/// Creates a compressor, optionally with long distance matching enabled using the given window log.
+/// Levels above [`MAX_REGULAR_LEVEL`] are accepted only in ultra mode.
+pub fn new_compressor(
+    level: i32,
+    dict: Option<&[u8]>,
+    long_window_log: Option<u32>,
+    ultra: bool,
+) -> io::Result<Compressor<'static>> {
+    let min_level = zstd_safe::min_c_level();
+    let max_level = if ultra {
+        zstd_safe::max_c_level()
+    } else {
+        MAX_REGULAR_LEVEL
+    };
+    if !(min_level..=max_level).contains(&level) {
+        let hint = if ultra {
+            ""
+        } else {
+            ", higher levels need ultra mode"
+        };
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "zstd compression level {} is out of range {}..={}{}",
+                level, min_level, max_level, hint
+            ),
+        ));
+    }
+    let mut compressor = match dict {
+        None => Compressor::new(level)?,
+        Some(dict) => Compressor::with_dictionary(level, dict)?,
+    };
+    if let Some(window_log) = long_window_log {
+        compressor.set_parameter(CParameter::EnableLongDistanceMatching(true))?;
+        compressor.set_parameter(CParameter::WindowLog(window_log))?;
+    }
+    Ok(compressor)
+}
+
+/// Creates a decompressor accepting windows up to the given window log
+pub fn new_decompressor(
+    dict: Option<&[u8]>,
+    long_window_log: Option<u32>,
+) -> io::Result<Decompressor<'static>> {
+    let mut decompressor = match dict {
+        None => Decompressor::new()?,
+        Some(dict) => Decompressor::with_dictionary(dict)?,
+    };
+    if let Some(window_log) = long_window_log {
+        decompressor.set_parameter(DParameter::WindowLogMax(window_log))?;
+    }
+    Ok(decompressor)
+}
+
+impl Encoder for Compressor<'_> {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
 
-impl Encoder for zstd::bulk::Compressor<'_> {
     fn compressed_len_bound(&mut self, src_len: usize) -> usize {
         zstd_safe::compress_bound(src_len)
     }
@@ -12,7 +182,11 @@ impl Encoder for zstd::bulk::Compressor<'_> {
     }
 }
 
-impl Decoder for zstd::bulk::Decompressor<'_> {
+impl Decoder for Decompressor<'_> {
+    fn capabilities(&self) -> CodecCapabilities {
+        CAPABILITIES
+    }
+
     fn decompress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
         self.decompress_to_buffer(src, dest)
     }
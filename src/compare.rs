@@ -0,0 +1,64 @@
+use crate::seek_position;
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+
+/// A writer that compares written data with the expected content instead of storing it
+pub struct Compare<'a> {
+    expected: &'a [u8],
+    pos: u64,
+    max_pos: u64,
+    first_mismatch: Option<u64>,
+}
+
+impl<'a> Compare<'a> {
+    pub fn new(expected: &'a [u8]) -> Self {
+        Compare {
+            expected,
+            pos: 0,
+            max_pos: 0,
+            first_mismatch: None,
+        }
+    }
+
+    /// Returns the offset of the first byte that differs from the expected content.
+    /// Data shorter or longer than expected is reported as a mismatch at the end of the shorter one.
+    pub fn first_mismatch(&self) -> Option<u64> {
+        let expected_len = self.expected.len() as u64;
+        match self.first_mismatch {
+            Some(offset) => Some(offset),
+            None if self.max_pos != expected_len => Some(self.max_pos.min(expected_len)),
+            None => None,
+        }
+    }
+}
+
+impl Write for Compare<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = (self.pos as usize).min(self.expected.len());
+        let end = (start + buf.len()).min(self.expected.len());
+        let expected = &self.expected[start..end];
+        let mismatch = expected
+            .iter()
+            .zip(buf)
+            .position(|(a, b)| a != b)
+            .or((expected.len() < buf.len()).then_some(expected.len()));
+        if let Some(i) = mismatch {
+            let offset = self.pos + i as u64;
+            self.first_mismatch = Some(self.first_mismatch.map_or(offset, |m| m.min(offset)));
+        }
+        self.pos += buf.len() as u64;
+        self.max_pos = self.max_pos.max(self.pos);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Compare<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(pos, self.pos, self.max_pos)?;
+        Ok(self.pos)
+    }
+}
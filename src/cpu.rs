@@ -0,0 +1,26 @@
+//! CPU time used by the current process, summed over all its threads.
+//! Supported only on Unix; on other platforms the CPU time is reported as 0.
+
+use std::time::Duration;
+
+/// Returns the user and system CPU time used by the process so far, or 0 if it can't be determined
+#[cfg(unix)]
+pub fn cpu_time() -> Duration {
+    // SAFETY: rusage is a plain C struct, for which all zero bytes are a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: getrusage only writes to the given struct
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+    duration(usage.ru_utime) + duration(usage.ru_stime)
+}
+
+#[cfg(unix)]
+fn duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+#[cfg(not(unix))]
+pub fn cpu_time() -> Duration {
+    Duration::ZERO
+}
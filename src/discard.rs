@@ -1,14 +1,27 @@
+use crate::seek_position;
 use std::io;
-use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom, Write};
+use std::time::Instant;
 
 #[derive(Default)]
 pub struct Discard {
     pos: u64,
     max_pos: u64,
+    first_write_time: Option<Instant>,
+}
+
+impl Discard {
+    /// Returns the time when the first non-empty buffer was written, if any
+    pub fn first_write_time(&self) -> Option<Instant> {
+        self.first_write_time
+    }
 }
 
 impl Write for Discard {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.first_write_time.is_none() && !buf.is_empty() {
+            self.first_write_time = Some(Instant::now());
+        }
         self.pos += buf.len() as u64;
         self.max_pos = self.max_pos.max(self.pos);
         Ok(buf.len())
@@ -21,21 +34,7 @@ impl Write for Discard {
 
 impl Seek for Discard {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        let (base_pos, offset) = match pos {
-            SeekFrom::Start(count) => (count, 0),
-            SeekFrom::End(count) => (self.max_pos, count),
-            SeekFrom::Current(count) => (self.pos, count),
-        };
-        self.pos = match base_pos.checked_add_signed(offset) {
-            Some(ok) => ok,
-            None => {
-                return Err(io::Error::new(
-                    ErrorKind::InvalidInput,
-                    "invalid seek to a negative or overflowing position",
-                ))
-            }
-        };
-        self.max_pos = self.max_pos.max(self.pos);
+        self.pos = seek_position(pos, self.pos, self.max_pos)?;
         Ok(self.pos)
     }
 }
@@ -0,0 +1,24 @@
+/// Computes order-0 Shannon entropy of the data in bits per byte
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0_u64; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Returns the smallest compression ratio achievable by an order-0 entropy coder
+pub fn entropy_floor(data: &[u8]) -> f64 {
+    entropy(data) / 8.0
+}
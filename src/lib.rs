@@ -0,0 +1,1407 @@
+pub mod archive;
+pub mod checksum;
+pub mod codec;
+pub mod compare;
+pub mod cpu;
+pub mod discard;
+pub mod entropy;
+pub mod memory;
+pub mod raw;
+pub mod reader;
+pub mod synthetic;
+pub mod sysinfo;
+pub mod transform;
+
+use anyhow::{bail, Context};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use checksum::Checksum;
+use clap::ValueEnum;
+use codec::{brotli, registry, CodecCapabilities};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::hash::Hasher;
+use std::io;
+use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use transform::Transform;
+use twox_hash::XxHash3_64;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Algorithm {
+    Copy,
+    /// LZ4 block format, not readable by the lz4 command line tool.
+    /// Level 0 is the fast mode, tuned with the acceleration; levels 1 to 12 are the HC mode.
+    Lz4,
+    /// LZ4 frame format, as used by the lz4 command line tool
+    Lz4Frame,
+    Zstd,
+    Brotli,
+    Snappy,
+    /// LZMA2 in the xz container format, as used by the xz command line tool
+    Lzma,
+    Lzav,
+    Bzip2,
+    /// Deflate with the zlib wrapper, as used by HTTP deflate encoding and PNG
+    Zlib,
+    /// LZMA in the legacy .lzma format of LZMA Utils, for decoders not supporting xz
+    LzmaAlone,
+    /// LZO1X-1 blocks, not readable by the lzop command line tool
+    Lzo,
+}
+
+impl Algorithm {
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_owned())
+            .unwrap_or_default()
+    }
+
+    pub fn extension(&self) -> &str {
+        registry::codec(*self).extension
+    }
+
+    pub fn from_file_name(path: &Path) -> Option<Algorithm> {
+        let extension = path.extension().and_then(OsStr::to_str)?;
+        registry::find_by_extension(extension).map(|codec| codec.algorithm)
+    }
+
+    /// Removes the extension of this algorithm from the path of a compressed file.
+    /// Returns `None` if the path doesn't end with the extension.
+    pub fn strip_extension(&self, path: &Path) -> Option<PathBuf> {
+        let extension = path.extension()?;
+        extension
+            .eq_ignore_ascii_case(self.extension())
+            .then(|| path.with_extension(""))
+    }
+
+    /// Identifier of the algorithm recorded in the header of compressed streams
+    pub fn id(&self) -> u8 {
+        registry::codec(*self).id
+    }
+
+    /// Returns the algorithm with the identifier recorded in a stream header
+    pub fn from_id(id: u8) -> Option<Algorithm> {
+        registry::find_by_id(id).map(|codec| codec.algorithm)
+    }
+
+    /// Returns the algorithm implied by the extension of the file if it differs from this one
+    pub fn conflicting_extension(&self, path: &Path) -> Option<Algorithm> {
+        Self::from_file_name(path).filter(|a| a != self)
+    }
+
+    /// Chunk size giving a good balance of compression ratio and speed
+    pub fn recommended_chunk_size(&self) -> usize {
+        registry::codec(*self).recommended_chunk_size
+    }
+
+    /// All compression levels accepted by the algorithm.
+    /// Algorithms without compression levels accept only 0.
+    pub fn compression_level_range(&self) -> RangeInclusive<i32> {
+        (registry::codec(*self).level_range)()
+    }
+
+    /// Level of the algorithm corresponding to the preset:
+    ///
+    /// | algorithm           | fast | default | best |
+    /// |---------------------|------|---------|------|
+    /// | copy, snappy, lzo   | 0    | 0       | 0    |
+    /// | lz4, lz4-frame      | 0    | 0       | 12   |
+    /// | zstd                | -3   | 3       | 19   |
+    /// | brotli              | 1    | 6       | 11   |
+    /// | lzma, lzma-alone    | 0    | 6       | 9    |
+    /// | lzav                | 0    | 0       | 1    |
+    /// | bzip2               | 1    | 9       | 9    |
+    /// | zlib                | 1    | 6       | 9    |
+    ///
+    /// The default levels are the ones of the standard tools of the codecs,
+    /// except lz4 level 0 being the fast mode.
+    pub fn preset_level(&self, preset: Preset) -> i32 {
+        let [fast, default, best] = registry::codec(*self).preset_levels;
+        match preset {
+            Preset::Fast => fast,
+            Preset::Default => default,
+            Preset::Best => best,
+        }
+    }
+
+    /// Whether the algorithm favors speed over the compression ratio,
+    /// using LZ77 matching only and no entropy coding
+    pub fn is_fast(&self) -> bool {
+        registry::codec(*self).fast
+    }
+
+    /// Compression levels used by benchmarks, a representative subset of [`Self::compression_level_range`]
+    pub fn get_compression_levels(&self) -> Vec<i32> {
+        (registry::codec(*self).benchmark_levels)()
+    }
+}
+
+/// Compression level named by its purpose, mapped to a level of each algorithm
+/// by [`Algorithm::preset_level`]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Favor speed over the compression ratio
+    Fast,
+    /// The balance of speed and ratio chosen by the standard tools of the codec
+    Default,
+    /// The best ratio, not counting the zstd ultra levels
+    Best,
+}
+
+/// Size of compressed data relative to the size of the uncompressed data
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Ratio(f64);
+
+impl Ratio {
+    pub fn new(compressed_len: u64, uncompressed_len: u64) -> Ratio {
+        Ratio(compressed_len as f64 / uncompressed_len as f64)
+    }
+
+    /// Compressed size divided by the uncompressed size, lower is better
+    pub fn as_fraction(&self) -> f64 {
+        self.0
+    }
+
+    /// Compressed size in percent of the uncompressed size, lower is better
+    pub fn as_percent(&self) -> f64 {
+        self.0 * 100.0
+    }
+
+    /// How many times the data got smaller, higher is better
+    pub fn as_factor(&self) -> f64 {
+        1.0 / self.0
+    }
+}
+
+impl Display for Ratio {
+    /// Prints the percentage and the factor, e.g. `25.0%, 4.00x`,
+    /// padded to the width of the format, if given
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!(
+            "{:.1}%, {:.2}x",
+            self.as_percent(),
+            self.as_factor()
+        ))
+    }
+}
+
+/// Units of the sizes and throughputs in printed results and reports
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Units {
+    /// Decimal units, multiples of 1000: kB, MB and MB/s
+    #[default]
+    Si,
+    /// Binary units, multiples of 1024: KiB, MiB and MiB/s
+    Iec,
+}
+
+impl Units {
+    fn base(&self) -> f64 {
+        match self {
+            Units::Si => 1000.0,
+            Units::Iec => 1024.0,
+        }
+    }
+
+    /// Converts the number of bytes to megabytes or mebibytes
+    pub fn megabytes(&self, bytes: f64) -> f64 {
+        bytes / (self.base() * self.base())
+    }
+
+    /// Unit of a throughput converted with [`Self::megabytes`]
+    pub fn throughput_unit(&self) -> &'static str {
+        match self {
+            Units::Si => "MB/s",
+            Units::Iec => "MiB/s",
+        }
+    }
+
+    /// Formats the throughput given in bytes per second, e.g. `123.4 MB/s`
+    pub fn format_throughput(&self, bytes_per_sec: f64) -> String {
+        format!(
+            "{:.1} {}",
+            self.megabytes(bytes_per_sec),
+            self.throughput_unit()
+        )
+    }
+
+    /// Formats the size in the largest unit it has at least one of, with at most one decimal,
+    /// e.g. `1.5 kB` or `1.5 KiB`
+    pub fn format_size(&self, bytes: f64) -> String {
+        let units: [&str; 6] = match self {
+            Units::Si => ["B", "kB", "MB", "GB", "TB", "PB"],
+            Units::Iec => ["B", "KiB", "MiB", "GiB", "TiB", "PiB"],
+        };
+        let mut value = bytes;
+        let mut unit = 0;
+        while value >= self.base() && unit < units.len() - 1 {
+            value /= self.base();
+            unit += 1;
+        }
+        let value = format!("{:.1}", value);
+        let value = value.strip_suffix(".0").unwrap_or(&value);
+        format!("{} {}", value, units[unit])
+    }
+}
+
+pub struct Measurement {
+    pub input_len: u64,
+    pub output_len: u64,
+    pub elapsed: Duration,
+    /// CPU time of all threads of the process, which exceeds `elapsed` if they run in parallel
+    pub cpu_time: Duration,
+}
+
+impl Measurement {
+    pub fn compression_ratio(&self) -> Ratio {
+        Ratio::new(self.output_len, self.input_len)
+    }
+
+    pub fn input_throughtput(&self) -> f64 {
+        self.input_len as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn output_throughtput(&self) -> f64 {
+        self.output_len as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn format_compression(&self) -> String {
+        format!(
+            "{} => {} ({})",
+            self.input_len,
+            self.output_len,
+            self.compression_ratio()
+        )
+    }
+}
+
+/// Codec specific settings not covered by the compression level
+#[derive(Clone, Copy, Default)]
+pub struct CodecOptions {
+    /// Window log for zstd long distance matching; disabled if not set
+    pub zstd_long: Option<u32>,
+    /// Use the slower extreme variant of the xz preset
+    pub lzma_extreme: bool,
+    /// Allow zstd levels above 19, which need much more memory
+    pub zstd_ultra: bool,
+    /// Dictionary id written to zstd frame headers instead of the one stored in the dictionary
+    pub zstd_dict_id: Option<u32>,
+    /// Acceleration of the lz4 fast mode, used only at level 0; higher is faster but weaker
+    pub lz4_acceleration: Option<u32>,
+    /// Window log of brotli; the default of 22 if not set.
+    /// Values above 24 need the large window decoder, enabled by passing the same value.
+    pub brotli_window: Option<u32>,
+    /// Kind of the input the brotli encoder is tuned for
+    pub brotli_mode: brotli::BrotliMode,
+}
+
+/// Creates an encoder of the algorithm.
+/// Fails if the compression level is outside [`Algorithm::compression_level_range`].
+pub fn encoder(
+    algorithm: Algorithm,
+    compression: i32,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<Box<dyn codec::Encoder>> {
+    let range = algorithm.compression_level_range();
+    if !range.contains(&compression) {
+        bail!(
+            "{} does not support compression level {}, the supported levels are {}..={}",
+            algorithm.name(),
+            compression,
+            range.start(),
+            range.end()
+        );
+    }
+    let encoder = (registry::codec(algorithm).new_encoder)(compression, dict, options)?;
+    warn_if_dict_unsupported(algorithm, encoder.capabilities(), dict);
+    Ok(encoder)
+}
+
+pub fn decoder(
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<Box<dyn codec::Decoder>> {
+    let decoder = (registry::codec(algorithm).new_decoder)(dict, options)?;
+    warn_if_dict_unsupported(algorithm, decoder.capabilities(), dict);
+    Ok(decoder)
+}
+
+fn warn_if_dict_unsupported(
+    algorithm: Algorithm,
+    capabilities: CodecCapabilities,
+    dict: Option<&[u8]>,
+) {
+    if dict.is_some() && !capabilities.dictionary {
+        eprintln!(
+            "warning: {} does not support dictionaries, ignoring the dictionary",
+            algorithm.name()
+        );
+    }
+}
+
+/// Value stored in place of the uncompressed length of a header frame.
+/// A header frame carries the container format version instead of compressed data.
+/// Every compressed stream starts with a header frame; appended streams add further ones.
+const HEADER_MARKER: u32 = u32::MAX;
+
+/// Set in the compressed length of frames storing the chunk uncompressed,
+/// because the codec could not make it smaller
+const STORED_FLAG: u32 = 1 << 31;
+
+/// Largest length of a chunk or of its compressed data in a frame, 2 GiB minus one byte.
+/// Frame headers store the lengths in 32 bits, with the highest bit of the compressed length
+/// marking stored frames.
+pub const MAX_FRAME_LEN: usize = (STORED_FLAG - 1) as usize;
+
+/// Frame length limit of [`DecompressionOptions::default`], 256 MiB.
+/// Large enough for the chunk sizes of all codecs, but streams compressed
+/// with longer chunks must be decompressed with a higher limit.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 256 << 20;
+
+/// Largest payload of a header frame. Headers hold a few bytes of options,
+/// so longer ones are rejected as corrupted before allocating memory for them.
+const MAX_HEADER_LEN: usize = 64 << 10;
+
+/// Value stored in place of the uncompressed length of a footer frame.
+/// A footer frame holds the checksum of all data since the preceding header frame.
+const FOOTER_MARKER: u32 = u32::MAX - 1;
+
+/// Version of the container format written by this build.
+/// Version 1 headers hold only the version. Since version 2, headers are followed
+/// by the transform applied to the chunks, if any. Version 3 adds stored frames.
+/// Version 4 puts the checksum kind before the transform and the checksum after each data frame.
+/// Version 5 adds a flags byte after the checksum kind, marking streams ending with a footer frame
+/// or holding the original file extension, stored after the flags with a one byte length,
+/// or a tar archive of a directory.
+/// Version 6 adds the identifier of the algorithm after the flags, 0 if not known,
+/// and a flag marking brotli streams compressed with the large window extension.
+pub const FORMAT_VERSION: u8 = 6;
+
+/// Default capacity of the buffer the compressed input is read through when decompressing.
+/// Frames within the buffer are decoded in place, others are first copied
+/// to a separate buffer growing to the length of the largest frame.
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 1 << 20;
+
+/// Settings of [`decompress_with_options`]
+#[derive(Clone, Debug)]
+pub struct DecompressionOptions {
+    /// Reject streams written with a different container version.
+    /// If disabled, such frames are decoded as if they had the current format,
+    /// which may fail or produce garbage.
+    pub check_version: bool,
+    /// Capacity of the buffer the input is read through
+    pub read_buffer_size: usize,
+    /// Largest frame length accepted, compressed or uncompressed.
+    /// Longer frames are reported as an error before allocating memory for them,
+    /// so corrupted lengths can't exhaust the memory.
+    pub max_frame_len: usize,
+    /// Decode the stream as written before the container had header frames,
+    /// failing on header and footer frames instead of parsing them
+    pub no_header: bool,
+}
+
+impl Default for DecompressionOptions {
+    fn default() -> Self {
+        DecompressionOptions {
+            check_version: true,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            no_header: false,
+        }
+    }
+}
+
+const FLAG_FOOTER_CHECKSUM: u8 = 1;
+const FLAG_ORIGINAL_EXTENSION: u8 = 2;
+const FLAG_TAR: u8 = 4;
+const FLAG_BROTLI_LARGE_WINDOW: u8 = 8;
+
+/// Properties of a compressed stream, recorded in its header frame
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerOptions {
+    /// Transform applied to each chunk before compression
+    pub transform: Transform,
+    /// Checksum of each uncompressed chunk stored after its frame
+    pub checksum: Checksum,
+    /// End the stream with a footer frame holding the xxh3 hash of the whole uncompressed data
+    pub footer_checksum: bool,
+    /// Extension of the input file, removed from the name of the compressed file.
+    /// Restored when deriving the name of the decompressed file.
+    pub original_extension: Option<String>,
+    /// The uncompressed data is a tar archive of a directory, unpacked on decompression
+    pub tar: bool,
+    /// Algorithm the frames were compressed with, not recorded before version 6
+    pub algorithm: Option<Algorithm>,
+    /// The frames were compressed by brotli with a window log above 24, so they can be decoded
+    /// only with the large window extension enabled. Not recorded before version 6,
+    /// nor in headers recording another algorithm.
+    pub brotli_large_window: bool,
+}
+
+/// Writes a header frame and returns its length
+fn write_header<W: Write>(output: &mut W, options: &ContainerOptions) -> io::Result<u64> {
+    let mut flags = 0;
+    if options.footer_checksum {
+        flags |= FLAG_FOOTER_CHECKSUM;
+    }
+    if options.original_extension.is_some() {
+        flags |= FLAG_ORIGINAL_EXTENSION;
+    }
+    if options.tar {
+        flags |= FLAG_TAR;
+    }
+    if options.brotli_large_window && options.algorithm.is_none_or(|a| a == Algorithm::Brotli) {
+        flags |= FLAG_BROTLI_LARGE_WINDOW;
+    }
+    let algorithm_id = options.algorithm.map_or(0, |a| a.id());
+    let mut payload = vec![
+        FORMAT_VERSION,
+        options.checksum.to_byte(),
+        flags,
+        algorithm_id,
+    ];
+    if let Some(extension) = &options.original_extension {
+        let len = u8::try_from(extension.len()).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("File extension {} is longer than 255 bytes", extension),
+            )
+        })?;
+        payload.push(len);
+        payload.extend_from_slice(extension.as_bytes());
+    }
+    if options.transform != Transform::None {
+        payload.extend(options.transform.to_bytes());
+    }
+    output.write_u32::<LittleEndian>(HEADER_MARKER)?;
+    output.write_u32::<LittleEndian>(payload.len() as u32)?;
+    output.write_all(&payload)?;
+    Ok(8 + payload.len() as u64)
+}
+
+/// Checks the original file extension is a single path component,
+/// so the name of the decompressed file derived from it stays in the directory of the input
+fn is_valid_extension(extension: &str) -> bool {
+    !extension.is_empty()
+        && extension != "."
+        && extension != ".."
+        && !extension.contains(['/', '\\', '\0'])
+}
+
+/// Checks the format version stored in the payload of a header frame
+/// and returns the properties of the following frames
+fn parse_header(payload: &[u8]) -> io::Result<ContainerOptions> {
+    let parse_transform = |bytes: &[u8]| match bytes {
+        [] => Ok(Transform::None),
+        bytes => Transform::from_bytes(bytes),
+    };
+    let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_owned());
+    match payload.split_first() {
+        Some((1, _)) => Ok(ContainerOptions::default()),
+        Some((2..=3, transform)) => Ok(ContainerOptions {
+            transform: parse_transform(transform)?,
+            ..ContainerOptions::default()
+        }),
+        Some((4, [checksum, transform @ ..])) => Ok(ContainerOptions {
+            transform: parse_transform(transform)?,
+            checksum: Checksum::from_byte(*checksum)?,
+            ..ContainerOptions::default()
+        }),
+        Some((version @ 5..=FORMAT_VERSION, [checksum, flags, rest @ ..])) => {
+            let known_flags = match version {
+                5 => FLAG_FOOTER_CHECKSUM | FLAG_ORIGINAL_EXTENSION | FLAG_TAR,
+                _ => {
+                    FLAG_FOOTER_CHECKSUM
+                        | FLAG_ORIGINAL_EXTENSION
+                        | FLAG_TAR
+                        | FLAG_BROTLI_LARGE_WINDOW
+                }
+            };
+            if flags & !known_flags != 0 {
+                return Err(invalid("Unknown flags in header"));
+            }
+            let (algorithm, rest) = match (version, rest) {
+                (5, rest) => (None, rest),
+                (_, [0, rest @ ..]) => (None, rest),
+                (_, [id, rest @ ..]) => {
+                    let algorithm = Algorithm::from_id(*id).ok_or_else(|| {
+                        io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Unknown algorithm {} in header", id),
+                        )
+                    })?;
+                    (Some(algorithm), rest)
+                }
+                (_, []) => return Err(invalid("Truncated header")),
+            };
+            let (original_extension, transform) = if flags & FLAG_ORIGINAL_EXTENSION != 0 {
+                let (extension, transform) = rest
+                    .split_first()
+                    .and_then(|(len, rest)| rest.split_at_checked(*len as usize))
+                    .ok_or_else(|| invalid("Truncated header"))?;
+                let extension = String::from_utf8(extension.to_vec())
+                    .map_err(|_| invalid("Original file extension is not valid UTF-8"))?;
+                if !is_valid_extension(&extension) {
+                    return Err(invalid("Original file extension is not a valid file name"));
+                }
+                (Some(extension), transform)
+            } else {
+                (None, rest)
+            };
+            Ok(ContainerOptions {
+                transform: parse_transform(transform)?,
+                checksum: Checksum::from_byte(*checksum)?,
+                footer_checksum: flags & FLAG_FOOTER_CHECKSUM != 0,
+                original_extension,
+                tar: flags & FLAG_TAR != 0,
+                algorithm,
+                brotli_large_window: flags & FLAG_BROTLI_LARGE_WINDOW != 0,
+            })
+        }
+        Some((4..=FORMAT_VERSION, _)) => Err(invalid("Truncated header")),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Unsupported container version {}, this build supports up to {}",
+                payload.first().copied().unwrap_or(0),
+                FORMAT_VERSION
+            ),
+        )),
+    }
+}
+
+/// Reads the properties of a compressed stream from its first header frame.
+/// Streams starting with a data frame, written before the container had headers,
+/// get the default properties.
+pub fn read_header<R: Read>(mut input: R) -> io::Result<ContainerOptions> {
+    let mut frame_header = [0; 8];
+    match input.read_exact(&mut frame_header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(ContainerOptions::default()),
+        Err(e) => return Err(e),
+    }
+    let mut frame_header = frame_header.as_slice();
+    if frame_header.read_u32::<LittleEndian>()? != HEADER_MARKER {
+        return Ok(ContainerOptions::default());
+    }
+    let len = frame_header.read_u32::<LittleEndian>()? as usize;
+    check_header_len(len)?;
+    let mut payload = vec![0; len];
+    input.read_exact(&mut payload)?;
+    parse_header(&payload)
+}
+
+/// Fails if the payload length of a header frame read from the input exceeds [`MAX_HEADER_LEN`]
+fn check_header_len(len: usize) -> io::Result<()> {
+    if len > MAX_HEADER_LEN {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Header frame length {} exceeds the maximum {}, the input may be corrupted",
+                len, MAX_HEADER_LEN
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn compress<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    chunk_size: usize,
+    encoder: &mut dyn codec::Encoder,
+) -> anyhow::Result<Measurement> {
+    compress_with_observer(input, output, chunk_size, encoder, |_, _| {})
+}
+
+/// Like [`compress`], but additionally calls `on_chunk` with each uncompressed chunk
+/// and its compressed length
+pub fn compress_with_observer<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    chunk_size: usize,
+    encoder: &mut dyn codec::Encoder,
+    on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<Measurement> {
+    compress_with_options(
+        input,
+        output,
+        chunk_size,
+        encoder,
+        ContainerOptions::default(),
+        on_chunk,
+    )
+}
+
+/// Like [`compress_with_observer`], but applies the transform to each chunk before compressing it
+/// and adds the checksums selected in the options.
+/// The options are recorded in the header, so [`decompress`] reverses the transform and verifies
+/// the checksums. `on_chunk` receives the chunks as read from the input.
+pub fn compress_with_options<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    chunk_size: usize,
+    encoder: &mut dyn codec::Encoder,
+    options: ContainerOptions,
+    on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<Measurement> {
+    check_chunk_size(chunk_size, encoder)?;
+    let input = BufReader::with_capacity(chunk_size, input);
+    compress_chunks(
+        input,
+        output,
+        iter::repeat(chunk_size),
+        encoder,
+        options,
+        on_chunk,
+    )
+}
+
+/// Size of the first chunk written by [`compress_adaptive`]
+pub const ADAPTIVE_INITIAL_CHUNK_SIZE: usize = 4096;
+
+/// Returns the sizes of consecutive chunks written by [`compress_adaptive`]:
+/// starting from [`ADAPTIVE_INITIAL_CHUNK_SIZE`], each chunk is twice as large as the previous one,
+/// until reaching `max_chunk_size`
+pub fn adaptive_chunk_sizes(max_chunk_size: usize) -> impl Iterator<Item = usize> {
+    iter::successors(
+        Some(ADAPTIVE_INITIAL_CHUNK_SIZE.min(max_chunk_size)),
+        move |&size| Some(size.saturating_mul(2).min(max_chunk_size)),
+    )
+}
+
+/// Like [`compress_with_options`], but starts with small chunks and makes them larger
+/// as described in [`adaptive_chunk_sizes`]. Small chunks at the start let streaming readers
+/// get the first data early, while the large chunks that follow give a better ratio.
+/// The size of each chunk is recorded in its frame header, so [`decompress`] handles such
+/// streams like any other.
+pub fn compress_adaptive<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    max_chunk_size: usize,
+    encoder: &mut dyn codec::Encoder,
+    options: ContainerOptions,
+    on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<Measurement> {
+    check_chunk_size(max_chunk_size, encoder)?;
+    let input = BufReader::with_capacity(max_chunk_size, input);
+    let chunk_sizes = adaptive_chunk_sizes(max_chunk_size);
+    compress_chunks(input, output, chunk_sizes, encoder, options, on_chunk)
+}
+
+/// Compresses chunks of the given sizes until the end of the input.
+/// Chunk sizes must not exceed the capacity of the input buffer.
+fn compress_chunks<R: Read + Seek, W: Write + Seek>(
+    input: BufReader<R>,
+    output: W,
+    mut chunk_sizes: impl Iterator<Item = usize>,
+    encoder: &mut dyn codec::Encoder,
+    options: ContainerOptions,
+    mut on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<Measurement> {
+    let mut tmp_buf = vec![0; encoder.compressed_len_bound(input.capacity())];
+    let mut transformed = Vec::new();
+    let mut chunk_buf = Vec::new();
+
+    measure(input, output, |input, output| {
+        write_header(output, &options)?;
+        let mut hasher = XxHash3_64::new();
+        let mut compress_chunk = |input_chunk: &[u8]| -> anyhow::Result<()> {
+            let chunk = transform_chunk(options.transform, input_chunk, &mut transformed);
+            let compressed_len = compress_frame(chunk, output, encoder, &mut tmp_buf)?;
+            let checksum = options.checksum;
+            checksum.write(checksum.compute(input_chunk), output)?;
+            if options.footer_checksum {
+                hasher.write(input_chunk);
+            }
+            on_chunk(input_chunk, compressed_len);
+            Ok(())
+        };
+        while !input.fill_buf()?.is_empty() {
+            let chunk_size = chunk_sizes.next().unwrap();
+            if input.buffer().len() >= chunk_size {
+                compress_chunk(&input.buffer()[..chunk_size])?;
+                input.consume(chunk_size);
+            } else {
+                // The buffer ends before the chunk, e.g. after a short read from a pipe
+                chunk_buf.clear();
+                input.take(chunk_size as u64).read_to_end(&mut chunk_buf)?;
+                compress_chunk(&chunk_buf)?;
+            }
+        }
+        if options.footer_checksum {
+            write_footer(output, hasher.finish())?;
+        }
+        output.flush()?;
+        Ok(())
+    })
+}
+
+/// Compresses the input with each of the encoders in a single pass over the input,
+/// writing the results to the corresponding outputs.
+/// The header of each output records the algorithm given with its encoder.
+/// Returns the number of bytes written to each output.
+pub fn compress_to_many<R: Read, W: Write>(
+    input: R,
+    outputs: &mut [(W, Algorithm, Box<dyn codec::Encoder>)],
+    chunk_size: usize,
+    options: ContainerOptions,
+) -> anyhow::Result<Vec<u64>> {
+    let mut tmp_bufs = Vec::with_capacity(outputs.len());
+    for (_, _, encoder) in outputs.iter_mut() {
+        check_chunk_size(chunk_size, encoder.as_ref())?;
+        tmp_bufs.push(vec![0; encoder.compressed_len_bound(chunk_size)]);
+    }
+    let mut output_lens = Vec::with_capacity(outputs.len());
+    for (output, algorithm, _) in outputs.iter_mut() {
+        let options = ContainerOptions {
+            algorithm: Some(*algorithm),
+            ..options.clone()
+        };
+        output_lens.push(write_header(output, &options)?);
+    }
+    let ContainerOptions {
+        transform,
+        checksum,
+        footer_checksum,
+        ..
+    } = options;
+    let mut hasher = XxHash3_64::new();
+    let mut transformed = Vec::new();
+    let mut input = BufReader::with_capacity(chunk_size, input);
+    while !input.fill_buf()?.is_empty() {
+        let input_chunk = input.buffer();
+        let chunk = transform_chunk(transform, input_chunk, &mut transformed);
+        let chunk_checksum = checksum.compute(input_chunk);
+        if footer_checksum {
+            hasher.write(input_chunk);
+        }
+        for (((output, _, encoder), tmp_buf), output_len) in outputs
+            .iter_mut()
+            .zip(tmp_bufs.iter_mut())
+            .zip(output_lens.iter_mut())
+        {
+            let compressed_len = compress_frame(chunk, output, encoder.as_mut(), tmp_buf)?;
+            checksum.write(chunk_checksum, output)?;
+            *output_len += (8 + compressed_len + checksum.encoded_len()) as u64;
+        }
+        let uncompressed_len = input_chunk.len();
+        input.consume(uncompressed_len);
+    }
+    let footer_hash = hasher.finish();
+    for ((output, _, _), output_len) in outputs.iter_mut().zip(output_lens.iter_mut()) {
+        if footer_checksum {
+            *output_len += write_footer(output, footer_hash)?;
+        }
+        output.flush()?;
+    }
+    Ok(output_lens)
+}
+
+/// Writes a footer frame with the hash of the data written since the header and returns its length
+fn write_footer<W: Write>(output: &mut W, hash: u64) -> io::Result<u64> {
+    output.write_u32::<LittleEndian>(FOOTER_MARKER)?;
+    output.write_u32::<LittleEndian>(8)?;
+    output.write_u64::<LittleEndian>(hash)?;
+    Ok(16)
+}
+
+fn check_chunk_size(chunk_size: usize, encoder: &dyn codec::Encoder) -> anyhow::Result<()> {
+    if chunk_size == 0 {
+        bail!("Chunk size must be greater than zero");
+    }
+    let max_chunk_size = encoder.max_chunk_size();
+    if chunk_size > max_chunk_size {
+        bail!(
+            "Chunk size {} exceeds the maximum of {} supported by the {}",
+            chunk_size,
+            max_chunk_size,
+            if max_chunk_size == MAX_FRAME_LEN {
+                "container format"
+            } else {
+                "codec"
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Returns the chunk with the transform applied, using `buf` for the result if needed
+fn transform_chunk<'a>(transform: Transform, chunk: &'a [u8], buf: &'a mut Vec<u8>) -> &'a [u8] {
+    if transform == Transform::None {
+        return chunk;
+    }
+    transform.apply(chunk, buf);
+    buf
+}
+
+/// Compresses a single chunk and writes it as a frame.
+/// If the codec can't make the chunk smaller, the chunk is stored uncompressed instead.
+/// Returns the length of the frame data, excluding the frame header.
+fn compress_frame<W: Write>(
+    chunk: &[u8],
+    output: &mut W,
+    encoder: &mut dyn codec::Encoder,
+    tmp_buf: &mut [u8],
+) -> anyhow::Result<usize> {
+    if chunk.len() > MAX_FRAME_LEN {
+        bail!(
+            "Chunk of {} bytes exceeds the maximum frame length of {}",
+            chunk.len(),
+            MAX_FRAME_LEN
+        );
+    }
+    let compressed_len = encoder.compress(chunk, tmp_buf)?;
+    if compressed_len > tmp_buf.len() {
+        bail!(
+            "Codec reported {} bytes of compressed output, but the buffer holds only {}",
+            compressed_len,
+            tmp_buf.len()
+        );
+    }
+    // Both lengths fit in 31 bits: the chunk length was checked above
+    // and compressed data longer than the chunk is never written
+    output.write_u32::<LittleEndian>(chunk.len() as u32)?;
+    if compressed_len >= chunk.len() {
+        output.write_u32::<LittleEndian>(STORED_FLAG | chunk.len() as u32)?;
+        output.write_all(chunk)?;
+        return Ok(chunk.len());
+    }
+    output.write_u32::<LittleEndian>(compressed_len as u32)?;
+    output.write_all(&tmp_buf[0..compressed_len])?;
+    Ok(compressed_len)
+}
+
+/// Checks that an input of the given length fits in a block of [`compress_single_block`],
+/// so it can be done before creating the output
+pub fn check_single_block_len(len: u64, encoder: &dyn codec::Encoder) -> anyhow::Result<()> {
+    let max_len = encoder.max_chunk_size();
+    if len > max_len as u64 {
+        bail!(
+            "Input of {} bytes doesn't fit in a single block of at most {} bytes",
+            len,
+            max_len
+        );
+    }
+    Ok(())
+}
+
+/// Compresses the whole input into a single block, preceded only by its uncompressed length.
+/// Without the header and frame lengths of the container this is the most compact format
+/// for small inputs, but the input must fit in memory and in the block size limit of the codec.
+/// Like in frames, the highest bit of the length marks a block stored uncompressed.
+pub fn compress_single_block<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    encoder: &mut dyn codec::Encoder,
+) -> anyhow::Result<Measurement> {
+    measure(input, output, |input, output| {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        check_single_block_len(data.len() as u64, encoder)?;
+        let mut buf = vec![0; encoder.compressed_len_bound(data.len())];
+        let compressed_len = if data.is_empty() {
+            0
+        } else {
+            encoder.compress(&data, &mut buf)?
+        };
+        if compressed_len > buf.len() {
+            bail!(
+                "Codec reported {} bytes of compressed output, but the buffer holds only {}",
+                compressed_len,
+                buf.len()
+            );
+        }
+        if data.is_empty() || compressed_len >= data.len() {
+            output.write_u32::<LittleEndian>(STORED_FLAG | data.len() as u32)?;
+            output.write_all(&data)?;
+        } else {
+            output.write_u32::<LittleEndian>(data.len() as u32)?;
+            output.write_all(&buf[0..compressed_len])?;
+        }
+        Ok(())
+    })
+}
+
+/// Decompresses a block written by [`compress_single_block`]
+pub fn decompress_single_block<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<Measurement> {
+    decompress_single_block_with_options(input, output, decoder, &DecompressionOptions::default())
+}
+
+/// Like [`decompress_single_block`], but with the given block length limit.
+/// The version check and the read buffer size of the options don't apply to blocks.
+pub fn decompress_single_block_with_options<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    decoder: &mut dyn codec::Decoder,
+    options: &DecompressionOptions,
+) -> anyhow::Result<Measurement> {
+    measure(input, output, |input, output| {
+        let len = input
+            .read_u32::<LittleEndian>()
+            .context("Missing the uncompressed length of the block")?;
+        let uncompressed_len = (len & !STORED_FLAG) as usize;
+        check_frame_len("Uncompressed", uncompressed_len, options.max_frame_len)?;
+        let mut src = Vec::new();
+        input
+            .take(options.max_frame_len as u64 + 1)
+            .read_to_end(&mut src)?;
+        check_frame_len("Compressed", src.len(), options.max_frame_len)?;
+        if len & STORED_FLAG != 0 {
+            if src.len() != uncompressed_len {
+                bail!(
+                    "Stored block length {} does not match the uncompressed length {}",
+                    src.len(),
+                    uncompressed_len
+                );
+            }
+            output.write_all(&src)?;
+        } else {
+            let mut dest = vec![0; uncompressed_len];
+            let count = decoder.decompress(&src, &mut dest)?;
+            if count != uncompressed_len {
+                bail!(
+                    "Decompressed length {} does not match the expected length {}",
+                    count,
+                    uncompressed_len
+                );
+            }
+            output.write_all(&dest)?;
+        }
+        output.flush()?;
+        Ok(())
+    })
+}
+
+/// Decompresses a stream written by [`compress`] or its variants.
+/// Header frames are recognized by their marker, so streams written before the container had
+/// a header, which start directly with a data frame, are decompressed as well,
+/// with no transform and no checksums.
+pub fn decompress<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<Measurement> {
+    decompress_with_version_check(input, output, decoder, true)
+}
+
+/// Like [`decompress`], but allows to skip rejecting streams written with a different
+/// container version. Such frames are decoded as if they had the current format,
+/// which may fail or produce garbage.
+pub fn decompress_with_version_check<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    decoder: &mut dyn codec::Decoder,
+    check_version: bool,
+) -> anyhow::Result<Measurement> {
+    let options = DecompressionOptions {
+        check_version,
+        ..DecompressionOptions::default()
+    };
+    decompress_with_options(input, output, decoder, &options)
+}
+
+/// Like [`decompress`], but with the given read buffer size, frame length limit
+/// and version check
+pub fn decompress_with_options<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    decoder: &mut dyn codec::Decoder,
+    options: &DecompressionOptions,
+) -> anyhow::Result<Measurement> {
+    // An empty buffer would be taken for the end of the input
+    let input = BufReader::with_capacity(options.read_buffer_size.max(1), input);
+    let mut src = Vec::new();
+    let mut dest = Vec::new();
+    let mut restored = Vec::new();
+
+    measure(input, output, |input, output| {
+        let mut chunk_index = 0;
+        let mut frame_offset = 0;
+        let mut header = ContainerOptions::default();
+        let mut hasher = XxHash3_64::new();
+        let mut footer_missing = false;
+        while !input.fill_buf()?.is_empty() {
+            let context = || {
+                format!(
+                    "Failed to decompress chunk {} at offset {}",
+                    chunk_index, frame_offset
+                )
+            };
+            let frame = decompress_frame(input, decoder, &mut src, &mut dest, &header, options)
+                .with_context(context)?;
+            match frame {
+                Frame::Header(frame_len, frame_header) => {
+                    if footer_missing {
+                        bail!(
+                            "Missing footer checksum before the header at offset {}",
+                            frame_offset
+                        );
+                    }
+                    header = frame_header;
+                    hasher = XxHash3_64::new();
+                    frame_offset += frame_len as u64;
+                }
+                Frame::Footer(frame_len, expected) => {
+                    let actual = hasher.finish();
+                    if actual != expected {
+                        bail!(
+                            "Footer checksum mismatch: expected {:016x}, computed {:016x}",
+                            expected,
+                            actual
+                        );
+                    }
+                    footer_missing = false;
+                    frame_offset += frame_len as u64;
+                }
+                Frame::Data(frame_len, checksum) => {
+                    let chunk = if header.transform == Transform::None {
+                        &dest
+                    } else {
+                        header.transform.reverse(&dest, &mut restored);
+                        &restored
+                    };
+                    header
+                        .checksum
+                        .verify(chunk, checksum)
+                        .with_context(context)?;
+                    if header.footer_checksum {
+                        hasher.write(chunk);
+                        footer_missing = true;
+                    }
+                    output.write_all(chunk)?;
+                    chunk_index += 1;
+                    frame_offset += frame_len as u64;
+                }
+            }
+        }
+        if footer_missing {
+            bail!("Missing footer checksum at the end of the stream, it may be truncated");
+        }
+        output.flush()?;
+        Ok(())
+    })
+}
+
+/// Kind and length of a frame read from the input, including the frame header and the checksum.
+/// Header frames also carry the properties of the following frames,
+/// data frames the stored checksum of the uncompressed chunk
+/// and footer frames the hash of the data since the header.
+enum Frame {
+    Header(usize, ContainerOptions),
+    Data(usize, u64),
+    Footer(usize, u64),
+}
+
+/// Decompresses a single frame into `dest`.
+/// Data frames are read as described by the last `header`.
+/// Header frames are only checked for a supported version, if requested.
+/// Headers of unsupported versions are then assumed to apply no transform and no checksum.
+fn decompress_frame<R: Read>(
+    input: &mut BufReader<R>,
+    decoder: &mut dyn codec::Decoder,
+    src: &mut Vec<u8>,
+    dest: &mut Vec<u8>,
+    header: &ContainerOptions,
+    options: &DecompressionOptions,
+) -> anyhow::Result<Frame> {
+    let uncompressed_len = input.read_u32::<LittleEndian>()?;
+    let compressed_len = input.read_u32::<LittleEndian>()?;
+    if options.no_header && (uncompressed_len == HEADER_MARKER || uncompressed_len == FOOTER_MARKER)
+    {
+        bail!("Found a header or footer frame in a stream expected to have none");
+    }
+    if uncompressed_len == HEADER_MARKER {
+        let frame_len = compressed_len.try_into().unwrap();
+        check_header_len(frame_len)?;
+        src.resize(frame_len, 0);
+        input.read_exact(src)?;
+        let header = match parse_header(src) {
+            Ok(header) => header,
+            Err(e) if options.check_version => return Err(e.into()),
+            Err(_) => ContainerOptions::default(),
+        };
+        return Ok(Frame::Header(8 + frame_len, header));
+    }
+    if uncompressed_len == FOOTER_MARKER {
+        if compressed_len != 8 {
+            bail!("Footer frame has length {} instead of 8", compressed_len);
+        }
+        return Ok(Frame::Footer(16, input.read_u64::<LittleEndian>()?));
+    }
+    let uncompressed_len = uncompressed_len.try_into().unwrap();
+    let frame_len = (compressed_len & !STORED_FLAG).try_into().unwrap();
+    check_frame_len("Uncompressed", uncompressed_len, options.max_frame_len)?;
+    check_frame_len("Compressed", frame_len, options.max_frame_len)?;
+    let checksum_len = header.checksum.encoded_len();
+    if compressed_len & STORED_FLAG != 0 {
+        if frame_len != uncompressed_len {
+            bail!(
+                "Stored frame length {} does not match the uncompressed length {}",
+                frame_len,
+                uncompressed_len
+            );
+        }
+        dest.resize(frame_len, 0);
+        input.read_exact(dest)?;
+        let checksum = header.checksum.read(input)?;
+        return Ok(Frame::Data(8 + frame_len + checksum_len, checksum));
+    }
+    dest.resize(uncompressed_len, 0);
+    let count = if input.buffer().len() >= frame_len {
+        let src = &input.buffer()[0..frame_len];
+        let count = decoder.decompress(src, dest)?;
+        input.consume(frame_len);
+        count
+    } else {
+        src.resize(frame_len, 0);
+        input.read_exact(src)?;
+        decoder.decompress(src, dest)?
+    };
+    if count != uncompressed_len {
+        bail!(
+            "Decompressed length {} does not match the expected length {}",
+            count,
+            uncompressed_len
+        );
+    }
+    let checksum = header.checksum.read(input)?;
+    Ok(Frame::Data(8 + frame_len + checksum_len, checksum))
+}
+
+/// Fails if a frame length read from the input exceeds the limit, so it is not allocated
+fn check_frame_len(what: &str, len: usize, max_len: usize) -> anyhow::Result<()> {
+    if len > max_len {
+        bail!(
+            "{} length {} exceeds the maximum frame length {}, the input may be corrupted",
+            what,
+            len,
+            max_len
+        );
+    }
+    Ok(())
+}
+
+/// Location of a single frame in the compressed and uncompressed streams
+#[derive(Clone, Debug)]
+pub struct FrameInfo {
+    /// Position of the frame header in the compressed stream
+    pub compressed_offset: u64,
+    /// Length of the compressed data, excluding the frame header
+    pub compressed_len: u64,
+    /// Position of the first byte of the frame in the uncompressed stream
+    pub uncompressed_offset: u64,
+    pub uncompressed_len: u64,
+    /// The frame holds the chunk uncompressed
+    pub stored: bool,
+    /// Transform to reverse after decompressing the frame
+    pub transform: Transform,
+    /// Kind of the checksum following the compressed data
+    pub checksum: Checksum,
+}
+
+impl FrameInfo {
+    /// Length of the whole frame, including the frame header and the checksum
+    pub fn frame_len(&self) -> u64 {
+        8 + self.compressed_len + self.checksum.encoded_len() as u64
+    }
+
+    pub fn uncompressed_range(&self) -> Range<u64> {
+        self.uncompressed_offset..self.uncompressed_offset + self.uncompressed_len
+    }
+}
+
+/// Reads frame headers of a compressed stream without decompressing the frames.
+/// Returns only the frames holding data; header frames are checked for a supported version
+/// and footer frames are skipped.
+pub fn scan_frames<R: Read + Seek>(input: R) -> io::Result<Vec<FrameInfo>> {
+    let mut input = BufReader::new(input);
+    let start_pos = input.stream_position()?;
+    let mut frames = Vec::new();
+    let mut last_frame_offset = 0;
+    let mut compressed_offset = 0;
+    let mut uncompressed_offset = 0;
+    let mut header = ContainerOptions::default();
+    while !input.fill_buf()?.is_empty() {
+        last_frame_offset = compressed_offset;
+        let uncompressed_len = input.read_u32::<LittleEndian>()?;
+        let compressed_len = input.read_u32::<LittleEndian>()?;
+        let is_data = uncompressed_len != HEADER_MARKER && uncompressed_len != FOOTER_MARKER;
+        let stored = is_data && compressed_len & STORED_FLAG != 0;
+        let compressed_len = if stored {
+            (compressed_len & !STORED_FLAG) as u64
+        } else {
+            compressed_len as u64
+        };
+        if uncompressed_len == HEADER_MARKER {
+            check_header_len(compressed_len as usize)?;
+            let mut payload = vec![0; compressed_len as usize];
+            input.read_exact(&mut payload)?;
+            header = parse_header(&payload)?;
+            compressed_offset += 8 + compressed_len;
+        } else if uncompressed_len == FOOTER_MARKER {
+            input.seek_relative(compressed_len as i64)?;
+            compressed_offset += 8 + compressed_len;
+        } else {
+            let uncompressed_len = uncompressed_len as u64;
+            if stored && compressed_len != uncompressed_len {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Stored frame at offset {} has length {}, but the uncompressed length is {}",
+                        compressed_offset, compressed_len, uncompressed_len
+                    ),
+                ));
+            }
+            let frame = FrameInfo {
+                compressed_offset,
+                compressed_len,
+                uncompressed_offset,
+                uncompressed_len,
+                stored,
+                transform: header.transform,
+                checksum: header.checksum,
+            };
+            input.seek_relative(frame.frame_len() as i64 - 8)?;
+            compressed_offset += frame.frame_len();
+            uncompressed_offset += uncompressed_len;
+            frames.push(frame);
+        }
+    }
+    if input.seek(SeekFrom::End(0))? - start_pos != compressed_offset {
+        return Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("Last frame at offset {} is truncated", last_frame_offset),
+        ));
+    }
+    Ok(frames)
+}
+
+/// Compresses an in-memory buffer into the chunked format produced by [`compress`]
+pub fn compress_bytes(
+    input: &[u8],
+    algorithm: Algorithm,
+    compression: i32,
+    chunk_size: usize,
+    dict: Option<&[u8]>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = encoder(algorithm, compression, dict, &CodecOptions::default())?;
+    let mut output = Cursor::new(Vec::with_capacity(input.len()));
+    let options = ContainerOptions {
+        algorithm: Some(algorithm),
+        ..ContainerOptions::default()
+    };
+    compress_with_options(
+        Cursor::new(input),
+        &mut output,
+        chunk_size,
+        encoder.as_mut(),
+        options,
+        |_, _| {},
+    )?;
+    Ok(output.into_inner())
+}
+
+/// Decompresses an in-memory buffer produced by [`compress`] or [`compress_bytes`]
+pub fn decompress_bytes(
+    input: &[u8],
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut decoder = decoder(algorithm, dict, &CodecOptions::default())?;
+    let mut output = Cursor::new(Vec::new());
+    decompress(Cursor::new(input), &mut output, decoder.as_mut())?;
+    Ok(output.into_inner())
+}
+
+/// Result of compressing the same input with several algorithms
+pub struct BestOf {
+    /// Algorithm that produced the smallest output
+    pub algorithm: Algorithm,
+    /// Compressed output of the winning algorithm
+    pub output: Vec<u8>,
+    /// Compressed length achieved by each candidate, in the order they were given
+    pub candidates: Vec<(Algorithm, usize)>,
+}
+
+/// Compresses the whole input with each of the algorithms at its highest compression level
+/// and keeps the smallest output. Ties are won by the algorithm given first.
+/// If `chunk_size` is not given, the size recommended for each algorithm is used.
+pub fn compress_best_of(
+    input: &[u8],
+    algorithms: &[Algorithm],
+    chunk_size: Option<usize>,
+    dict: Option<&[u8]>,
+) -> anyhow::Result<BestOf> {
+    let mut best: Option<(Algorithm, Vec<u8>)> = None;
+    let mut candidates = Vec::with_capacity(algorithms.len());
+    for &algorithm in algorithms {
+        let level = *algorithm.get_compression_levels().last().unwrap();
+        let chunk_size = chunk_size.unwrap_or_else(|| algorithm.recommended_chunk_size());
+        let output = compress_bytes(input, algorithm, level, chunk_size, dict)
+            .with_context(|| format!("Failed to compress with {}", algorithm.name()))?;
+        candidates.push((algorithm, output.len()));
+        if best.as_ref().is_none_or(|(_, b)| output.len() < b.len()) {
+            best = Some((algorithm, output));
+        }
+    }
+    let Some((algorithm, output)) = best else {
+        bail!("No algorithms to choose from");
+    };
+    Ok(BestOf {
+        algorithm,
+        output,
+        candidates,
+    })
+}
+
+/// Measure performance of compression or decompression
+fn measure<I: Seek, O: Seek, T>(
+    mut input: I,
+    mut output: O,
+    mut process: impl FnMut(&mut I, &mut O) -> anyhow::Result<T>,
+) -> anyhow::Result<Measurement> {
+    let input_start = input.stream_position()?;
+    let output_start = output.stream_position()?;
+    let start_time = Instant::now();
+    let start_cpu_time = cpu::cpu_time();
+    process(&mut input, &mut output)?;
+    let end_time = Instant::now();
+    let end_cpu_time = cpu::cpu_time();
+    let input_pos = input.stream_position()?;
+    let output_pos = output.stream_position()?;
+
+    Ok(Measurement {
+        input_len: input_pos - input_start,
+        output_len: output_pos - output_start,
+        elapsed: end_time - start_time,
+        cpu_time: end_cpu_time.saturating_sub(start_cpu_time),
+    })
+}
+
+/// Computes the position a seek moves to, given the current position and the end of the stream.
+/// Shared by the in-memory readers and writers, which only track positions.
+/// Like with files, seeking past the end doesn't make the output longer until written to.
+pub(crate) fn seek_position(pos: SeekFrom, current: u64, end: u64) -> io::Result<u64> {
+    let (base_pos, offset) = match pos {
+        SeekFrom::Start(count) => (count, 0),
+        SeekFrom::End(count) => (end, count),
+        SeekFrom::Current(count) => (current, count),
+    };
+    base_pos.checked_add_signed(offset).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
@@ -1,22 +1,41 @@
-mod codec;
-mod discard;
-
-use crate::discard::Discard;
-use anyhow::bail;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use anyhow::{bail, Context};
 use clap::{Args, Parser, Subcommand, ValueEnum};
-use codec::{brotli, lzma};
-use std::cmp::min;
-use std::ffi::OsStr;
+use compresto::archive::{TarReader, TarWriter};
+use compresto::checksum::Checksum;
+use compresto::codec;
+use compresto::codec::brotli::BrotliMode;
+use compresto::codec::zstd::CoverParams;
+use compresto::compare::Compare;
+use compresto::discard::Discard;
+use compresto::raw::{check_compression, compress_raw, decompress_raw};
+use compresto::reader::{RangeReader, StreamReader};
+use compresto::synthetic::Synthetic;
+use compresto::sysinfo::SystemInfo;
+use compresto::transform::Transform;
+use compresto::{
+    check_single_block_len, compress_adaptive, compress_best_of, compress_single_block,
+    compress_to_many, compress_with_options, decoder, decompress_single_block_with_options,
+    decompress_with_options, encoder, entropy, memory, read_header, scan_frames, Algorithm,
+    CodecOptions, ContainerOptions, DecompressionOptions, FrameInfo, Measurement, Preset, Ratio,
+    Units, DEFAULT_MAX_FRAME_LEN, MAX_FRAME_LEN,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde::Serialize;
+use std::cmp::{min, Ordering};
+use std::ffi::OsString;
 use std::fmt::{Display, Formatter};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{BufRead, BufReader, Cursor, Error, Read, Seek, Write};
-use std::path::{Path, PathBuf};
+use std::io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
 use std::process::exit;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, Instant};
-use human_bytes::human_bytes;
-use serde::Serialize;
 
 #[derive(Parser)]
 struct Config {
@@ -26,29 +45,66 @@ struct Config {
 
 #[derive(Subcommand)]
 enum Command {
-    /// Compress a file
+    /// Compress a file, or a directory as a tar archive
     Compress(CompressionCfg),
     /// Decompress a file
     Decompress(DecompressionCfg),
     /// Benchmark compression+decompression of a single file
     Benchmark(CompressionCfg),
+    /// Benchmark decompression of an already compressed file
+    BenchmarkDecompress(BenchmarkDecompressionCfg),
     /// Run multiple benchmarks
     BenchmarkMany(BenchmarkManyCfg),
+    /// Check that a file is restored exactly after compression and decompression
+    Verify(CompressionCfg),
+    /// Train a zstd dictionary from sample files
+    TrainDict(TrainDictCfg),
+    /// Show which frames of a compressed file contain the given uncompressed byte ranges
+    MapOffsets(MapOffsetsCfg),
+    /// List the supported algorithms with their file extensions and compression levels
+    ListAlgorithms(ListAlgorithmsCfg),
 }
 
 #[derive(Args, Clone)]
 struct InputCfg {
-    /// Input file path
+    /// Input file path, or - to compress the standard input
     #[arg()]
     path: PathBuf,
 
-    /// Path to a dictionary file
-    #[arg(long, short = 'd')]
+    #[clap(flatten)]
+    dictionary: DictionaryCfg,
+}
+
+#[derive(Args, Clone)]
+struct DictionaryCfg {
+    /// Path to a dictionary file, or - to read the dictionary from the standard input
+    #[arg(long, short = 'd', env = "COMPRESTO_DICT")]
     dict: Option<PathBuf>,
 
     /// Length of the dictionary prefix to use
-    #[arg(long, default_value = "16384")]
-    dict_len: u64,
+    #[arg(long, default_value = "16384", value_parser = parse_size)]
+    dict_len: usize,
+}
+
+#[derive(Args, Clone, Default)]
+struct OutputCfg {
+    /// Output file path. If not given, derived from the input file path.
+    #[arg(long = "output", short = 'o', value_name = "PATH", conflicts_with_all = ["output_prefix", "output_suffix"])]
+    output_path: Option<PathBuf>,
+
+    /// Prefix prepended to the derived output file name
+    #[arg(long, default_value = "", value_parser = parse_file_name_part)]
+    output_prefix: String,
+
+    /// Suffix appended to the derived output file name, before the algorithm extension
+    #[arg(long, default_value = "", value_parser = parse_file_name_part)]
+    output_suffix: String,
+
+    /// Permissions of the output file, given in octal, e.g. 640.
+    /// If not given, new files get the default permissions limited by umask.
+    #[cfg(unix)]
+    #[arg(long, value_name = "OCTAL", value_parser = parse_mode)]
+    mode: Option<u32>,
 }
 
 #[derive(Args)]
@@ -56,343 +112,3111 @@ struct CompressionCfg {
     #[clap(flatten)]
     input: InputCfg,
 
+    #[clap(flatten)]
+    output: OutputCfg,
+
     /// Compression algorithm
     #[arg(long, short = 'a', default_value = "zstd")]
     algorithm: Algorithm,
 
-    /// Compression level
-    #[arg(long, short = 'c', default_value = "1", allow_hyphen_values = true)]
-    compression: i32,
+    /// Compression level, or a preset of fast, default or best mapped to a level
+    /// of the algorithm, e.g. best is zstd level 19, brotli level 11 and lz4 level 12.
+    /// Defaults to 1, or to the closest level of algorithms not supporting it.
+    #[arg(long, short = 'c', allow_hyphen_values = true, value_parser = parse_level)]
+    compression: Option<Level>,
 
-    /// Size of a file chunk in bytes. Each chunk is compressed independently.
-    #[arg(long, short = 'b', default_value = "16384")]
-    chunk_size: usize,
+    /// Size of a file chunk in bytes, at most 2 GiB minus one byte.
+    /// Each chunk is compressed independently.
+    /// If not given, the size recommended for the algorithm is used.
+    #[arg(long, short = 'b', value_parser = parse_size)]
+    chunk_size: Option<usize>,
+
+    /// Enable zstd long distance matching with the given window log
+    #[arg(long, value_name = "WINDOW_LOG", num_args = 0..=1, require_equals = true,
+          default_missing_value = "27",
+          value_parser = clap::value_parser!(u32).range(10..=31))]
+    long: Option<u32>,
+
+    /// Use the extreme variant of the lzma preset selected with -c (like xz -9e)
+    #[arg(long, short = 'e')]
+    extreme: bool,
+
+    /// Allow zstd compression levels 20 to 22, which need much more memory
+    #[arg(long)]
+    ultra: bool,
+
+    /// Acceleration of the lz4 fast mode selected with -c 0, from 1 (default) to 65537.
+    /// Higher values compress faster but less.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=codec::lz4::MAX_ACCELERATION as i64))]
+    acceleration: Option<u32>,
+
+    /// Brotli window log, from 10 to 30; 22 by default.
+    /// Windows above 24 are recorded in the header, but single blocks written with them
+    /// need decompress --brotli-large-window.
+    #[arg(long, value_name = "WINDOW_LOG",
+          value_parser = clap::value_parser!(u32).range(codec::brotli::MIN_WINDOW as i64..=codec::brotli::MAX_WINDOW as i64))]
+    brotli_window: Option<u32>,
+
+    /// Kind of the input the brotli encoder is tuned for
+    #[arg(long, value_enum, default_value = "generic")]
+    brotli_mode: BrotliMode,
+
+    /// Dictionary id written to zstd frame headers, replacing the one stored in the dictionary
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    zstd_dict_id: Option<u32>,
+
+    /// Reversible transform applied to each chunk before compression:
+    /// none, delta[:N] (differences of bytes N positions apart, 1 by default)
+    /// or transpose:N (N-byte rows stored by columns).
+    /// Recorded in the output, so decompression reverses it automatically.
+    #[arg(long, visible_alias = "filter", default_value = "none", value_parser = Transform::from_str)]
+    transform: Transform,
+
+    /// Checksum of each uncompressed chunk stored after its frame and verified on decompression
+    #[arg(long, value_enum, default_value = "none")]
+    checksum: Checksum,
+
+    /// Store the xxh3 hash of the whole uncompressed input at the end of the output
+    /// and verify it on decompression. Detects also missing or reordered chunks.
+    #[arg(long)]
+    footer_checksum: bool,
+
+    /// Replace the extension of the input file with the algorithm extension,
+    /// e.g. compress foo.txt to foo.zstd instead of foo.txt.zstd.
+    /// The original extension is recorded in the output and restored on decompression.
+    #[arg(long)]
+    strip_extension: bool,
+
+    /// Start with 4 KiB chunks and double the size of each next chunk, up to the chunk size.
+    /// Gives readers of a stream the first data early and a good ratio later.
+    #[arg(long)]
+    adaptive_chunks: bool,
+
+    /// Do not display the progress bar
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
+    /// Units of the printed sizes and throughputs: si for multiples of 1000, e.g. kB and MB/s,
+    /// or iec for multiples of 1024, e.g. KiB and MiB/s.
+    /// JSON results give throughputs in MB/s regardless.
+    #[arg(long, value_enum, default_value = "si")]
+    units: Units,
+
+    /// Print the result as a JSON object to the standard output
+    /// instead of a line of text to the standard error
+    #[arg(long, conflicts_with_all = ["best_of", "also", "dry_run", "show_frame_entropy"])]
+    json: bool,
+
+    /// Write a single native stream of the codec instead of the chunked container,
+    /// readable by the standard tools of zstd, lz4, xz, bzip2, zlib and snappy.
+    /// The whole input shares one compression context, which finds repetitions across chunks,
+    /// but such output cannot be randomly accessed or appended to.
+    #[arg(long, visible_alias = "streaming",
+          conflicts_with_all = ["chunk_size", "transform", "checksum", "footer_checksum", "strip_extension", "adaptive_chunks", "append", "show_frame_entropy", "frame_entropy_report", "per_chunk_stats", "best_of", "also"])]
+    raw: bool,
+
+    /// Compress the whole file in one block preceded only by its uncompressed length,
+    /// without the header and frame lengths of the chunked container.
+    /// The most compact format for small files, which must fit in the block size limit of the codec.
+    #[arg(long,
+          conflicts_with_all = ["raw", "chunk_size", "transform", "checksum", "footer_checksum", "strip_extension", "adaptive_chunks", "append", "show_frame_entropy", "frame_entropy_report", "per_chunk_stats", "best_of", "also"])]
+    single_block: bool,
+
+    /// Append the compressed data to the output file instead of overwriting it
+    #[arg(long)]
+    append: bool,
+
+    /// Delete the input file after the output has been written and synced to disk
+    #[arg(long, visible_alias = "rm")]
+    remove_source: bool,
+
+    /// Start reading the input file at the given byte offset
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["remove_source", "synthetic"])]
+    offset: Option<usize>,
+
+    /// Read at most the given number of bytes of the input file, e.g. for compressing a sample
+    /// of a large file
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["remove_source", "synthetic"])]
+    length: Option<usize>,
+
+    /// Expected length of the standard input, used to show the progress and the remaining time.
+    /// Chunks are made no larger than the expected input, so their buffers are sized for it.
+    /// The input may turn out shorter or longer. Ignored for files, whose length is known.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    input_size: Option<usize>,
+
+    /// Only print the algorithm and the path of each output file, without compressing
+    #[arg(long, conflicts_with_all = ["remove_source", "best_of"])]
+    dry_run: bool,
+
+    /// Overwrite output files if they exist
+    #[arg(long, short = 'f')]
+    force: bool,
+
+    /// Print the entropy and the achieved compression ratio of each chunk
+    #[arg(long)]
+    show_frame_entropy: bool,
+
+    /// Save the entropy and the achieved compression ratio of each chunk to a CSV file
+    #[arg(long)]
+    frame_entropy_report: Option<PathBuf>,
+
+    /// Print the minimum, median and maximum compression ratio of the chunks
+    #[arg(long)]
+    per_chunk_stats: bool,
+
+    /// Measure the time from the start of decompression until the first decompressed byte
+    /// is written out
+    #[arg(long)]
+    ttfb: bool,
+
+    /// Measure the compression time of each chunk and report its 50th, 95th and 99th
+    /// percentile, which show the tail latency hidden by the average throughput
+    #[arg(long)]
+    chunk_latency: bool,
+
+    /// Compress each chunk also with the given algorithm and print the compressed sizes
+    /// of both algorithms chunk by chunk, instead of measuring the speed
+    #[arg(long, value_name = "ALGORITHM", conflicts_with_all = ["transform", "adaptive_chunks", "json", "per_chunk_stats", "chunk_latency", "verify"])]
+    compare_with: Option<Algorithm>,
+
+    /// Benchmark data generated in memory instead of reading a file.
+    /// The input path then describes the data as KIND:SIZE,
+    /// where KIND is zeros, repeating, text or random, e.g. text:16Mi.
+    #[arg(long)]
+    synthetic: bool,
+
+    /// Seed of the random generator of synthetic data
+    #[arg(long, default_value = "0", requires = "synthetic")]
+    seed: u64,
+
+    /// Decompress once more after the measurement and check that the result is identical
+    /// to the input, reporting the offset of the first differing byte
+    #[arg(long)]
+    verify: bool,
+
+    /// Pin the benchmark to the CPU core with the given index to reduce the variance
+    /// caused by moving it between cores. Ignored with a warning on unsupported platforms.
+    #[arg(long, value_name = "N")]
+    cpu_affinity: Option<usize>,
+
+    /// Print a status line to stderr at most every given number of seconds.
+    /// Unlike the progress bar, the status lines are suitable for log files.
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    status_interval: Option<Duration>,
+
+    /// Compress the whole file with each of the given algorithms at the highest level
+    /// and keep only the smallest result
+    #[arg(long, value_delimiter = ',', num_args = 1..,
+          conflicts_with_all = ["algorithm", "compression", "transform", "checksum", "footer_checksum", "strip_extension", "adaptive_chunks", "append", "show_frame_entropy", "frame_entropy_report"])]
+    best_of: Vec<Algorithm>,
+
+    /// Additionally compress the file with the given algorithms in the same pass,
+    /// each to its own output file
+    #[arg(long, value_delimiter = ',', num_args = 1..,
+          conflicts_with_all = ["output_path", "adaptive_chunks", "append", "best_of", "show_frame_entropy", "frame_entropy_report"])]
+    also: Vec<Algorithm>,
+}
+
+impl CompressionCfg {
+    fn reads_stdin(&self) -> bool {
+        self.input.path == Path::new("-")
+    }
+
+    /// Returns the level given by the user or the level of the preset for the algorithm
+    fn level(&self) -> i32 {
+        self.level_for(self.algorithm)
+    }
+
+    /// Returns the level given with -c for the algorithm,
+    /// or the default level 1 moved into the range of levels the algorithm supports
+    fn level_for(&self, algorithm: Algorithm) -> i32 {
+        match self.compression {
+            Some(level) => level.for_algorithm(algorithm),
+            None => {
+                let range = algorithm.compression_level_range();
+                1.clamp(*range.start(), *range.end())
+            }
+        }
+    }
+
+    /// Returns the chunk size given by the user or the one recommended for the algorithm
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+            .unwrap_or_else(|| self.algorithm.recommended_chunk_size())
+    }
+
+    /// Sets the chunk size recommended for the algorithm if the user didn't give one,
+    /// telling the user about it. Left unset for `--best-of`, which uses the size
+    /// recommended for each of its algorithms.
+    fn with_recommended_chunk_size(mut self) -> Self {
+        if self.chunk_size.is_none() && self.best_of.is_empty() {
+            let chunk_size = self.algorithm.recommended_chunk_size();
+            eprintln!(
+                "Using chunk size of {} recommended for {}",
+                chunk_size,
+                self.algorithm.name()
+            );
+            self.chunk_size = Some(chunk_size);
+        }
+        self
+    }
+
+    fn codec_options(&self) -> CodecOptions {
+        CodecOptions {
+            zstd_long: self.long,
+            lzma_extreme: self.extreme,
+            zstd_ultra: self.ultra,
+            zstd_dict_id: self.zstd_dict_id,
+            lz4_acceleration: self.acceleration,
+            brotli_window: self.brotli_window,
+            brotli_mode: self.brotli_mode,
+        }
+    }
+
+    fn container_options(&self) -> anyhow::Result<ContainerOptions> {
+        let original_extension = match self.input.path.extension() {
+            Some(extension) if self.strip_extension => match extension.to_str() {
+                Some(extension) => Some(extension.to_owned()),
+                None => bail!(
+                    "The extension of {} is not valid UTF-8, so it can't be stripped",
+                    self.input.path.display()
+                ),
+            },
+            _ => None,
+        };
+        Ok(ContainerOptions {
+            transform: self.transform,
+            checksum: self.checksum,
+            footer_checksum: self.footer_checksum,
+            original_extension,
+            tar: self.input.path.is_dir(),
+            algorithm: Some(self.algorithm),
+            brotli_large_window: codec::brotli::is_large_window(self.brotli_window),
+        })
+    }
+
+    /// Returns the algorithm selected with -a followed by the distinct ones given with --also
+    fn algorithms(&self) -> Vec<Algorithm> {
+        let mut algorithms = vec![self.algorithm];
+        for &algorithm in &self.also {
+            if !algorithms.contains(&algorithm) {
+                algorithms.push(algorithm);
+            }
+        }
+        algorithms
+    }
+
+    /// Returns the path the output file name is derived from
+    fn output_base_path(&self) -> PathBuf {
+        if self.input.path.is_dir() {
+            let mut path = self.input.path.clone().into_os_string();
+            path.push(".tar");
+            PathBuf::from(path)
+        } else if self.strip_extension {
+            self.input.path.with_extension("")
+        } else {
+            self.input.path.clone()
+        }
+    }
 }
 
+/// Settings of reading a compressed stream, shared by decompress and benchmark-decompress
 #[derive(Args)]
-struct DecompressionCfg {
+struct StreamCfg {
     #[clap(flatten)]
     input: InputCfg,
 
-    /// Compression algorithm. If not given, determined automatically from the file extension.
+    /// Compression algorithm. If not given, read from the header of the stream
+    /// or, for streams not recording it, determined from the file extension.
     #[clap(long, short = 'a')]
     algorithm: Option<Algorithm>,
+
+    /// Fail instead of warning when the algorithm given with -a doesn't match the one recorded
+    /// in the header or, for streams not recording it, the file extension
+    #[arg(long)]
+    strict: bool,
+
+    /// Try to decompress files written with an unsupported container version,
+    /// decoding their frames as if they had the current format
+    #[arg(long)]
+    ignore_version: bool,
+
+    /// Allow zstd windows up to the given window log, as needed by files compressed with --long
+    #[arg(long, value_name = "WINDOW_LOG", num_args = 0..=1, require_equals = true,
+          default_missing_value = "27",
+          value_parser = clap::value_parser!(u32).range(10..=31))]
+    long: Option<u32>,
+
+    /// Accept brotli streams with a window above 24, as written by compress --brotli-window
+    /// with such a window log. Such streams don't conform to RFC 7932.
+    /// Needed only by streams not recording the window in a header, i.e. single blocks
+    /// and containers written before version 6.
+    #[arg(long)]
+    brotli_large_window: bool,
+
+    /// Dictionary id expected in zstd frame headers, if it was overridden during compression
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    zstd_dict_id: Option<u32>,
+
+    /// Capacity of the buffer the compressed file is read through.
+    /// Frames longer than that are copied to a separate buffer before decoding.
+    #[arg(long, value_name = "SIZE", default_value = "1Mi", value_parser = parse_size)]
+    read_buffer: usize,
+
+    /// Reject frames longer than SIZE, compressed or uncompressed, before allocating memory
+    /// for them. Limits the memory a corrupted or malicious file can make decompression use.
+    /// Defaults to 256Mi; files compressed with longer chunks need a higher limit.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_frame: Option<usize>,
+
+    /// Read a single native stream of the codec, as written by compress --raw
+    /// or by the standard tools of the codec, instead of the chunked container
+    #[arg(long, visible_alias = "streaming")]
+    raw: bool,
+
+    /// Read a single block written by compress --single-block instead of the chunked container
+    #[arg(long, conflicts_with = "raw")]
+    single_block: bool,
+
+    /// Read a chunked container written before it had a header frame, without looking for one.
+    /// Such files don't record their properties, so the algorithm must be given with -a.
+    #[arg(long, visible_alias = "legacy", requires = "algorithm",
+          conflicts_with_all = ["raw", "single_block"])]
+    no_header: bool,
+}
+
+#[derive(Args)]
+struct DecompressionCfg {
+    #[clap(flatten)]
+    stream: StreamCfg,
+
+    #[clap(flatten)]
+    output: OutputCfg,
+
+    /// Overwrite the output file if it exists
+    #[arg(long, short = 'f')]
+    force: bool,
+
+    /// Only check that the file decompresses correctly, without writing any output.
+    /// Prints the algorithm and the path of the output file that would be written.
+    #[arg(long, conflicts_with = "json")]
+    dry_run: bool,
+
+    /// Delete the input file after the output has been written and synced to disk
+    #[arg(long, visible_alias = "rm", conflicts_with = "dry_run")]
+    remove_source: bool,
+
+    /// Do not display the progress bar
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
+    /// Units of the printed sizes and throughputs: si for multiples of 1000, e.g. kB and MB/s,
+    /// or iec for multiples of 1024, e.g. KiB and MiB/s.
+    /// JSON results give throughputs in MB/s regardless.
+    #[arg(long, value_enum, default_value = "si")]
+    units: Units,
+
+    /// Print the result as a JSON object to the standard output
+    /// instead of a line of text to the standard error
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct BenchmarkDecompressionCfg {
+    #[clap(flatten)]
+    stream: StreamCfg,
+
+    /// Units of the printed sizes and throughputs: si for multiples of 1000, e.g. kB and MB/s,
+    /// or iec for multiples of 1024, e.g. KiB and MiB/s.
+    /// JSON results give throughputs in MB/s regardless.
+    #[arg(long, value_enum, default_value = "si")]
+    units: Units,
+
+    /// Print the result as a JSON object to the standard output
+    /// instead of a line of text to the standard error
+    #[arg(long)]
+    json: bool,
 }
 
 #[derive(Args)]
 struct BenchmarkManyCfg {
+    /// Input file paths
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
     #[clap(flatten)]
-    input: InputCfg,
+    dictionary: DictionaryCfg,
 
-    /// List of algorithms to benchmark
-    #[arg(long, short = 'a', value_delimiter = ',', default_value = "lz4,lzav,snappy,zstd,brotli", num_args = 1..)]
-    algorithms: Vec<Algorithm>,
+    /// Benchmark the concatenation of all input files, instead of each file separately
+    #[arg(long)]
+    combine: bool,
 
-    /// Size of a file chunk in bytes. Each chunk is compressed independently.
-    #[arg(long, short = 'b', default_value = "16384")]
-    chunk_size: usize,
+    /// Benchmark only the first N bytes of each input file, for a quick estimate on large files.
+    /// The results of files cut this way are marked as sampled.
+    #[arg(long, value_name = "N", value_parser = parse_size)]
+    sample_bytes: Option<usize>,
+
+    /// List of algorithms to benchmark.
+    /// Also accepts the groups all, all-fast with the codecs using no entropy coding except copy
+    /// and all-strong with the other ones.
+    #[arg(long, short = 'a', value_delimiter = ',', default_value = "lz4,lzav,snappy,zstd,brotli", num_args = 1..,
+          value_parser = parse_algorithms)]
+    algorithms: Vec<Algorithms>,
+
+    /// Don't benchmark copy before the other algorithms.
+    /// Copying measures the cost of reading, framing and writing the data,
+    /// which is included in the results of every algorithm.
+    #[arg(long)]
+    no_baseline: bool,
+
+    /// Size of a file chunk in bytes, at most 2 GiB minus one byte.
+    /// Each chunk is compressed independently.
+    /// Sizes of particular algorithms or groups of algorithms can be given as ALGORITHM=SIZE,
+    /// e.g. 256Ki,zstd=1Mi,lz4=64Ki.
+    /// Algorithms without a size use the size recommended for them.
+    #[arg(long, short = 'b', value_parser = parse_chunk_sizes)]
+    chunk_size: Option<ChunkSizes>,
+
+    /// Compression levels to benchmark for each algorithm, instead of the default ones.
+    /// Given as a comma-separated list of levels or ranges including both ends, e.g. 1,3,5..9.
+    /// Levels not supported by an algorithm are skipped.
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_levels)]
+    levels: Option<Levels>,
+
+    /// Also benchmark zstd levels 20 to 22
+    #[arg(long)]
+    ultra: bool,
+
+    /// Benchmark only every Nth of the default levels of each algorithm, starting with the first,
+    /// for a quick overview. Step 1 benchmarks all of them.
+    #[arg(long, value_name = "N", default_value = "1", conflicts_with = "levels")]
+    step: NonZeroUsize,
+
+    /// Comma-separated accelerations of the lz4 fast mode to benchmark lz4 level 0 with,
+    /// e.g. 1,2,4,8
+    #[arg(long, value_delimiter = ',',
+          value_parser = clap::value_parser!(u32).range(1..=codec::lz4::MAX_ACCELERATION as i64))]
+    acceleration: Vec<u32>,
+
+    /// Comma-separated transforms to benchmark each algorithm and level with,
+    /// in the format accepted by compress --transform
+    #[arg(long, visible_alias = "filter", value_delimiter = ',', default_value = "none",
+          value_parser = Transform::from_str)]
+    transform: Vec<Transform>,
+
+    /// Maximum number of benchmarks run concurrently. Defaults to the number of CPU cores.
+    /// Peak memory is measured for the whole process, so use 1 to get it for each benchmark separately.
+    /// CPU time is measured for the whole process as well, so it is reported only with 1.
+    #[arg(long, short = 'j')]
+    jobs: Option<NonZeroUsize>,
+
+    /// Order of the printed results.
+    /// If not given, results are listed in the order the algorithms and levels were given.
+    /// Saved reports are always sorted by the algorithm name, the level, the acceleration,
+    /// the chunk size and the filter, so reports of repeated runs can be compared line by line.
+    #[arg(long, value_enum)]
+    sort_by: Option<SortBy>,
+
+    /// Keep only the results not beaten by another one in both the ratio and the compression speed
+    /// and sort them by ratio
+    #[arg(long, conflicts_with = "sort_by")]
+    pareto: bool,
+
+    /// Print the CPU model, the number of cores and the versions of the codec libraries.
+    /// They are also written as comment lines at the start of the report.
+    #[arg(long)]
+    sysinfo: bool,
+
+    /// Check that each decompressed result is identical to the input.
+    /// The check runs after the measured decompression, so it doesn't affect the results.
+    #[arg(long)]
+    verify: bool,
+
+    /// Print the results and the summary of each input as JSON instead of a table
+    #[arg(long)]
+    json: bool,
+
+    /// Units of the printed sizes and throughputs: si for multiples of 1000, e.g. kB and MB/s,
+    /// or iec for multiples of 1024, e.g. KiB and MiB/s.
+    /// Reports and JSON results give throughputs in MB/s regardless.
+    #[arg(long, value_enum, default_value = "si")]
+    units: Units,
 
     /// Save benchmark results to a CSV file
     #[arg(long, short)]
-    report: Option<PathBuf>
+    report: Option<PathBuf>,
+
+    /// Create the directory and save there the results as results.csv
+    /// and the rows of each algorithm as <ALGORITHM>.txt
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Also save the compressed output of each run to the output directory,
+    /// named after the input file, the algorithm, the level and the filter
+    #[arg(long, requires = "output_dir")]
+    keep_compressed: bool,
 }
 
-#[derive(ValueEnum, Copy, Clone, Serialize)]
-enum Algorithm {
-    Copy,
-    Lz4,
-    Zstd,
-    Brotli,
-    Snappy,
-    Lzma,
-    Lzav,
+/// Sorting order of benchmark results, the best ones first
+#[derive(Clone, Copy, ValueEnum)]
+enum SortBy {
+    /// Lowest compressed to uncompressed size ratio first
+    Ratio,
+    /// Highest compression throughput first
+    CompressSpeed,
+    /// Highest decompression throughput first
+    DecompressSpeed,
+    /// Alphabetically by algorithm name, then by level
+    Name,
 }
 
-impl Algorithm {
-    fn extension(&self) -> &str {
-        match self {
-            Algorithm::Copy => "bak",
-            Algorithm::Zstd => "zstd",
-            Algorithm::Lz4 => "lz4",
-            Algorithm::Brotli => "br",
-            Algorithm::Snappy => "sz",
-            Algorithm::Lzma => "xz",
-            Algorithm::Lzav => "lzav",
-        }
-    }
-
-    fn from_file_name(path: &Path) -> Option<Algorithm> {
-        match path.extension().and_then(OsStr::to_str) {
-            Some("bak") => Some(Self::Copy),
-            Some("zstd") => Some(Self::Zstd),
-            Some("lz4") => Some(Self::Lz4),
-            Some("br") => Some(Self::Brotli),
-            Some("sz") => Some(Self::Snappy),
-            Some("xz") => Some(Self::Lzma),
-            Some("lzav") => Some(Self::Lzav),
-            _ => None,
-        }
-    }
+#[derive(Args)]
+struct MapOffsetsCfg {
+    /// Compressed file path
+    #[arg()]
+    path: PathBuf,
 
-    fn get_compression_levels(&self) -> Vec<i32> {
-        match self {
-            Algorithm::Copy => vec![0],
-            Algorithm::Zstd => Vec::from_iter((-7..=-1).chain(1..=12)),
-            Algorithm::Lz4 => Vec::from_iter((-9..=-1).chain(1..=9)),
-            Algorithm::Brotli => Vec::from_iter(1..=8),
-            Algorithm::Snappy => vec![0],
-            Algorithm::Lzma => Vec::from_iter(1..9),
-            Algorithm::Lzav => vec![0, 1],
-        }
-    }
+    /// Uncompressed byte ranges, given as start-end with the end excluded
+    #[arg(long, short = 'r', value_delimiter = ',', required = true, value_parser = parse_range)]
+    ranges: Vec<Range<u64>>,
 }
 
-struct Measurement {
-    input_len: u64,
-    output_len: u64,
-    elapsed: Duration,
+#[derive(Args)]
+struct ListAlgorithmsCfg {
+    /// Print the algorithms as a JSON array
+    #[arg(long)]
+    json: bool,
 }
 
-impl Measurement {
-    fn compression_ratio(&self) -> f64 {
-        self.output_len as f64 / self.input_len as f64
-    }
+#[derive(Args)]
+struct TrainDictCfg {
+    /// Sample files or directories containing sample files
+    #[arg(required = true)]
+    samples: Vec<PathBuf>,
 
-    fn input_throughtput(&self) -> f64 {
-        self.input_len as f64 / self.elapsed.as_secs_f64()
-    }
+    /// Path of the dictionary file to write
+    #[arg(long, short = 'o')]
+    output: PathBuf,
 
-    fn output_throughtput(&self) -> f64 {
-        self.output_len as f64 / self.elapsed.as_secs_f64()
-    }
+    /// Maximum size of the dictionary in bytes
+    #[arg(long, default_value = "112640", value_parser = parse_size)]
+    dict_size: usize,
 
-    fn format_compression(&self) -> String {
-        format!(
-            "{} => {} ({:.1} %)",
-            self.input_len,
-            self.output_len,
-            self.compression_ratio() * 100.0
-        )
-    }
+    /// Train with the COVER algorithm, trying several values of its parameters not given
+    /// with --cover-k and --cover-d and keeping the best dictionary.
+    /// Slower than the default trainer, but often gives smaller compressed samples.
+    #[arg(long)]
+    cover: bool,
+
+    /// Length of the segments selected by the COVER algorithm, implies --cover
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u32).range(1..))]
+    cover_k: Option<u32>,
+
+    /// Length of the substrings scoring the segments in the COVER algorithm, implies --cover
+    #[arg(long, value_name = "D", value_parser = clap::value_parser!(u32).range(6..=16))]
+    cover_d: Option<u32>,
+
+    /// Number of values of K tried by the COVER algorithm if --cover-k is not given
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    cover_steps: Option<u32>,
+
+    /// Leave every Nth sample out of training and report the compression ratio
+    /// of the left out samples with and without the dictionary
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(2..))]
+    holdout: Option<u32>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 struct BenchmarkResult {
+    /// Input file path, or the paths of combined input files joined with `+`
+    file: String,
     algorithm: Algorithm,
     level: i32,
+    /// Acceleration of the lz4 fast mode, if given
+    acceleration: Option<u32>,
+    chunk_size: usize,
+    filter: Option<String>,
+    /// Total size of the input file, or of the benchmarked part of it if sampled
     uncompr_len: u64,
+    /// Only a part of the input file was benchmarked, selected with --offset and --length
+    /// or with --sample-bytes
+    sampled: bool,
     compr_len: u64,
     ratio: f64,
     inv_ratio: f64,
+    /// Throughputs in MB/s, so reports of runs with different units can be compared
     compr_mbps: f64,
     decompr_mbps: f64,
+    /// CPU time of all threads of the process, for computing the cost of parallel compression.
+    /// Missing if other benchmarks ran at the same time, as their CPU time would be included.
+    compr_cpu_secs: Option<f64>,
+    decompr_cpu_secs: Option<f64>,
+    peak_memory_bytes: u64,
+    ttfb_ms: Option<f64>,
+    /// Percentiles of the compression time of a single chunk
+    chunk_p50_ms: Option<f64>,
+    chunk_p95_ms: Option<f64>,
+    chunk_p99_ms: Option<f64>,
+    /// Units of the throughputs and of the printed sizes
+    #[serde(skip)]
+    units: Units,
 }
 
 impl BenchmarkResult {
-    fn new(cfg: CompressionCfg, compression: Measurement, decompression: Measurement) -> Self {
+    fn new(
+        cfg: CompressionCfg,
+        compression: Measurement,
+        decompression: Measurement,
+        peak_memory_bytes: u64,
+        time_to_first_byte: Option<Duration>,
+        mut chunk_times: Vec<Duration>,
+    ) -> Self {
+        let ratio = compression.compression_ratio();
+        chunk_times.sort_unstable();
+        let chunk_ms =
+            |percent| percentile(&chunk_times, percent).map(|t| round(t.as_secs_f64() * 1000.0, 3));
         Self {
+            file: cfg.input.path.display().to_string(),
             algorithm: cfg.algorithm,
-            level: cfg.compression,
+            level: cfg.level(),
+            acceleration: cfg.acceleration,
+            chunk_size: cfg.chunk_size(),
+            filter: (cfg.transform != Transform::None).then(|| cfg.transform.to_string()),
             uncompr_len: compression.input_len,
+            sampled: cfg.offset.is_some() || cfg.length.is_some(),
             compr_len: compression.output_len,
-            ratio: (compression.compression_ratio() * 1000.0).round() / 1000.0,
-            inv_ratio: (1.0 / compression.compression_ratio() * 1000.0).round() / 1000.0, 
-            compr_mbps: (compression.input_throughtput() / 100_000.0).round() / 10.0,
-            decompr_mbps: (decompression.output_throughtput() / 100_000.0).round() / 10.0,
+            ratio: round(ratio.as_fraction(), 3),
+            inv_ratio: round(ratio.as_factor(), 3),
+            compr_mbps: round(Units::Si.megabytes(compression.input_throughtput()), 1),
+            decompr_mbps: round(Units::Si.megabytes(decompression.output_throughtput()), 1),
+            compr_cpu_secs: Some(round(compression.cpu_time.as_secs_f64(), 6)),
+            decompr_cpu_secs: Some(round(decompression.cpu_time.as_secs_f64(), 6)),
+            peak_memory_bytes,
+            ttfb_ms: time_to_first_byte.map(|t| round(t.as_secs_f64() * 1000.0, 3)),
+            chunk_p50_ms: chunk_ms(50),
+            chunk_p95_ms: chunk_ms(95),
+            chunk_p99_ms: chunk_ms(99),
+            units: cfg.units,
+        }
+    }
+
+    /// Formats a throughput in MB/s in the units of the printed results
+    fn format_throughput(&self, mbps: f64) -> String {
+        self.units.format_throughput(mbps * 1_000_000.0)
+    }
+
+    /// Names the algorithm, the level and the filter, if any
+    fn label(&self) -> String {
+        let mut label = format!("{} level {}", self.algorithm.name(), self.level);
+        if let Some(acceleration) = self.acceleration {
+            label.push_str(&format!(", acceleration {}", acceleration));
+        }
+        if let Some(filter) = &self.filter {
+            label.push_str(&format!(", filter {}", filter));
         }
+        label
     }
 }
 
+/// Returns the value the given percentage of the sorted values doesn't exceed,
+/// using the nearest-rank method, or `None` if there are no values
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+/// Rounds the value to the given number of decimal places
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10_f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
 impl Display for BenchmarkResult {
-    
-    
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Computed again from the lengths, because the stored ratios are rounded
+        let ratio = Ratio::new(self.compr_len, self.uncompr_len);
         write!(
             f,
-            "{:10} lev. {:3}:    {:8} => {:8} ({:5.1}%, {:4.2}x),    compr.: {:6.1} MB/s, decompr.: {:6.1} MB/s",
-            self.algorithm
-                .to_possible_value()
-                .unwrap_or_default()
-                .get_name(),
+            "{:10} lev. {:3}:    {:8} => {:8} ({:>13}),    compr.: {:>11}, decompr.: {:>11}, peak mem.: {:8}",
+            self.algorithm.name(),
             self.level,
-            human_bytes(self.uncompr_len as f64),
-            human_bytes(self.compr_len as f64),
-            self.ratio * 100.0,
-            1.0 / self.ratio,
-            self.compr_mbps,
-            self.decompr_mbps
-        )
+            self.units.format_size(self.uncompr_len as f64),
+            self.units.format_size(self.compr_len as f64),
+            ratio,
+            self.format_throughput(self.compr_mbps),
+            self.format_throughput(self.decompr_mbps),
+            self.units.format_size(self.peak_memory_bytes as f64)
+        )?;
+        if let Some(acceleration) = self.acceleration {
+            write!(f, ", acceleration: {}", acceleration)?;
+        }
+        if let Some(filter) = &self.filter {
+            write!(f, ", filter: {}", filter)?;
+        }
+        if self.sampled {
+            write!(f, ", sampled")?;
+        }
+        if let Some(ttfb_ms) = self.ttfb_ms {
+            write!(f, ", TTFB: {:8.3} ms", ttfb_ms)?;
+        }
+        if let (Some(p50), Some(p95), Some(p99)) =
+            (self.chunk_p50_ms, self.chunk_p95_ms, self.chunk_p99_ms)
+        {
+            write!(
+                f,
+                ", chunk p50/p95/p99: {:.3}/{:.3}/{:.3} ms",
+                p50, p95, p99
+            )?;
+        }
+        Ok(())
     }
 }
 
-fn main() {
-    let cmd = Config::parse();
-    if let Err(e) = run(cmd) {
-        eprintln!("error: {}", e);
-        exit(1);
-    }
+/// Result of a single compression or decompression, printed by --json
+#[derive(Serialize)]
+struct MeasurementReport {
+    input_len: u64,
+    output_len: u64,
+    /// Output length divided by the input length
+    ratio: f64,
+    inv_ratio: f64,
+    elapsed_secs: f64,
+    /// CPU time of all threads
+    cpu_secs: f64,
+    /// Throughput of the uncompressed data in MB/s, whatever the units of printed results
+    mbps: f64,
 }
 
-fn run(cmd: Config) -> anyhow::Result<()> {
-    match cmd.command {
-        Command::Decompress(cfg) => run_decompress_cmd(cfg),
-        Command::Compress(cfg) => run_compress_cmd(cfg),
-        Command::Benchmark(cfg) => run_benchmark_cmd(cfg).map(|_| ()),
-        Command::BenchmarkMany(cfg) => run_benchmark_many_cmd(cfg),
+/// Prints the lengths, the ratio and the throughput of the uncompressed data given in bytes
+/// per second to the standard error, or as JSON to the standard output
+fn print_measurement(
+    result: &Measurement,
+    throughput: f64,
+    units: Units,
+    json: bool,
+) -> anyhow::Result<()> {
+    if !json {
+        eprintln!(
+            "{}, {}",
+            result.format_compression(),
+            units.format_throughput(throughput)
+        );
+        return Ok(());
     }
-}
-
-fn run_decompress_cmd(cfg: DecompressionCfg) -> anyhow::Result<()> {
-    let Some(algorithm) = cfg
-        .algorithm
-        .or_else(|| Algorithm::from_file_name(&cfg.input.path))
-    else {
-        bail!("Cannot determine compression algorithm from the extension. Please use -a/--algorithm option.");
+    let ratio = result.compression_ratio();
+    let report = MeasurementReport {
+        input_len: result.input_len,
+        output_len: result.output_len,
+        ratio: round(ratio.as_fraction(), 3),
+        inv_ratio: round(ratio.as_factor(), 3),
+        elapsed_secs: result.elapsed.as_secs_f64(),
+        cpu_secs: result.cpu_time.as_secs_f64(),
+        mbps: round(Units::Si.megabytes(throughput), 1),
     };
-
-    let dict = dictionary(&cfg.input)?;
-    let mut decoder = decoder(algorithm, dict.as_ref())?;
-    let input = open_input(&cfg.input)?;
-    let output = open_output(&cfg.input.path, algorithm, false)?;
-    let result = decompress(input, output, decoder.as_mut())?;
-    eprintln!(
-        "{}, {:.1} MB/s",
-        result.format_compression(),
-        result.output_throughtput() / 1_000_000.0
-    );
+    println!("{}", serde_json::to_string_pretty(&report)?);
     Ok(())
 }
 
-fn run_compress_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
-    let dict = dictionary(&cfg.input)?;
-    let mut encoder = encoder(cfg.algorithm, cfg.compression, dict.as_ref())?;
-    let input = open_input(&cfg.input)?;
-    let output = open_output(&cfg.input.path, cfg.algorithm, true)?;
-    let result = compress(input, output, cfg.chunk_size, encoder.as_mut())?;
-    eprintln!(
-        "{}, {:.1} MB/s",
-        result.format_compression(),
-        result.input_throughtput() / 1_000_000.0
-    );
-    Ok(())
+/// Results of a single input, printed as JSON by benchmark-many --json
+#[derive(Serialize)]
+struct InputResults {
+    file: String,
+    results: Vec<BenchmarkResult>,
+    summary: Option<BenchmarkSummary>,
 }
 
-fn run_benchmark_cmd(cfg: CompressionCfg) -> anyhow::Result<BenchmarkResult> {
-    let dict = dictionary(&cfg.input)?;
-    let mut encoder = encoder(cfg.algorithm, cfg.compression, dict.as_ref())?;
-    let mut decoder = decoder(cfg.algorithm, dict.as_ref())?;
+/// Settings doing best in each measured metric.
+/// Ties go to the result listed first.
+#[derive(Serialize)]
+struct BenchmarkSummary {
+    best_ratio: BenchmarkResult,
+    fastest_compression: BenchmarkResult,
+    fastest_decompression: BenchmarkResult,
+}
 
-    let mut input = open_input(&cfg.input)?;
-    let mut buffered_input = Vec::new();
-    input.read_to_end(&mut buffered_input)?;
-    let input_len = buffered_input.len();
-    let mut input = Cursor::new(buffered_input);
+impl BenchmarkSummary {
+    /// Returns `None` if there are no results.
+    /// Copy is left out unless there are no other results, as it would be always the fastest.
+    fn new(results: &[BenchmarkResult]) -> Option<Self> {
+        let compressed: Vec<&BenchmarkResult> = results
+            .iter()
+            .filter(|r| r.algorithm != Algorithm::Copy)
+            .collect();
+        let results: Vec<&BenchmarkResult> = if compressed.is_empty() {
+            results.iter().collect()
+        } else {
+            compressed
+        };
+        let fastest = |speed: fn(&BenchmarkResult) -> f64| {
+            results
+                .iter()
+                .copied()
+                .min_by(|a, b| speed(b).total_cmp(&speed(a)))
+                .cloned()
+        };
+        Some(BenchmarkSummary {
+            // All results are for the same input, so this avoids comparing rounded ratios
+            best_ratio: results
+                .iter()
+                .copied()
+                .min_by_key(|r| r.compr_len)
+                .cloned()?,
+            fastest_compression: fastest(|r| r.compr_mbps)?,
+            fastest_decompression: fastest(|r| r.decompr_mbps)?,
+        })
+    }
+}
 
-    let mut output = Cursor::new(Vec::<u8>::with_capacity(input_len));
+impl Display for BenchmarkSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ratio = Ratio::new(self.best_ratio.compr_len, self.best_ratio.uncompr_len);
+        writeln!(f, "Summary:")?;
+        writeln!(
+            f,
+            "  best ratio:            {}: {}",
+            self.best_ratio.label(),
+            ratio
+        )?;
+        writeln!(
+            f,
+            "  fastest compression:   {}: {}",
+            self.fastest_compression.label(),
+            self.fastest_compression
+                .format_throughput(self.fastest_compression.compr_mbps)
+        )?;
+        write!(
+            f,
+            "  fastest decompression: {}: {}",
+            self.fastest_decompression.label(),
+            self.fastest_decompression
+                .format_throughput(self.fastest_decompression.decompr_mbps)
+        )
+    }
+}
 
-    let c_perf = compress(&mut input, &mut output, cfg.chunk_size, encoder.as_mut())?;
-    output.rewind()?;
-    let d_perf = decompress(output, Discard::default(), decoder.as_mut())?;
-    let result = BenchmarkResult::new(cfg, c_perf, d_perf);
-    println!("{}", result);
-    Ok(result)
+#[derive(Serialize)]
+struct ChunkEntropy {
+    chunk: usize,
+    offset: u64,
+    uncompr_len: usize,
+    compr_len: usize,
+    entropy: f64,
+    ratio: f64,
+    entropy_floor: f64,
+    gap: f64,
 }
 
-fn run_benchmark_many_cmd(cfg: BenchmarkManyCfg) -> anyhow::Result<()> {
-    let mut results = Vec::new();
-    
-    for algorithm in cfg.algorithms {
-        for level in algorithm.get_compression_levels() {
-            let run_cfg = CompressionCfg {
-                input: cfg.input.clone(),
-                algorithm,
-                compression: level,
-                chunk_size: cfg.chunk_size,
-            };
-            results.push(run_benchmark_cmd(run_cfg)?);
-        }
-    }
-    
-    if let Some(path) = cfg.report {
-        let mut writer = csv::Writer::from_path(path)?;
-        for result in results {
-            writer.serialize(&result)?;   
+impl ChunkEntropy {
+    /// Computes statistics of the next chunk, following the ones already computed
+    fn new(previous: &[ChunkEntropy], chunk: &[u8], compressed_len: usize) -> Self {
+        let offset = previous
+            .last()
+            .map(|c| c.offset + c.uncompr_len as u64)
+            .unwrap_or_default();
+        let ratio = Ratio::new(compressed_len as u64, chunk.len() as u64).as_fraction();
+        let entropy_floor = entropy::entropy_floor(chunk);
+        Self {
+            chunk: previous.len(),
+            offset,
+            uncompr_len: chunk.len(),
+            compr_len: compressed_len,
+            entropy: entropy::entropy(chunk),
+            ratio,
+            entropy_floor,
+            gap: ratio - entropy_floor,
         }
-        writer.flush()?;
     }
-    
-    Ok(())
 }
 
-fn open_input(config: &InputCfg) -> Result<File, Error> {
-    File::open(&config.path).map_err(|e| {
-        Error::new(
-            e.kind(),
-            format!("Could not open file {}: {}", config.path.display(), e),
+impl Display for ChunkEntropy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "chunk {:6} at {:12}: entropy {:4.2} bits/byte, ratio {:5.1}%, entropy floor {:5.1}%, gap {:+6.1}%",
+            self.chunk,
+            self.offset,
+            self.entropy,
+            self.ratio * 100.0,
+            self.entropy_floor * 100.0,
+            self.gap * 100.0
         )
-    })
+    }
 }
 
-fn open_output(input_path: &Path, algorithm: Algorithm, compress: bool) -> Result<File, Error> {
-    let extension_suffix = if compress { algorithm.extension() } else { "" };
-
-    let new_extension = match input_path.extension() {
-        None => extension_suffix.to_owned(),
-        Some(ext) => format!("{}.{}", ext.to_string_lossy(), extension_suffix),
-    };
-    let output_path = input_path.with_extension(new_extension);
-    let output = File::create(&output_path).map_err(|e| {
-        Error::new(
-            e.kind(),
-            format!("Could not create file {}: {}", output_path.display(), e),
-        )
-    })?;
-    Ok(output)
+/// Periodically prints the progress of a long running operation as plain text lines
+struct StatusReporter {
+    interval: Duration,
+    /// Unknown for the standard input without a size hint
+    total_len: Option<u64>,
+    start: Instant,
+    last_report: Instant,
+    input_len: u64,
+    output_len: u64,
+    units: Units,
 }
 
-fn encoder(
-    algorithm: Algorithm,
-    compression: i32,
-    dict: Option<&Vec<u8>>,
-) -> anyhow::Result<Box<dyn codec::Encoder>> {
-    Ok(match (algorithm, dict) {
-        (Algorithm::Copy, _) => Box::new(codec::copy::Copy),
-        (Algorithm::Lz4, _) => Box::new(codec::lz4::Lz4Compressor::new(compression)),
-        (Algorithm::Zstd, None) => Box::new(zstd::bulk::Compressor::new(compression)?),
-        (Algorithm::Zstd, Some(dict)) => {
-            Box::new(zstd::bulk::Compressor::with_dictionary(compression, dict)?)
-        }
-        (Algorithm::Brotli, None) => Box::new(brotli::BrotliCompressor(compression)),
-        (Algorithm::Brotli, Some(dict)) => {
-            Box::new(brotli::BrotliDictCompressor::new(compression as u32, dict))
-        }
-        (Algorithm::Snappy, _) => Box::new(snap::raw::Encoder::new()),
-        (Algorithm::Lzma, _) => Box::new(lzma::LzmaCompressor(compression as u32)),
-        (Algorithm::Lzav, _) => Box::new(codec::lzav::LzavCompressor::new(compression as u32)),
-    })
-}
+impl StatusReporter {
+    fn new(interval: Duration, total_len: Option<u64>, units: Units) -> Self {
+        let now = Instant::now();
+        StatusReporter {
+            interval,
+            total_len,
+            start: now,
+            last_report: now,
+            input_len: 0,
+            output_len: 0,
+            units,
+        }
+    }
 
-fn decoder(
+    /// Records a processed chunk and prints the status if the interval has passed
+    fn update(&mut self, input_len: usize, output_len: usize) {
+        self.input_len += input_len as u64;
+        self.output_len += output_len as u64;
+        let now = Instant::now();
+        if now - self.last_report >= self.interval {
+            self.last_report = now;
+            eprintln!("{}", self.format_status(now - self.start));
+        }
+    }
+
+    fn format_status(&self, elapsed: Duration) -> String {
+        let throughput = self.input_len as f64 / elapsed.as_secs_f64();
+        let Some(total_len) = self.total_len else {
+            return format!(
+                "status: {}, ratio {:.1}%, {}",
+                self.units.format_size(self.input_len as f64),
+                Ratio::new(self.output_len, self.input_len.max(1)).as_percent(),
+                self.units.format_throughput(throughput)
+            );
+        };
+        let remaining = total_len.saturating_sub(self.input_len);
+        let eta = Duration::from_secs_f64(remaining as f64 / throughput.max(1.0)).as_secs();
+        format!(
+            "status: {} of {} ({:.1}%), ratio {:.1}%, {}, ETA {}:{:02}:{:02}",
+            self.units.format_size(self.input_len as f64),
+            self.units.format_size(total_len as f64),
+            self.input_len as f64 / total_len.max(1) as f64 * 100.0,
+            Ratio::new(self.output_len, self.input_len.max(1)).as_percent(),
+            self.units.format_throughput(throughput),
+            eta / 3600,
+            eta / 60 % 60,
+            eta % 60
+        )
+    }
+}
+
+fn main() {
+    let cmd = Config::parse();
+    install_interrupt_handler();
+    if let Err(e) = run(cmd) {
+        eprintln!("error: {:#}", e);
+        remove_partial_outputs_and_exit(1);
+    }
+    outputs_completed();
+}
+
+/// Output files created or truncated by [`open_output`] and directories created
+/// by unpacking an archive, not completely written yet
+static PARTIAL_OUTPUTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Makes Ctrl-C remove the partially written output files before exiting,
+/// so no truncated file is left looking like a complete one
+fn install_interrupt_handler() {
+    let result = ctrlc::set_handler(|| remove_partial_outputs_and_exit(130));
+    if let Err(e) = result {
+        eprintln!("warning: Cannot remove partial output on Ctrl-C: {}", e);
+    }
+}
+
+/// Registers an output to remove if the command fails or is interrupted
+fn output_started(path: PathBuf) {
+    PARTIAL_OUTPUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(path);
+}
+
+/// Removes the outputs registered so far and exits with the given code
+fn remove_partial_outputs_and_exit(code: i32) -> ! {
+    // The lock is kept until exit, so no further output can be registered
+    let outputs = PARTIAL_OUTPUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for path in outputs.iter() {
+        let _ = match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+            _ => std::fs::remove_file(path),
+        };
+    }
+    exit(code);
+}
+
+/// Keeps the output files registered so far when the command fails or is interrupted
+fn outputs_completed() {
+    PARTIAL_OUTPUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+fn run(cmd: Config) -> anyhow::Result<()> {
+    match cmd.command {
+        Command::Decompress(cfg) => run_decompress_cmd(cfg),
+        Command::Compress(cfg) => run_compress_cmd(cfg.with_recommended_chunk_size()),
+        Command::Benchmark(cfg) => run_benchmark_cmd(cfg.with_recommended_chunk_size()),
+        Command::BenchmarkDecompress(cfg) => run_benchmark_decompress_cmd(cfg),
+        Command::BenchmarkMany(cfg) => run_benchmark_many_cmd(cfg),
+        Command::Verify(cfg) => run_verify_cmd(cfg.with_recommended_chunk_size()),
+        Command::TrainDict(cfg) => run_train_dict_cmd(cfg),
+        Command::MapOffsets(cfg) => run_map_offsets_cmd(cfg),
+        Command::ListAlgorithms(cfg) => run_list_algorithms_cmd(cfg),
+    }
+}
+
+/// Returns the algorithm given with -a, recorded in the header of the stream
+/// or implied by the extension of the input file, in this order of precedence.
+/// Warns if -a differs from the others, or fails with --strict.
+fn decompression_algorithm(
+    cfg: &StreamCfg,
+    header: &ContainerOptions,
+) -> anyhow::Result<Algorithm> {
+    let Some(algorithm) = cfg
+        .algorithm
+        .or(header.algorithm)
+        .or_else(|| Algorithm::from_file_name(&cfg.input.path))
+    else {
+        bail!("Cannot determine compression algorithm from the header or the extension. Please use -a/--algorithm option.");
+    };
+    let detected = cfg
+        .algorithm
+        .and_then(|a| a.conflicting_extension(&cfg.input.path));
+    let mismatch = match (header.algorithm, detected) {
+        (Some(recorded), _) if recorded != algorithm => Some(format!(
+            "Algorithm {} doesn't match the header of {}, which records {}",
+            algorithm.name(),
+            cfg.input.path.display(),
+            recorded.name()
+        )),
+        (None, Some(detected)) => Some(format!(
+            "Algorithm {} doesn't match the extension of {}, which suggests {}",
+            algorithm.name(),
+            cfg.input.path.display(),
+            detected.name()
+        )),
+        _ => None,
+    };
+    if let Some(message) = mismatch {
+        if cfg.strict {
+            bail!(message);
+        }
+        eprintln!("warning: {}", message);
+    }
+    Ok(algorithm)
+}
+
+/// Reads the header of the input stream, if it has one.
+/// Unreadable headers are reported when decompressing the stream.
+fn stream_header(cfg: &StreamCfg) -> anyhow::Result<ContainerOptions> {
+    if cfg.raw || cfg.single_block || cfg.no_header {
+        return Ok(ContainerOptions::default());
+    }
+    Ok(read_header(open_input(&cfg.input)?).unwrap_or_default())
+}
+
+impl StreamCfg {
+    /// Returns the codec options given on the command line,
+    /// enabling the brotli large window also if the header records it
+    fn codec_options(&self, header: &ContainerOptions) -> CodecOptions {
+        CodecOptions {
+            zstd_long: self.long,
+            zstd_dict_id: self.zstd_dict_id,
+            brotli_window: (self.brotli_large_window || header.brotli_large_window)
+                .then_some(codec::brotli::MAX_WINDOW),
+            ..CodecOptions::default()
+        }
+    }
+}
+
+fn run_decompress_cmd(cfg: DecompressionCfg) -> anyhow::Result<()> {
+    let header = stream_header(&cfg.stream)?;
+    let algorithm = decompression_algorithm(&cfg.stream, &header)?;
+    let dict = dictionary(&cfg.stream.input.dictionary)?;
+    let options = cfg.stream.codec_options(&header);
+    if cfg.stream.raw {
+        return run_decompress_raw_cmd(&cfg, algorithm, dict.as_deref(), &options);
+    }
+    let mut decoder = decoder(algorithm, dict.as_deref(), &options)?;
+    if cfg.stream.single_block {
+        return run_decompress_single_block_cmd(&cfg, algorithm, decoder.as_mut());
+    }
+    if header.tar {
+        return run_decompress_tar_cmd(&cfg, algorithm, decoder.as_mut());
+    }
+    let input = open_input(&cfg.stream.input)?;
+    let progress = progress_bar(&input, cfg.quiet)?;
+    let input = progress.wrap_read(input);
+    // Streams compressed with --strip-extension record the extension to restore
+    let original_extension = header
+        .original_extension
+        .filter(|_| cfg.output.output_path.is_none());
+    if cfg.dry_run {
+        let result = decompress_with_options(
+            input,
+            Discard::default(),
+            decoder.as_mut(),
+            &decompression_options(&cfg.stream),
+        )?;
+        progress.finish_and_clear();
+        eprintln!(
+            "{}: OK, {} bytes uncompressed",
+            cfg.stream.input.path.display(),
+            result.output_len
+        );
+        print_dry_run_output(&cfg, algorithm, original_extension.as_deref());
+        return Ok(());
+    }
+    let output = open_output(
+        &cfg.stream.input.path,
+        &cfg.output,
+        algorithm,
+        false,
+        false,
+        cfg.force,
+        original_extension.as_deref(),
+    )?;
+    let result = decompress_with_options(
+        input,
+        &output,
+        decoder.as_mut(),
+        &decompression_options(&cfg.stream),
+    )?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.stream.input.path, &[&output])?;
+    }
+    print_measurement(&result, result.output_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+fn decompression_options(cfg: &StreamCfg) -> DecompressionOptions {
+    DecompressionOptions {
+        check_version: !cfg.ignore_version,
+        read_buffer_size: cfg.read_buffer,
+        max_frame_len: cfg.max_frame.unwrap_or(DEFAULT_MAX_FRAME_LEN),
+        no_header: cfg.no_header,
+    }
+}
+
+/// Options for decompressing a stream compressed by this process, which is trusted
+/// to have frames no longer than the chunk size it was given
+fn own_output_decompression_options() -> DecompressionOptions {
+    DecompressionOptions {
+        max_frame_len: MAX_FRAME_LEN,
+        ..DecompressionOptions::default()
+    }
+}
+
+/// Unpacks a stream compressed from a directory into a directory named like the stream
+/// without the `.tar` and algorithm extensions, or into the directory given with -o
+fn run_decompress_tar_cmd(
+    cfg: &DecompressionCfg,
+    algorithm: Algorithm,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<()> {
+    if cfg.remove_source {
+        bail!("--remove-source is not supported for directories");
+    }
+    let mut dir = output_path(&cfg.stream.input.path, &cfg.output, algorithm, false, None)?;
+    if cfg.output.output_path.is_none() && dir.extension() == Some("tar".as_ref()) {
+        dir.set_extension("");
+    }
+    if dir.exists() && !cfg.force {
+        bail!(
+            "Output directory {} already exists, use --force to unpack into it",
+            dir.display()
+        );
+    }
+    let input = open_input(&cfg.stream.input)?;
+    let progress = progress_bar(&input, cfg.quiet)?;
+    let input = progress.wrap_read(input);
+    if cfg.dry_run {
+        let result = decompress_with_options(
+            input,
+            Discard::default(),
+            decoder,
+            &decompression_options(&cfg.stream),
+        )?;
+        progress.finish_and_clear();
+        eprintln!(
+            "{}: OK, {} bytes uncompressed",
+            cfg.stream.input.path.display(),
+            result.output_len
+        );
+        println!("{}\t{}", algorithm.name(), dir.display());
+        return Ok(());
+    }
+    // Files unpacked into an existing directory can't be told apart from the ones it had,
+    // so only a new directory is removed if unpacking fails
+    if !dir.exists() {
+        output_started(dir.clone());
+    }
+    let mut output = TarWriter::new(&dir)?;
+    let result = decompress_with_options(
+        input,
+        &mut output,
+        decoder,
+        &decompression_options(&cfg.stream),
+    )?;
+    output
+        .finish()
+        .with_context(|| format!("Failed to unpack into {}", dir.display()))?;
+    progress.finish_and_clear();
+    print_measurement(&result, result.output_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+/// Prints the algorithm and the path of the file decompress would write, if it can be derived
+fn print_dry_run_output(
+    cfg: &DecompressionCfg,
+    algorithm: Algorithm,
+    original_extension: Option<&str>,
+) {
+    match output_path(
+        &cfg.stream.input.path,
+        &cfg.output,
+        algorithm,
+        false,
+        original_extension,
+    ) {
+        Ok(path) => println!("{}\t{}", algorithm.name(), path.display()),
+        Err(e) => eprintln!("warning: {}", e),
+    }
+}
+
+fn run_decompress_raw_cmd(
+    cfg: &DecompressionCfg,
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<()> {
+    let input = open_input(&cfg.stream.input)?;
+    let progress = progress_bar(&input, cfg.quiet)?;
+    let input = progress.wrap_read(input);
+    if cfg.dry_run {
+        let result = decompress_raw(input, Discard::default(), algorithm, dict, options)?;
+        progress.finish_and_clear();
+        eprintln!(
+            "{}: OK, {} bytes uncompressed",
+            cfg.stream.input.path.display(),
+            result.output_len
+        );
+        print_dry_run_output(cfg, algorithm, None);
+        return Ok(());
+    }
+    let output = open_output(
+        &cfg.stream.input.path,
+        &cfg.output,
+        algorithm,
+        false,
+        false,
+        cfg.force,
+        None,
+    )?;
+    let result = decompress_raw(input, &output, algorithm, dict, options)?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.stream.input.path, &[&output])?;
+    }
+    print_measurement(&result, result.output_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+fn run_decompress_single_block_cmd(
+    cfg: &DecompressionCfg,
+    algorithm: Algorithm,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<()> {
+    let input = open_input(&cfg.stream.input)?;
+    let progress = progress_bar(&input, cfg.quiet)?;
+    let input = progress.wrap_read(input);
+    if cfg.dry_run {
+        let result = decompress_single_block_with_options(
+            input,
+            Discard::default(),
+            decoder,
+            &decompression_options(&cfg.stream),
+        )?;
+        progress.finish_and_clear();
+        eprintln!(
+            "{}: OK, {} bytes uncompressed",
+            cfg.stream.input.path.display(),
+            result.output_len
+        );
+        print_dry_run_output(cfg, algorithm, None);
+        return Ok(());
+    }
+    let output = open_output(
+        &cfg.stream.input.path,
+        &cfg.output,
+        algorithm,
+        false,
+        false,
+        cfg.force,
+        None,
+    )?;
+    let result = decompress_single_block_with_options(
+        input,
+        &output,
+        decoder,
+        &decompression_options(&cfg.stream),
+    )?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.stream.input.path, &[&output])?;
+    }
+    print_measurement(&result, result.output_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+fn run_compress_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    if cfg.reads_stdin() {
+        check_stdin_cfg(&cfg)?;
+    } else if cfg.input_size.is_some() {
+        eprintln!("warning: --input-size applies only to the standard input, ignoring it");
+    }
+    if cfg.dry_run {
+        return run_compress_dry_run_cmd(cfg);
+    }
+    if !cfg.best_of.is_empty() {
+        return run_compress_best_of_cmd(cfg);
+    }
+    if !cfg.also.is_empty() {
+        return run_compress_also_cmd(cfg);
+    }
+    let dict = dictionary(&cfg.input.dictionary)?;
+    let options = cfg.codec_options();
+    if cfg.raw {
+        return run_compress_raw_cmd(&cfg, dict.as_deref(), &options);
+    }
+    let mut encoder = encoder(cfg.algorithm, cfg.level(), dict.as_deref(), &options)?;
+    if cfg.single_block {
+        return run_compress_single_block_cmd(&cfg, encoder.as_mut());
+    }
+    // Directories are compressed as a tar archive packed on the fly
+    let (input, input_len): (Box<dyn ReadSeek>, Option<u64>) = if cfg.reads_stdin() {
+        let input = StreamReader::new(io::stdin().lock());
+        (Box::new(input), cfg.input_size.map(|size| size as u64))
+    } else if cfg.input.path.is_dir() {
+        if cfg.append || cfg.remove_source {
+            bail!("--append and --remove-source are not supported for directories");
+        }
+        if cfg.offset.is_some() || cfg.length.is_some() {
+            bail!("--offset and --length are not supported for directories");
+        }
+        let input = TarReader::new(&cfg.input.path)?;
+        (Box::new(input), Some(directory_size(&cfg.input.path)?))
+    } else {
+        let input = open_input_range(&cfg)?;
+        let input_len = input.len();
+        (Box::new(input), Some(input_len))
+    };
+    let mut output = open_output(
+        &cfg.output_base_path(),
+        &cfg.output,
+        cfg.algorithm,
+        true,
+        cfg.append,
+        cfg.force,
+        None,
+    )?;
+    if cfg.append {
+        let mut decoder = decoder(cfg.algorithm, dict.as_deref(), &options)?;
+        check_appendable(&mut output, &cfg.container_options()?, decoder.as_mut())?;
+        output.seek(SeekFrom::End(0))?;
+    }
+    let progress = match input_len {
+        Some(len) => progress_bar_with_len(len, cfg.quiet),
+        None => progress_bar_without_len(cfg.quiet),
+    };
+    let mut status = cfg
+        .status_interval
+        .map(|interval| StatusReporter::new(interval, input_len, cfg.units));
+    let collect_entropy = cfg.show_frame_entropy || cfg.frame_entropy_report.is_some();
+    let mut chunk_entropy = Vec::new();
+    let result = compress_with_cfg(
+        &cfg,
+        progress.wrap_read(input),
+        &output,
+        encoder.as_mut(),
+        |chunk, compressed_len| {
+            if collect_entropy {
+                chunk_entropy.push(ChunkEntropy::new(&chunk_entropy, chunk, compressed_len));
+            }
+            if let Some(status) = &mut status {
+                status.update(chunk.len(), compressed_len);
+            }
+        },
+    )?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.input.path, &[&output])?;
+    }
+
+    if cfg.show_frame_entropy {
+        for c in &chunk_entropy {
+            println!("{}", c);
+        }
+    }
+    if let Some(path) = &cfg.frame_entropy_report {
+        let mut writer = csv::Writer::from_path(path)?;
+        for c in &chunk_entropy {
+            writer.serialize(c)?;
+        }
+        writer.flush()?;
+    }
+    print_measurement(&result, result.input_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+/// Makes sure we don't append to a corrupted file or to a file written with other settings,
+/// as the new frames would be unreadable. The header must record the same algorithm, transform
+/// and checksum. Streams written before headers recorded the algorithm are checked by decompressing
+/// the first frame not stored uncompressed, which only the algorithm of the file can restore.
+fn check_appendable(
+    output: &mut File,
+    options: &ContainerOptions,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<()> {
+    let frames = scan_frames(&mut *output).context("Cannot append to the existing output file")?;
+    if frames.is_empty() {
+        return Ok(());
+    }
+    output.seek(SeekFrom::Start(0))?;
+    let existing =
+        read_header(&mut *output).context("Cannot append to the existing output file")?;
+    let algorithm = options
+        .algorithm
+        .expect("compression records the algorithm");
+    match existing.algorithm {
+        Some(existing) if existing != algorithm => bail!(
+            "Cannot append to the existing output file, it was compressed with {}, not with {}",
+            existing.name(),
+            algorithm.name()
+        ),
+        Some(_) => {}
+        None => check_first_frame(output, &frames, algorithm, decoder)?,
+    }
+    if existing.transform != options.transform {
+        bail!(
+            "Cannot append to the existing output file, it was compressed with transform {}, not {}",
+            existing.transform,
+            options.transform
+        );
+    }
+    if existing.checksum != options.checksum {
+        let name = |checksum: Checksum| checksum.to_possible_value().unwrap().get_name().to_owned();
+        bail!(
+            "Cannot append to the existing output file, it has checksum {}, not {}",
+            name(existing.checksum),
+            name(options.checksum)
+        );
+    }
+    Ok(())
+}
+
+/// Fails if the first frame not stored uncompressed can't be restored with the decoder
+fn check_first_frame(
+    output: &mut File,
+    frames: &[FrameInfo],
+    algorithm: Algorithm,
+    decoder: &mut dyn codec::Decoder,
+) -> anyhow::Result<()> {
+    let Some(frame) = frames.iter().find(|frame| !frame.stored) else {
+        return Ok(());
+    };
+    output.seek(SeekFrom::Start(frame.compressed_offset + 8))?;
+    let mut src = vec![0; frame.compressed_len as usize];
+    output.read_exact(&mut src)?;
+    let mut dest = vec![0; frame.uncompressed_len as usize];
+    match decoder.decompress(&src, &mut dest) {
+        Ok(len) if len == dest.len() => Ok(()),
+        _ => bail!(
+            "Cannot append to the existing output file, it was not compressed with {}",
+            algorithm.name()
+        ),
+    }
+}
+
+/// Fails if the config needs a file input, which the standard input can't replace
+fn check_stdin_cfg(cfg: &CompressionCfg) -> anyhow::Result<()> {
+    if cfg.output.output_path.is_none() {
+        bail!("--output is required when compressing the standard input");
+    }
+    if cfg.dry_run || cfg.raw || cfg.single_block || !cfg.best_of.is_empty() || !cfg.also.is_empty()
+    {
+        bail!("--dry-run, --raw, --single-block, --best-of and --also are not supported for the standard input");
+    }
+    if cfg.remove_source || cfg.offset.is_some() || cfg.length.is_some() {
+        bail!("--remove-source, --offset and --length are not supported for the standard input");
+    }
+    Ok(())
+}
+
+/// Prints the algorithm and the path of each file compress would write, without touching them.
+/// Fails like compress if a file exists and may not be overwritten.
+fn run_compress_dry_run_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    for algorithm in cfg.algorithms() {
+        let path = output_path(&cfg.output_base_path(), &cfg.output, algorithm, true, None)?;
+        if path.exists() && !cfg.force && !cfg.append {
+            bail!(
+                "Output file {} already exists, use --force to overwrite",
+                path.display()
+            );
+        }
+        println!("{}\t{}", algorithm.name(), path.display());
+    }
+    Ok(())
+}
+
+fn run_compress_raw_cmd(
+    cfg: &CompressionCfg,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<()> {
+    check_compression(cfg.algorithm, cfg.level(), options)?;
+    let input = open_input_range(cfg)?;
+    let output = open_output(
+        &cfg.input.path,
+        &cfg.output,
+        cfg.algorithm,
+        true,
+        false,
+        cfg.force,
+        None,
+    )?;
+    let progress = progress_bar_with_len(input.len(), cfg.quiet);
+    let result = compress_raw(
+        progress.wrap_read(input),
+        &output,
+        cfg.algorithm,
+        cfg.level(),
+        dict,
+        options,
+    )?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.input.path, &[&output])?;
+    }
+    print_measurement(&result, result.input_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+fn run_compress_single_block_cmd(
+    cfg: &CompressionCfg,
+    encoder: &mut dyn codec::Encoder,
+) -> anyhow::Result<()> {
+    let input = open_input_range(cfg)?;
+    check_single_block_len(input.len(), encoder)?;
+    let output = open_output(
+        &cfg.input.path,
+        &cfg.output,
+        cfg.algorithm,
+        true,
+        false,
+        cfg.force,
+        None,
+    )?;
+    let progress = progress_bar_with_len(input.len(), cfg.quiet);
+    let result = compress_single_block(progress.wrap_read(input), &output, encoder)?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        remove_source(&cfg.input.path, &[&output])?;
+    }
+    print_measurement(&result, result.input_throughtput(), cfg.units, cfg.json)?;
+    Ok(())
+}
+
+/// Smallest chunk size chosen from the `--input-size` hint, so a wrong hint doesn't split
+/// the input into many tiny frames
+const MIN_HINTED_CHUNK_SIZE: usize = 4096;
+
+/// Compresses the input with the chunk sizes and the container options given in the config
+fn compress_with_cfg<R: Read + Seek, W: Write + Seek>(
+    cfg: &CompressionCfg,
+    input: R,
+    output: W,
+    encoder: &mut dyn codec::Encoder,
+    on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<Measurement> {
+    let mut chunk_size = cfg.chunk_size();
+    if let (true, Some(input_size)) = (cfg.reads_stdin(), cfg.input_size) {
+        // Longer inputs are still compressed, just in more chunks
+        chunk_size = chunk_size.min(input_size.max(MIN_HINTED_CHUNK_SIZE));
+    }
+    let options = cfg.container_options()?;
+    if cfg.adaptive_chunks {
+        compress_adaptive(input, output, chunk_size, encoder, options, on_chunk)
+    } else {
+        compress_with_options(input, output, chunk_size, encoder, options, on_chunk)
+    }
+}
+
+fn run_compress_also_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    let algorithms = cfg.algorithms();
+    let dict = dictionary(&cfg.input.dictionary)?;
+    let options = cfg.codec_options();
+    let mut outputs = Vec::with_capacity(algorithms.len());
+    for &algorithm in &algorithms {
+        let level = cfg.level_for(algorithm);
+        let encoder = encoder(algorithm, level, dict.as_deref(), &options)?;
+        let output = open_output(
+            &cfg.output_base_path(),
+            &cfg.output,
+            algorithm,
+            true,
+            false,
+            cfg.force,
+            None,
+        )?;
+        outputs.push((output, algorithm, encoder));
+    }
+    let input = open_input_range(&cfg)?;
+    let input_len = input.len();
+    let progress = progress_bar_with_len(input_len, cfg.quiet);
+    let output_lens = compress_to_many(
+        progress.wrap_read(input),
+        &mut outputs,
+        cfg.chunk_size(),
+        cfg.container_options()?,
+    )?;
+    progress.finish_and_clear();
+    if cfg.remove_source {
+        let files: Vec<&File> = outputs.iter().map(|(output, _, _)| output).collect();
+        remove_source(&cfg.input.path, &files)?;
+    }
+
+    for (algorithm, output_len) in algorithms.iter().zip(output_lens) {
+        eprintln!(
+            "{:10} {} => {} ({})",
+            algorithm.name(),
+            input_len,
+            output_len,
+            Ratio::new(output_len, input_len.max(1))
+        );
+    }
+    Ok(())
+}
+
+fn run_compress_best_of_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    let dict = dictionary(&cfg.input.dictionary)?;
+    let mut input = open_input_range(&cfg)?;
+    let mut buffered_input = Vec::new();
+    input.read_to_end(&mut buffered_input)?;
+
+    let best = compress_best_of(
+        &buffered_input,
+        &cfg.best_of,
+        cfg.chunk_size,
+        dict.as_deref(),
+    )?;
+    for (algorithm, compressed_len) in &best.candidates {
+        eprintln!(
+            "{:10} {} => {} ({})",
+            algorithm.name(),
+            buffered_input.len(),
+            compressed_len,
+            Ratio::new(*compressed_len as u64, buffered_input.len().max(1) as u64)
+        );
+    }
+    let mut output = open_output(
+        &cfg.input.path,
+        &cfg.output,
+        best.algorithm,
+        true,
+        false,
+        cfg.force,
+        None,
+    )?;
+    output.write_all(&best.output)?;
+    if cfg.remove_source {
+        remove_source(&cfg.input.path, &[&output])?;
+    }
+    eprintln!("Kept the output of {}", best.algorithm.name());
+    if let Some(path) = &cfg.output.output_path {
+        if best.algorithm.conflicting_extension(path).is_some() {
+            eprintln!(
+                "warning: The extension of {} doesn't match {}",
+                path.display(),
+                best.algorithm.name()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_benchmark_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    let dict = dictionary(&cfg.input.dictionary)?;
+    let buffered_input = if cfg.synthetic {
+        let (kind, len) = match parse_synthetic(&cfg.input.path.to_string_lossy()) {
+            Ok(synthetic) => synthetic,
+            Err(e) => bail!("Invalid synthetic data {}: {}", cfg.input.path.display(), e),
+        };
+        kind.generate(len, cfg.seed)
+    } else {
+        let mut input = open_input_range(&cfg)?;
+        let mut buffered_input = Vec::new();
+        input.read_to_end(&mut buffered_input)?;
+        buffered_input
+    };
+    if let Some(other) = cfg.compare_with {
+        return run_compare_chunks_cmd(&cfg, other, &buffered_input, dict.as_deref());
+    }
+    let per_chunk_stats = cfg.per_chunk_stats;
+    let json = cfg.json;
+    let mut chunks = Vec::new();
+    if let Some(core) = cfg.cpu_affinity {
+        pin_to_core(core)?;
+    }
+    let result = benchmark(
+        cfg,
+        &buffered_input,
+        dict.as_deref(),
+        None,
+        |chunk, compressed_len| {
+            if per_chunk_stats {
+                chunks.push((chunk.len(), compressed_len));
+            }
+        },
+    )?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result);
+    }
+    if per_chunk_stats {
+        print_chunk_stats(&chunks);
+    }
+    Ok(())
+}
+
+/// Compresses each chunk with the configured algorithm and the other one
+/// and prints both compressed sizes
+fn run_compare_chunks_cmd(
+    cfg: &CompressionCfg,
+    other: Algorithm,
+    input: &[u8],
+    dict: Option<&[u8]>,
+) -> anyhow::Result<()> {
+    let options = cfg.codec_options();
+    let algorithms = [cfg.algorithm, other];
+    let mut encoders = Vec::with_capacity(algorithms.len());
+    for algorithm in algorithms {
+        let level = cfg.level_for(algorithm);
+        encoders.push(encoder(algorithm, level, dict, &options)?);
+    }
+    let chunk_size = cfg.chunk_size();
+    let mut tmp_buf = Vec::new();
+    // Number of chunks compressed better by each algorithm and the number of ties
+    let mut wins = [0; 2];
+    let mut ties = 0;
+    println!(
+        "{:>8} {:>12} {:>10} {:>10} {:>10}  smaller",
+        "chunk",
+        "offset",
+        "length",
+        cfg.algorithm.name(),
+        other.name()
+    );
+    for (index, chunk) in input.chunks(chunk_size).enumerate() {
+        let mut lens = [0; 2];
+        for (len, encoder) in lens.iter_mut().zip(encoders.iter_mut()) {
+            tmp_buf.resize(encoder.compressed_len_bound(chunk.len()), 0);
+            *len = encoder.compress(chunk, &mut tmp_buf)?;
+        }
+        let smaller = match lens[0].cmp(&lens[1]) {
+            Ordering::Less => {
+                wins[0] += 1;
+                cfg.algorithm.name()
+            }
+            Ordering::Greater => {
+                wins[1] += 1;
+                other.name()
+            }
+            Ordering::Equal => {
+                ties += 1;
+                "-".to_owned()
+            }
+        };
+        println!(
+            "{:8} {:12} {:10} {:10} {:10}  {}",
+            index,
+            index * chunk_size,
+            chunk.len(),
+            lens[0],
+            lens[1],
+            smaller
+        );
+    }
+    println!(
+        "{} smaller in {} chunks, {} smaller in {} chunks, equal in {} chunks",
+        cfg.algorithm.name(),
+        wins[0],
+        other.name(),
+        wins[1],
+        ties
+    );
+    Ok(())
+}
+
+/// Decompresses the file read into memory first, so only the decoding is measured
+fn run_benchmark_decompress_cmd(cfg: BenchmarkDecompressionCfg) -> anyhow::Result<()> {
+    let header = stream_header(&cfg.stream)?;
+    let algorithm = decompression_algorithm(&cfg.stream, &header)?;
+    let dict = dictionary(&cfg.stream.input.dictionary)?;
+    let options = cfg.stream.codec_options(&header);
+    let mut input = open_input(&cfg.stream.input)?;
+    let mut compressed = Vec::new();
+    input.read_to_end(&mut compressed)?;
+    let input = Cursor::new(compressed.as_slice());
+
+    let result = if cfg.stream.raw {
+        decompress_raw(
+            input,
+            Discard::default(),
+            algorithm,
+            dict.as_deref(),
+            &options,
+        )?
+    } else {
+        let mut decoder = decoder(algorithm, dict.as_deref(), &options)?;
+        if cfg.stream.single_block {
+            decompress_single_block_with_options(
+                input,
+                Discard::default(),
+                decoder.as_mut(),
+                &decompression_options(&cfg.stream),
+            )?
+        } else {
+            decompress_with_options(
+                input,
+                Discard::default(),
+                decoder.as_mut(),
+                &decompression_options(&cfg.stream),
+            )?
+        }
+    };
+    if !cfg.json {
+        eprint!("{}: ", algorithm.name());
+    }
+    print_measurement(&result, result.output_throughtput(), cfg.units, cfg.json)
+}
+
+/// Pins the current thread to the given CPU core.
+/// Fails if the core is not available to the process.
+fn pin_to_core(core: usize) -> anyhow::Result<()> {
+    let Some(core_ids) = core_affinity::get_core_ids() else {
+        eprintln!(
+            "warning: CPU affinity is not supported on this platform, ignoring --cpu-affinity"
+        );
+        return Ok(());
+    };
+    let Some(&core_id) = core_ids.iter().find(|id| id.id == core) else {
+        let available: Vec<String> = core_ids.iter().map(|id| id.id.to_string()).collect();
+        bail!(
+            "CPU core {} is not available, choose one of: {}",
+            core,
+            available.join(", ")
+        );
+    };
+    if !core_affinity::set_for_current(core_id) {
+        eprintln!("warning: Failed to pin the benchmark to CPU core {}", core);
+    }
+    Ok(())
+}
+
+/// Prints the distribution of compression ratios of chunks given as (uncompressed, compressed) lengths
+fn print_chunk_stats(chunks: &[(usize, usize)]) {
+    let mut ratios: Vec<(usize, Ratio)> = chunks
+        .iter()
+        .map(|&(uncompr_len, compr_len)| Ratio::new(compr_len as u64, uncompr_len as u64))
+        .enumerate()
+        .collect();
+    if ratios.is_empty() {
+        return;
+    }
+    ratios.sort_by(|a, b| a.1.as_fraction().total_cmp(&b.1.as_fraction()));
+    println!("Compression ratio of {} chunks:", ratios.len());
+    println!("{:10} {:>8} {:>8}", "", "chunk", "ratio");
+    let rows = [
+        ("min", ratios[0]),
+        ("median", ratios[ratios.len() / 2]),
+        ("max", ratios[ratios.len() - 1]),
+    ];
+    for (label, (index, ratio)) in rows {
+        println!("{:10} {:8} {:7.1}%", label, index, ratio.as_percent());
+    }
+}
+
+/// Compresses and decompresses the input held in memory, measuring both.
+/// Saves the compressed data to `compressed_output`, if given.
+fn benchmark(
+    cfg: CompressionCfg,
+    input: &[u8],
+    dict: Option<&[u8]>,
+    compressed_output: Option<&Path>,
+    on_chunk: impl FnMut(&[u8], usize),
+) -> anyhow::Result<BenchmarkResult> {
+    memory::reset_peak_rss();
+    let options = cfg.codec_options();
+    let mut encoder = encoder(cfg.algorithm, cfg.level(), dict, &options)?;
+    let dict = dict.filter(|_| encoder.capabilities().dictionary);
+    let mut decoder = decoder(cfg.algorithm, dict, &options)?;
+
+    let mut input = Cursor::new(input);
+
+    let mut output = Cursor::new(Vec::<u8>::with_capacity(input.get_ref().len()));
+
+    let mut timed_encoder = TimedEncoder::new(encoder.as_mut(), cfg.chunk_latency);
+    let c_perf = compress_with_cfg(&cfg, &mut input, &mut output, &mut timed_encoder, on_chunk)?;
+    let chunk_times = timed_encoder.times;
+    output.rewind()?;
+    let mut sink = Discard::default();
+    let start_time = Instant::now();
+    let d_perf = decompress_with_options(
+        &mut output,
+        &mut sink,
+        decoder.as_mut(),
+        &own_output_decompression_options(),
+    )?;
+    let ttfb = sink
+        .first_write_time()
+        .filter(|_| cfg.ttfb)
+        .map(|t| t - start_time);
+    if cfg.verify {
+        output.rewind()?;
+        let mut comparison = Compare::new(input.get_ref());
+        decompress_with_options(
+            &mut output,
+            &mut comparison,
+            decoder.as_mut(),
+            &own_output_decompression_options(),
+        )?;
+        if let Some(offset) = comparison.first_mismatch() {
+            bail!(
+                "{} level {}: decompressed data differs from the original at offset {}",
+                cfg.algorithm.name(),
+                cfg.level(),
+                offset
+            );
+        }
+    }
+    if let Some(path) = compressed_output {
+        std::fs::write(path, output.get_ref())
+            .with_context(|| format!("Could not write {}", path.display()))?;
+    }
+    Ok(BenchmarkResult::new(
+        cfg,
+        c_perf,
+        d_perf,
+        memory::peak_rss(),
+        ttfb,
+        chunk_times,
+    ))
+}
+
+/// Encoder measuring the time of each chunk compressed by the wrapped encoder, if enabled.
+/// Unlike the time between chunks, it doesn't include reading the input and writing the output.
+struct TimedEncoder<'a> {
+    encoder: &'a mut dyn codec::Encoder,
+    enabled: bool,
+    times: Vec<Duration>,
+}
+
+impl<'a> TimedEncoder<'a> {
+    fn new(encoder: &'a mut dyn codec::Encoder, enabled: bool) -> Self {
+        TimedEncoder {
+            encoder,
+            enabled,
+            times: Vec::new(),
+        }
+    }
+}
+
+impl codec::Encoder for TimedEncoder<'_> {
+    fn capabilities(&self) -> codec::CodecCapabilities {
+        self.encoder.capabilities()
+    }
+
+    fn max_chunk_size(&self) -> usize {
+        self.encoder.max_chunk_size()
+    }
+
+    fn compressed_len_bound(&mut self, uncompressed_len: usize) -> usize {
+        self.encoder.compressed_len_bound(uncompressed_len)
+    }
+
+    fn compress(&mut self, src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        if !self.enabled {
+            return self.encoder.compress(src, dest);
+        }
+        let start = Instant::now();
+        let result = self.encoder.compress(src, dest);
+        self.times.push(start.elapsed());
+        result
+    }
+}
+
+fn run_verify_cmd(cfg: CompressionCfg) -> anyhow::Result<()> {
+    let dict = dictionary(&cfg.input.dictionary)?;
+    let options = cfg.codec_options();
+    let mut encoder = encoder(cfg.algorithm, cfg.level(), dict.as_deref(), &options)?;
+    let dict = dict.filter(|_| encoder.capabilities().dictionary);
+    let mut decoder = decoder(cfg.algorithm, dict.as_deref(), &options)?;
+
+    let mut input = open_input_range(&cfg)?;
+    let mut buffered_input = Vec::new();
+    input.read_to_end(&mut buffered_input)?;
+
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_cfg(
+        &cfg,
+        Cursor::new(&buffered_input),
+        &mut compressed,
+        encoder.as_mut(),
+        |_, _| {},
+    )?;
+    compressed.rewind()?;
+    let mut comparison = Compare::new(&buffered_input);
+    decompress_with_options(
+        compressed,
+        &mut comparison,
+        decoder.as_mut(),
+        &own_output_decompression_options(),
+    )?;
+
+    match comparison.first_mismatch() {
+        None => {
+            println!("{}: OK", cfg.input.path.display());
+            Ok(())
+        }
+        Some(offset) => bail!(
+            "{}: decompressed data differs from the original at offset {}",
+            cfg.input.path.display(),
+            offset
+        ),
+    }
+}
+
+impl BenchmarkManyCfg {
+    /// Returns the selected algorithms with the groups expanded, each one once
+    fn algorithms(&self) -> Vec<Algorithm> {
+        let mut algorithms = Vec::new();
+        for &algorithm in self.algorithms.iter().flat_map(|group| &group.0) {
+            if !algorithms.contains(&algorithm) {
+                algorithms.push(algorithm);
+            }
+        }
+        algorithms
+    }
+}
+
+fn run_benchmark_many_cmd(cfg: BenchmarkManyCfg) -> anyhow::Result<()> {
+    let dict = dictionary(&cfg.dictionary)?;
+    let inputs = read_benchmark_inputs(&cfg)?;
+    let sysinfo = cfg.sysinfo.then(SystemInfo::collect);
+    if let Some(sysinfo) = &sysinfo {
+        println!("{}", sysinfo);
+    }
+
+    let algorithms = cfg.algorithms();
+    let mut settings = Vec::new();
+    if !cfg.no_baseline {
+        settings.push((Algorithm::Copy, 0, None, Transform::None));
+    }
+    for &algorithm in &algorithms {
+        let levels = match &cfg.levels {
+            Some(levels) => supported_levels(algorithm, &levels.0, cfg.ultra),
+            None => {
+                let mut levels = algorithm.get_compression_levels();
+                if cfg.ultra && algorithm == Algorithm::Zstd {
+                    levels.extend(codec::zstd::MAX_REGULAR_LEVEL + 1..=22);
+                }
+                levels.into_iter().step_by(cfg.step.get()).collect()
+            }
+        };
+        for &level in &levels {
+            // Only the lz4 fast mode has an acceleration
+            let accelerations = if algorithm == Algorithm::Lz4 && level == 0 {
+                cfg.acceleration.iter().copied().map(Some).collect()
+            } else {
+                Vec::new()
+            };
+            let accelerations = if accelerations.is_empty() {
+                vec![None]
+            } else {
+                accelerations
+            };
+            for &acceleration in &accelerations {
+                for &transform in &cfg.transform {
+                    let setting = (algorithm, level, acceleration, transform);
+                    if !settings.contains(&setting) {
+                        settings.push(setting);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(dir) = &cfg.output_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create directory {}", dir.display()))?;
+    }
+
+    let mut runs = Vec::new();
+    for (input_cfg, data, sampled) in &inputs {
+        for &(algorithm, level, acceleration, transform) in &settings {
+            let compressed_output =
+                cfg.output_dir
+                    .as_ref()
+                    .filter(|_| cfg.keep_compressed)
+                    .map(|dir| {
+                        dir.join(compressed_output_name(
+                            &cfg,
+                            &input_cfg.path,
+                            algorithm,
+                            level,
+                            acceleration,
+                            transform,
+                        ))
+                    });
+            let run_cfg = CompressionCfg {
+                input: input_cfg.clone(),
+                output: OutputCfg::default(),
+                algorithm,
+                compression: Some(Level::Number(level)),
+                chunk_size: Some(match &cfg.chunk_size {
+                    Some(chunk_sizes) => chunk_sizes.for_algorithm(algorithm),
+                    None => algorithm.recommended_chunk_size(),
+                }),
+                long: None,
+                extreme: false,
+                ultra: cfg.ultra,
+                acceleration,
+                brotli_window: None,
+                brotli_mode: BrotliMode::default(),
+                zstd_dict_id: None,
+                transform,
+                checksum: Checksum::None,
+                footer_checksum: false,
+                strip_extension: false,
+                adaptive_chunks: false,
+                quiet: true,
+                raw: false,
+                single_block: false,
+                append: false,
+                remove_source: false,
+                json: false,
+                units: cfg.units,
+                offset: None,
+                length: sampled.then_some(data.len()),
+                input_size: None,
+                dry_run: false,
+                force: false,
+                show_frame_entropy: false,
+                frame_entropy_report: None,
+                per_chunk_stats: false,
+                ttfb: false,
+                chunk_latency: false,
+                compare_with: None,
+                synthetic: false,
+                seed: 0,
+                verify: cfg.verify,
+                cpu_affinity: None,
+                status_interval: None,
+                best_of: Vec::new(),
+                also: Vec::new(),
+            };
+            runs.push((run_cfg, data.as_slice(), compressed_output));
+        }
+    }
+
+    // Results are collected in the order of the runs, regardless of which finishes first
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(cfg.jobs.map_or(0, NonZeroUsize::get))
+        .build()?;
+    let mut results = pool.install(|| {
+        runs.into_par_iter()
+            .map(|(run_cfg, data, compressed_output)| {
+                benchmark(
+                    run_cfg,
+                    data,
+                    dict.as_deref(),
+                    compressed_output.as_deref(),
+                    |_, _| {},
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+    // The CPU time of the process includes the benchmarks running in parallel
+    if pool.current_num_threads() > 1 {
+        for result in &mut results {
+            result.compr_cpu_secs = None;
+            result.decompr_cpu_secs = None;
+        }
+    }
+
+    // Sorting, filtering and the summary apply to the results of each input separately
+    let mut results = results.into_iter();
+    let mut results_by_input = Vec::with_capacity(inputs.len());
+    for (input_cfg, _, _) in &inputs {
+        let mut input_results: Vec<_> = results.by_ref().take(settings.len()).collect();
+        if cfg.pareto {
+            retain_pareto_frontier(&mut input_results);
+            sort_results(&mut input_results, SortBy::Ratio);
+        }
+        if let Some(sort_by) = cfg.sort_by {
+            sort_results(&mut input_results, sort_by);
+        }
+        results_by_input.push(InputResults {
+            file: input_cfg.path.display().to_string(),
+            summary: BenchmarkSummary::new(&input_results),
+            results: input_results,
+        });
+    }
+
+    if cfg.json {
+        println!("{}", serde_json::to_string_pretty(&results_by_input)?);
+    } else {
+        for (index, input_results) in results_by_input.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            if inputs.len() > 1 {
+                println!("{}:", input_results.file);
+            }
+            for result in &input_results.results {
+                println!("{}", result);
+            }
+            if let Some(summary) = &input_results.summary {
+                println!("\n{}", summary);
+            }
+        }
+    }
+    if let Some(dir) = &cfg.output_dir {
+        write_algorithm_summaries(dir, &algorithms, &results_by_input)?;
+    }
+    let report: Vec<_> = results_by_input
+        .into_iter()
+        .flat_map(|r| {
+            let mut results = r.results;
+            sort_by_settings(&mut results);
+            results
+        })
+        .collect();
+
+    if let Some(path) = &cfg.report {
+        write_report(path, sysinfo.as_ref(), &report)?;
+    }
+    if let Some(dir) = &cfg.output_dir {
+        write_report(&dir.join("results.csv"), sysinfo.as_ref(), &report)?;
+    }
+    Ok(())
+}
+
+/// Saves the results to a CSV file, preceded by the system information as comment lines
+fn write_report(
+    path: &Path,
+    sysinfo: Option<&SystemInfo>,
+    results: &[BenchmarkResult],
+) -> anyhow::Result<()> {
+    let mut file =
+        File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+    if let Some(sysinfo) = sysinfo {
+        for line in sysinfo.to_string().lines() {
+            writeln!(file, "# {}", line)?;
+        }
+    }
+    let mut writer = csv::Writer::from_writer(file);
+    for result in results {
+        writer.serialize(result)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the results of each algorithm to <ALGORITHM>.txt in the directory,
+/// in the same format as printed, grouped by input
+fn write_algorithm_summaries(
+    dir: &Path,
+    algorithms: &[Algorithm],
+    results_by_input: &[InputResults],
+) -> anyhow::Result<()> {
+    for &algorithm in algorithms {
+        let path = dir.join(format!("{}.txt", algorithm.name()));
+        let mut file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        for input_results in results_by_input {
+            let results = input_results
+                .results
+                .iter()
+                .filter(|r| r.algorithm == algorithm);
+            writeln!(file, "{}:", input_results.file)?;
+            for result in results {
+                writeln!(file, "{}", result)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of the file the compressed output of a run is saved to with --keep-compressed,
+/// e.g. data.txt.zstd-3.zstd, data.txt.zstd-3-delta_4.zstd or data.txt.lz4-0-a8.lz4.
+/// Inputs named like another input are named after their whole path instead,
+/// e.g. a_data.txt.zstd-3.zstd and b_data.txt.zstd-3.zstd for a/data.txt and b/data.txt.
+fn compressed_output_name(
+    cfg: &BenchmarkManyCfg,
+    input: &Path,
     algorithm: Algorithm,
-    dict: Option<&Vec<u8>>,
-) -> anyhow::Result<Box<dyn codec::Decoder>> {
-    Ok(match (algorithm, dict) {
-        (Algorithm::Copy, _) => Box::new(codec::copy::Copy),
-        (Algorithm::Lz4, _) => Box::new(codec::lz4::Lz4Decompressor),
-        (Algorithm::Zstd, None) => Box::new(zstd::bulk::Decompressor::new()?),
-        (Algorithm::Zstd, Some(dict)) => Box::new(zstd::bulk::Decompressor::with_dictionary(dict)?),
-        (Algorithm::Brotli, None) => Box::new(brotli::BrotliDecompressor),
-        (Algorithm::Brotli, Some(dict)) => Box::new(brotli::BrotliDictDecompressor::new(dict)),
-        (Algorithm::Snappy, _) => Box::new(snap::raw::Decoder::new()),
-        (Algorithm::Lzma, _) => Box::new(lzma::LzmaDecompressor),
-        (Algorithm::Lzav, _) => Box::new(codec::lzav::LzavDecompressor),
+    level: i32,
+    acceleration: Option<u32>,
+    transform: Transform,
+) -> String {
+    let input_name = if cfg.combine && cfg.paths.len() > 1 {
+        "combined".to_owned()
+    } else if cfg
+        .paths
+        .iter()
+        .filter(|path| path.file_name() == input.file_name())
+        .count()
+        > 1
+    {
+        let components: Vec<_> = input
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        components.join("_")
+    } else {
+        input
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+    let mut name = format!("{}.{}-{}", input_name, algorithm.name(), level);
+    if let Some(acceleration) = acceleration {
+        name.push_str(&format!("-a{}", acceleration));
+    }
+    if transform != Transform::None {
+        name.push_str(&format!("-{}", transform.to_string().replace(':', "_")));
+    }
+    format!("{}.{}", name, algorithm.extension())
+}
+
+/// Reads the files to benchmark, or only their samples selected with --sample-bytes,
+/// or the concatenation of all of them if requested, labeled with their paths joined with `+`.
+/// Returns the data of each input with a flag set if it is only a sample of the file.
+fn read_benchmark_inputs(cfg: &BenchmarkManyCfg) -> io::Result<Vec<(InputCfg, Vec<u8>, bool)>> {
+    let mut inputs = Vec::with_capacity(cfg.paths.len());
+    for path in &cfg.paths {
+        let input_cfg = InputCfg {
+            path: path.clone(),
+            dictionary: cfg.dictionary.clone(),
+        };
+        let input = open_input(&input_cfg)?;
+        let file_len = input.metadata()?.len();
+        let limit = cfg.sample_bytes.map_or(u64::MAX, |len| len as u64);
+        let mut data = Vec::new();
+        input.take(limit).read_to_end(&mut data)?;
+        let sampled = (data.len() as u64) < file_len;
+        inputs.push((input_cfg, data, sampled));
+    }
+    if cfg.combine && inputs.len() > 1 {
+        let label: Vec<String> = cfg.paths.iter().map(|p| p.display().to_string()).collect();
+        let input_cfg = InputCfg {
+            path: PathBuf::from(label.join("+")),
+            dictionary: cfg.dictionary.clone(),
+        };
+        let sampled = inputs.iter().any(|(_, _, sampled)| *sampled);
+        let data = inputs.into_iter().flat_map(|(_, data, _)| data).collect();
+        return Ok(vec![(input_cfg, data, sampled)]);
+    }
+    Ok(inputs)
+}
+
+/// Sorts the results stably, so equal results stay in the order they were run
+fn sort_results(results: &mut [BenchmarkResult], sort_by: SortBy) {
+    match sort_by {
+        // All results are for the same input, so this avoids comparing rounded ratios
+        SortBy::Ratio => results.sort_by_key(|r| r.compr_len),
+        SortBy::CompressSpeed => results.sort_by(|a, b| b.compr_mbps.total_cmp(&a.compr_mbps)),
+        SortBy::DecompressSpeed => {
+            results.sort_by(|a, b| b.decompr_mbps.total_cmp(&a.decompr_mbps))
+        }
+        SortBy::Name => sort_by_settings(results),
+    }
+}
+
+/// Sorts the results by the settings they were measured with, which differ for each result
+/// of an input, so unlike sorting by the measured values it gives the same order in every run
+fn sort_by_settings(results: &mut [BenchmarkResult]) {
+    results.sort_by_key(|r| {
+        (
+            r.algorithm.name(),
+            r.level,
+            r.acceleration,
+            r.chunk_size,
+            r.filter.clone(),
+        )
+    });
+}
+
+/// Removes the results dominated by another result, i.e. the ones for which another result
+/// compresses at least as well and at least as fast, and is strictly better in one of these
+fn retain_pareto_frontier(results: &mut Vec<BenchmarkResult>) {
+    let dominates = |a: &BenchmarkResult, b: &BenchmarkResult| {
+        a.compr_len <= b.compr_len
+            && a.compr_mbps >= b.compr_mbps
+            && (a.compr_len < b.compr_len || a.compr_mbps > b.compr_mbps)
+    };
+    let dominated: Vec<bool> = results
+        .iter()
+        .map(|r| results.iter().any(|other| dominates(other, r)))
+        .collect();
+    let mut dominated = dominated.into_iter();
+    results.retain(|_| !dominated.next().unwrap());
+}
+
+/// Filters out levels not supported by the algorithm, printing a warning for each of them
+fn supported_levels(algorithm: Algorithm, levels: &[i32], ultra: bool) -> Vec<i32> {
+    let mut supported = Vec::with_capacity(levels.len());
+    for &level in levels {
+        let needs_ultra = algorithm == Algorithm::Zstd && level > codec::zstd::MAX_REGULAR_LEVEL;
+        if !algorithm.compression_level_range().contains(&level) || (needs_ultra && !ultra) {
+            eprintln!(
+                "warning: {} does not support compression level {}, skipping",
+                algorithm.name(),
+                level
+            );
+        } else {
+            supported.push(level);
+        }
+    }
+    supported
+}
+
+fn run_train_dict_cmd(cfg: TrainDictCfg) -> anyhow::Result<()> {
+    let mut sample_paths = Vec::new();
+    for path in &cfg.samples {
+        collect_files(path, &mut sample_paths)
+            .with_context(|| format!("Could not read samples from {}", path.display()))?;
+    }
+    let mut samples = Vec::with_capacity(sample_paths.len());
+    let mut held_out = Vec::new();
+    for (index, path) in sample_paths.iter().enumerate() {
+        let sample = std::fs::read(path)
+            .with_context(|| format!("Could not read sample {}", path.display()))?;
+        match cfg.holdout {
+            Some(n) if index % n as usize == n as usize - 1 => held_out.push(sample),
+            _ => samples.push(sample),
+        }
+    }
+
+    let cover = CoverParams {
+        k: cfg.cover_k,
+        d: cfg.cover_d,
+        steps: cfg.cover_steps,
+    };
+    let dict = if cfg.cover || cover != CoverParams::default() {
+        let (dict, cover) = codec::zstd::train_cover(&samples, cfg.dict_size, cover)
+            .context("Failed to train the dictionary")?;
+        eprintln!(
+            "COVER parameters: k={} d={} steps={}",
+            cover.k.unwrap_or_default(),
+            cover.d.unwrap_or_default(),
+            cover.steps.unwrap_or_default()
+        );
+        dict
+    } else {
+        zstd::dict::from_samples(&samples, cfg.dict_size)
+            .context("Failed to train the dictionary")?
+    };
+    std::fs::write(&cfg.output, &dict)
+        .with_context(|| format!("Could not write dictionary {}", cfg.output.display()))?;
+    eprintln!(
+        "Trained a {} bytes dictionary from {} samples, saved to {}",
+        dict.len(),
+        samples.len(),
+        cfg.output.display()
+    );
+    if !held_out.is_empty() {
+        let uncompressed_len = held_out.iter().map(|s| s.len() as u64).sum();
+        let without_dict = compressed_len(&held_out, None)?;
+        let with_dict = compressed_len(&held_out, Some(&dict))?;
+        eprintln!(
+            "{} held out samples: {} => {} ({}) with the dictionary, {} ({}) without",
+            held_out.len(),
+            uncompressed_len,
+            with_dict,
+            Ratio::new(with_dict, uncompressed_len),
+            without_dict,
+            Ratio::new(without_dict, uncompressed_len)
+        );
+    }
+    Ok(())
+}
+
+/// Compresses each sample separately with zstd at the default level
+/// and returns the total length of the compressed samples
+fn compressed_len(samples: &[Vec<u8>], dict: Option<&[u8]>) -> anyhow::Result<u64> {
+    let level = zstd::DEFAULT_COMPRESSION_LEVEL;
+    let mut encoder = encoder(Algorithm::Zstd, level, dict, &CodecOptions::default())?;
+    let mut buf = Vec::new();
+    let mut total_len = 0;
+    for sample in samples {
+        buf.resize(encoder.compressed_len_bound(sample.len()), 0);
+        total_len += encoder.compress(sample, &mut buf)? as u64;
+    }
+    Ok(total_len)
+}
+
+/// Properties of an algorithm printed by the list-algorithms command
+#[derive(Serialize)]
+struct AlgorithmInfo {
+    name: String,
+    extension: String,
+    min_level: i32,
+    max_level: i32,
+    /// Levels run by benchmark-many if not given explicitly
+    benchmark_levels: Vec<i32>,
+    dictionary: bool,
+}
+
+impl AlgorithmInfo {
+    fn new(algorithm: Algorithm) -> anyhow::Result<AlgorithmInfo> {
+        let range = algorithm.compression_level_range();
+        let benchmark_levels = algorithm.get_compression_levels();
+        let encoder = encoder(
+            algorithm,
+            benchmark_levels[0],
+            None,
+            &CodecOptions::default(),
+        )?;
+        Ok(AlgorithmInfo {
+            name: algorithm.name(),
+            extension: algorithm.extension().to_owned(),
+            min_level: *range.start(),
+            max_level: *range.end(),
+            benchmark_levels,
+            dictionary: encoder.capabilities().dictionary,
+        })
+    }
+}
+
+impl Display for AlgorithmInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:10} .{:5} levels {}..={} (benchmarked: {}), dictionary: {}",
+            self.name,
+            self.extension,
+            self.min_level,
+            self.max_level,
+            format_levels(&self.benchmark_levels),
+            if self.dictionary { "yes" } else { "no" }
+        )
+    }
+}
+
+/// Formats levels compactly, replacing runs of consecutive levels with ranges
+fn format_levels(levels: &[i32]) -> String {
+    let mut runs: Vec<(i32, i32)> = Vec::new();
+    for &level in levels {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == level => *end = level,
+            _ => runs.push((level, level)),
+        }
+    }
+    let runs: Vec<String> = runs
+        .iter()
+        .map(|&(start, end)| match end - start {
+            0 => start.to_string(),
+            1 => format!("{}, {}", start, end),
+            _ => format!("{}..={}", start, end),
+        })
+        .collect();
+    runs.join(", ")
+}
+
+fn run_list_algorithms_cmd(cfg: ListAlgorithmsCfg) -> anyhow::Result<()> {
+    let algorithms = Algorithm::value_variants()
+        .iter()
+        .map(|&algorithm| AlgorithmInfo::new(algorithm))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if cfg.json {
+        println!("{}", serde_json::to_string_pretty(&algorithms)?);
+    } else {
+        for algorithm in &algorithms {
+            println!("{}", algorithm);
+        }
+    }
+    Ok(())
+}
+
+fn run_map_offsets_cmd(cfg: MapOffsetsCfg) -> anyhow::Result<()> {
+    let input = File::open(&cfg.path)
+        .with_context(|| format!("Could not open file {}", cfg.path.display()))?;
+    let frames = scan_frames(input)?;
+    for range in cfg.ranges {
+        println!("{}-{}:", range.start, range.end);
+        for (index, frame) in frames.iter().enumerate() {
+            let frame_range = frame.uncompressed_range();
+            if frame_range.start < range.end && range.start < frame_range.end {
+                println!(
+                    "  frame {}: compressed {}+{}, uncompressed {}-{}",
+                    index,
+                    frame.compressed_offset,
+                    frame.frame_len(),
+                    frame_range.start,
+                    frame_range.end
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Appends the path to `files` if it is a file, or all files contained in it if it is a directory
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_files(&entry?.path(), files)?;
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn open_input(config: &InputCfg) -> Result<File, Error> {
+    let file = File::open(&config.path).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Could not open file {}: {}", config.path.display(), e),
+        )
+    })?;
+    // Opening a directory succeeds on Unix, but reading it fails with a confusing error
+    if file.metadata()?.is_dir() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} is a directory; please give the path of a file",
+                config.path.display()
+            ),
+        ));
+    }
+    Ok(file)
+}
+
+/// Opens the range of the input file selected with --offset and --length
+fn open_input_range(cfg: &CompressionCfg) -> io::Result<RangeReader<File>> {
+    let input = open_input(&cfg.input)?;
+    let offset = cfg.offset.unwrap_or(0) as u64;
+    RangeReader::new(input, offset, cfg.length.map(|len| len as u64)).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Could not read {}: {}", cfg.input.path.display(), e),
+        )
     })
 }
 
-fn dictionary(input_cfg: &InputCfg) -> io::Result<Option<Vec<u8>>> {
-    match input_cfg.dict.as_ref() {
+/// Readable and seekable input, such as a file or a directory packed into a tar archive
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Returns the total length of the files in the directory, without following symbolic links
+fn directory_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += directory_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+fn progress_bar(input: &File, quiet: bool) -> io::Result<ProgressBar> {
+    Ok(progress_bar_with_len(input.metadata()?.len(), quiet))
+}
+
+fn progress_bar_with_len(len: u64, quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template(
+        "{wide_bar} {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta} left)",
+    )
+    .unwrap();
+    ProgressBar::new(len).with_style(style)
+}
+
+/// Shows the amount of data processed so far, for inputs of unknown length
+fn progress_bar_without_len(quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template("{spinner} {bytes} ({binary_bytes_per_sec})").unwrap();
+    ProgressBar::new_spinner().with_style(style)
+}
+
+/// Parses a byte range given as start-end
+fn parse_range(s: &str) -> Result<Range<u64>, String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| format!("invalid range '{}', expected start-end", s))?;
+    let start: u64 = start
+        .parse()
+        .map_err(|_| format!("invalid range start '{}'", start))?;
+    let end: u64 = end
+        .parse()
+        .map_err(|_| format!("invalid range end '{}'", end))?;
+    if start >= end {
+        return Err(format!("range '{}' is empty", s));
+    }
+    Ok(start..end)
+}
+
+/// Compression level given as a number or as the name of a preset
+#[derive(Clone, Copy, Debug)]
+enum Level {
+    Number(i32),
+    Preset(Preset),
+}
+
+impl Level {
+    fn for_algorithm(self, algorithm: Algorithm) -> i32 {
+        match self {
+            Level::Number(level) => level,
+            Level::Preset(preset) => algorithm.preset_level(preset),
+        }
+    }
+}
+
+/// Parses a compression level given as a number or as one of fast, default and best
+fn parse_level(s: &str) -> Result<Level, String> {
+    match s.parse() {
+        Ok(level) => Ok(Level::Number(level)),
+        Err(_) => Preset::from_str(s, true)
+            .map(Level::Preset)
+            .map_err(|_| "expected a number or one of fast, default, best".to_owned()),
+    }
+}
+
+/// Parses the description of synthetic data in the format KIND:SIZE
+fn parse_synthetic(s: &str) -> Result<(Synthetic, usize), String> {
+    let (kind, size) = s
+        .split_once(':')
+        .ok_or_else(|| "expected KIND:SIZE".to_owned())?;
+    let kind = Synthetic::from_str(kind, true)?;
+    Ok((kind, parse_size(size)?))
+}
+
+/// Parses a number of bytes with an optional decimal suffix k, m, g or binary suffix ki, mi, gi,
+/// ignoring case. The suffixes may end with a b, as in 16MB or 16MiB.
+fn parse_size(s: &str) -> Result<usize, String> {
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(digits_end);
+    let suffix = suffix.to_ascii_lowercase();
+    let unit = match suffix.strip_suffix('b') {
+        Some(unit) if !unit.is_empty() => unit,
+        _ => suffix.as_str(),
+    };
+    let multiplier: usize = match unit {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1_000_000,
+        "g" => 1_000_000_000,
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        "gi" => 1 << 30,
+        _ => return Err(format!("invalid size suffix '{}'", suffix)),
+    };
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+/// Chunk sizes given by the user for all algorithms or for particular ones
+#[derive(Clone)]
+struct ChunkSizes {
+    default: Option<usize>,
+    overrides: Vec<(Algorithm, usize)>,
+}
+
+impl ChunkSizes {
+    /// Returns the size given for the algorithm, the size given for all algorithms,
+    /// or the size recommended for the algorithm, in this order
+    fn for_algorithm(&self, algorithm: Algorithm) -> usize {
+        self.overrides
+            .iter()
+            .rev()
+            .find(|(a, _)| *a == algorithm)
+            .map(|(_, size)| *size)
+            .or(self.default)
+            .unwrap_or_else(|| algorithm.recommended_chunk_size())
+    }
+}
+
+/// Parses a comma-separated list of sizes for all algorithms or for particular ones,
+/// e.g. 256Ki,zstd=1Mi,all-fast=64Ki. Later sizes of an algorithm take precedence.
+fn parse_chunk_sizes(s: &str) -> Result<ChunkSizes, String> {
+    let mut chunk_sizes = ChunkSizes {
+        default: None,
+        overrides: Vec::new(),
+    };
+    for item in s.split(',') {
+        match item.split_once('=') {
+            Some((algorithms, size)) => {
+                let size = parse_size(size)?;
+                for algorithm in parse_algorithms(algorithms)?.0 {
+                    chunk_sizes.overrides.push((algorithm, size));
+                }
+            }
+            None if chunk_sizes.default.is_some() => {
+                return Err(format!(
+                    "more than one chunk size for all algorithms in '{}'",
+                    s
+                ))
+            }
+            None => chunk_sizes.default = Some(parse_size(item)?),
+        }
+    }
+    Ok(chunk_sizes)
+}
+
+/// An algorithm or a group of algorithms given by the user
+#[derive(Clone)]
+struct Algorithms(Vec<Algorithm>);
+
+/// Parses an algorithm name or one of the groups all, all-fast and all-strong
+fn parse_algorithms(s: &str) -> Result<Algorithms, String> {
+    let all = Algorithm::value_variants().iter().copied();
+    let algorithms = match s {
+        "all" => all.collect(),
+        "all-fast" => all
+            .filter(|a| a.is_fast() && *a != Algorithm::Copy)
+            .collect(),
+        "all-strong" => all.filter(|a| !a.is_fast()).collect(),
+        _ => match all.clone().find(|a| a.name().eq_ignore_ascii_case(s)) {
+            Some(algorithm) => vec![algorithm],
+            None => {
+                let names: Vec<String> = all.map(|a| a.name()).collect();
+                return Err(format!(
+                    "unknown algorithm '{}', expected all, all-fast, all-strong or one of: {}",
+                    s,
+                    names.join(", ")
+                ));
+            }
+        },
+    };
+    Ok(Algorithms(algorithms))
+}
+
+/// Compression levels given by the user
+#[derive(Clone)]
+struct Levels(Vec<i32>);
+
+/// Parses a comma-separated list of levels and inclusive ranges of levels, e.g. -1,1..3 or 1..=3
+fn parse_levels(s: &str) -> Result<Levels, String> {
+    let mut levels = Vec::new();
+    for item in s.split(',') {
+        let parse = |level: &str| {
+            level
+                .trim()
+                .parse::<i32>()
+                .map_err(|_| format!("invalid level '{}'", level))
+        };
+        match item.split_once("..") {
+            Some((start, end)) => {
+                let end = end.strip_prefix('=').unwrap_or(end);
+                levels.extend(parse(start)?..=parse(end)?)
+            }
+            None => levels.push(parse(item)?),
+        }
+    }
+    Ok(Levels(levels))
+}
+
+/// Parses a non-negative, possibly fractional number of seconds
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds '{}'", s))
+}
+
+fn parse_file_name_part(s: &str) -> Result<String, String> {
+    if s.chars().any(std::path::is_separator) {
+        Err("must not contain path separators".to_owned())
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+fn parse_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err("must be an octal number not greater than 7777".to_owned()),
+    }
+}
+
+/// Derives the output path from the input path, unless given explicitly.
+/// When decompressing, `original_extension` recorded by `compress --strip-extension`
+/// is appended to the name.
+fn output_path(
+    input_path: &Path,
+    output_cfg: &OutputCfg,
+    algorithm: Algorithm,
+    compress: bool,
+    original_extension: Option<&str>,
+) -> Result<PathBuf, Error> {
+    if let Some(path) = &output_cfg.output_path {
+        return Ok(path.clone());
+    }
+
+    // When decompressing, recover the original name by stripping the algorithm extension
+    let base_path = match algorithm.strip_extension(input_path) {
+        _ if compress => input_path.to_path_buf(),
+        Some(path) if original_extension.is_some() => path,
+        Some(path) => {
+            if path.extension().is_none() {
+                eprintln!(
+                    "warning: The decompressed file name {} has no extension",
+                    path.display()
+                );
+            }
+            path
+        }
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Cannot derive the output file name, because {} does not end with .{}. \
+                    Please use -o/--output option.",
+                    input_path.display(),
+                    algorithm.extension()
+                ),
+            ))
+        }
+    };
+
+    let mut file_name = OsString::from(&output_cfg.output_prefix);
+    file_name.push(base_path.file_name().unwrap_or_default());
+    file_name.push(&output_cfg.output_suffix);
+    if compress {
+        file_name.push(".");
+        file_name.push(algorithm.extension());
+    } else if let Some(extension) = original_extension {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    Ok(base_path.with_file_name(file_name))
+}
+
+fn open_output(
+    input_path: &Path,
+    output_cfg: &OutputCfg,
+    algorithm: Algorithm,
+    compress: bool,
+    append: bool,
+    force: bool,
+    original_extension: Option<&str>,
+) -> Result<File, Error> {
+    let output_path = output_path(
+        input_path,
+        output_cfg,
+        algorithm,
+        compress,
+        original_extension,
+    )?;
+    let mut options = OpenOptions::new();
+    options.read(append).write(true);
+    if append || force {
+        options.create(true).truncate(!append);
+    } else {
+        // Fails if the file exists, without a race between checking and creating it
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    if let Some(mode) = output_cfg.mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    let output = options.open(&output_path).map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => Error::new(
+            e.kind(),
+            format!(
+                "Output file {} already exists, use --force to overwrite",
+                output_path.display()
+            ),
+        ),
+        _ => Error::new(
+            e.kind(),
+            format!("Could not create file {}: {}", output_path.display(), e),
+        ),
+    })?;
+    // The mode passed to open is limited by umask and is ignored for existing files
+    #[cfg(unix)]
+    if let Some(mode) = output_cfg.mode {
+        use std::os::unix::fs::PermissionsExt;
+        output
+            .set_permissions(std::fs::Permissions::from_mode(mode))
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!(
+                        "Could not set permissions of {}: {}",
+                        output_path.display(),
+                        e
+                    ),
+                )
+            })?;
+    }
+    // Appended files hold earlier data, so they are never removed
+    if !append {
+        output_started(output_path);
+    }
+    Ok(output)
+}
+
+/// Deletes the input file, after making sure the outputs made from it are stored on disk
+fn remove_source(input_path: &Path, outputs: &[&File]) -> anyhow::Result<()> {
+    for output in outputs {
+        output.sync_all()?;
+    }
+    // Once the source is gone, the outputs are the only copy of the data
+    outputs_completed();
+    std::fs::remove_file(input_path)
+        .with_context(|| format!("Could not remove {}", input_path.display()))
+}
+
+fn dictionary(dictionary_cfg: &DictionaryCfg) -> io::Result<Option<Vec<u8>>> {
+    match dictionary_cfg.dict.as_deref() {
         None => Ok(None),
-        Some(p) => Ok(Some(load_dictionary(p, input_cfg.dict_len).map_err(
+        Some(p) => Ok(Some(load_dictionary(p, dictionary_cfg.dict_len).map_err(
             |e| {
                 Error::new(
                     e.kind(),
@@ -403,9 +3227,14 @@ fn dictionary(input_cfg: &InputCfg) -> io::Result<Option<Vec<u8>>> {
     }
 }
 
-fn load_dictionary(path: &Path, len: u64) -> io::Result<Vec<u8>> {
+fn load_dictionary(path: &Path, len: usize) -> io::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().lock().take(len as u64).read_to_end(&mut data)?;
+        return Ok(data);
+    }
     let mut dict_input = File::open(path)?;
-    let to_read = min(len, dict_input.metadata()?.len()) as usize;
+    let to_read = min(len as u64, dict_input.metadata()?.len()) as usize;
     let mut data = vec![0_u8; to_read];
     let mut ptr = &mut data[0..];
     while !ptr.is_empty() {
@@ -414,78 +3243,3 @@ fn load_dictionary(path: &Path, len: u64) -> io::Result<Vec<u8>> {
     }
     Ok(data)
 }
-
-fn compress<R: Read + Seek, W: Write + Seek>(
-    input: R,
-    output: W,
-    chunk_size: usize,
-    encoder: &mut dyn codec::Encoder,
-) -> anyhow::Result<Measurement> {
-    let input = BufReader::with_capacity(chunk_size, input);
-    let mut tmp_buf = vec![0; encoder.compressed_len_bound(chunk_size)];
-
-    measure(input, output, |input, output| {
-        while !input.fill_buf()?.is_empty() {
-            let input_chunk = input.buffer();
-            let uncompressed_len = input_chunk.len();
-            let compressed_len = encoder.compress(input_chunk, &mut tmp_buf)?;
-            output.write_u32::<LittleEndian>(uncompressed_len.try_into().unwrap())?;
-            output.write_u32::<LittleEndian>(compressed_len.try_into().unwrap())?;
-            output.write_all(&tmp_buf[0..compressed_len])?;
-            input.consume(uncompressed_len);
-        }
-        output.flush()?;
-        Ok(())
-    })
-}
-
-fn decompress<R: Read + Seek, W: Write + Seek>(
-    input: R,
-    output: W,
-    decoder: &mut dyn codec::Decoder,
-) -> anyhow::Result<Measurement> {
-    let input = BufReader::with_capacity(256 * 1024 * 1024, input);
-    let mut src = Vec::new();
-    let mut dest = Vec::new();
-
-    measure(input, output, |input, output| {
-        while !input.fill_buf()?.is_empty() {
-            let uncompressed_len = input.read_u32::<LittleEndian>()?.try_into().unwrap();
-            let frame_len = input.read_u32::<LittleEndian>()?.try_into().unwrap();
-            dest.resize(uncompressed_len, 0);
-            if input.buffer().len() >= frame_len {
-                let src = &input.buffer()[0..frame_len];
-                let count = decoder.decompress(src, &mut dest)?;
-                assert_eq!(count, uncompressed_len);
-                input.consume(frame_len);
-            } else {
-                src.resize(frame_len, 0);
-                input.read_exact(&mut src)?;
-                let count = decoder.decompress(&src, &mut dest)?;
-                assert_eq!(count, uncompressed_len);
-            }
-            output.write_all(&dest)?;
-        }
-        output.flush()?;
-        Ok(())
-    })
-}
-
-/// Measure performance of compression or decompression
-fn measure<I: Seek, O: Seek, T>(
-    mut input: I,
-    mut output: O,
-    mut process: impl FnMut(&mut I, &mut O) -> anyhow::Result<T>,
-) -> anyhow::Result<Measurement> {
-    let start_time = Instant::now();
-    process(&mut input, &mut output)?;
-    let end_time = Instant::now();
-    let input_pos = input.stream_position()?;
-    let output_pos = output.stream_position()?;
-
-    Ok(Measurement {
-        input_len: input_pos,
-        output_len: output_pos,
-        elapsed: end_time - start_time,
-    })
-}
@@ -0,0 +1,32 @@
+//! Peak memory usage of the current process.
+//! Supported only on Linux; on other platforms the peak is reported as 0.
+
+/// Resets the peak resident set size to the current one, so that a following
+/// call to [`peak_rss`] reports the peak reached since this call
+#[cfg(target_os = "linux")]
+pub fn reset_peak_rss() {
+    // Writing 5 to clear_refs resets VmHWM; this is best effort, as older kernels don't support it
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn reset_peak_rss() {}
+
+/// Returns the peak resident set size of the process in bytes, or 0 if it can't be determined
+#[cfg(target_os = "linux")]
+pub fn peak_rss() -> u64 {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return 0;
+    };
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map_or(0, |kb| kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn peak_rss() -> u64 {
+    0
+}
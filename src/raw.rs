@@ -0,0 +1,196 @@
+//! Single native streams of codecs with a standard file format, without the chunked container.
+//! Such files can be read by the command line tools of the codecs, but not randomly accessed.
+
+use crate::{codec, measure, Algorithm, CodecOptions, Measurement};
+use anyhow::bail;
+use lzma_sys::LZMA_PRESET_EXTREME;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, Write};
+use xz2::stream::{Check, LzmaOptions, Stream};
+
+/// Returns true if the algorithm has a standard stream format usable by [`compress_raw`]
+pub fn supports_raw(algorithm: Algorithm) -> bool {
+    matches!(
+        algorithm,
+        Algorithm::Zstd
+            | Algorithm::Lz4Frame
+            | Algorithm::Lzma
+            | Algorithm::LzmaAlone
+            | Algorithm::Bzip2
+            | Algorithm::Zlib
+            | Algorithm::Snappy
+    )
+}
+
+fn check_supported(algorithm: Algorithm) -> anyhow::Result<()> {
+    if !supports_raw(algorithm) {
+        bail!(
+            "{} has no standard stream format, so it can be used only with the chunked container",
+            algorithm.name()
+        );
+    }
+    Ok(())
+}
+
+fn warn_unsupported_dictionary(algorithm: Algorithm, dict: Option<&[u8]>) {
+    if dict.is_some() && algorithm != Algorithm::Zstd {
+        eprintln!(
+            "warning: {} streams do not support dictionaries, ignoring the dictionary",
+            algorithm.name()
+        );
+    }
+}
+
+/// Checks that [`compress_raw`] supports the algorithm and the compression level,
+/// so it can be done before creating the output
+pub fn check_compression(
+    algorithm: Algorithm,
+    compression: i32,
+    options: &CodecOptions,
+) -> anyhow::Result<()> {
+    check_supported(algorithm)?;
+    let range = algorithm.compression_level_range();
+    let needs_ultra = algorithm == Algorithm::Zstd
+        && compression > codec::zstd::MAX_REGULAR_LEVEL
+        && !options.zstd_ultra;
+    if !range.contains(&compression) || needs_ultra {
+        bail!(
+            "{} does not support compression level {}",
+            algorithm.name(),
+            compression
+        );
+    }
+    Ok(())
+}
+
+/// Compresses the whole input into a single stream in the native format of the algorithm
+pub fn compress_raw<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    algorithm: Algorithm,
+    compression: i32,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<Measurement> {
+    check_compression(algorithm, compression, options)?;
+    warn_unsupported_dictionary(algorithm, dict);
+    measure(input, output, |input, output| {
+        match algorithm {
+            Algorithm::Zstd => {
+                let dict = codec::zstd::dictionary_with_id(dict, options.zstd_dict_id)?;
+                let dict = dict.as_deref().unwrap_or_default();
+                let mut encoder = zstd::Encoder::with_dictionary(output, compression, dict)?;
+                if let Some(window_log) = options.zstd_long {
+                    encoder.long_distance_matching(true)?;
+                    encoder.window_log(window_log)?;
+                }
+                io::copy(input, &mut encoder)?;
+                encoder.finish()?;
+            }
+            Algorithm::Lz4Frame => {
+                let mut encoder = lz4::EncoderBuilder::new()
+                    .level(compression as u32)
+                    .build(output)?;
+                io::copy(input, &mut encoder)?;
+                encoder.finish().1?;
+            }
+            Algorithm::Lzma | Algorithm::LzmaAlone => {
+                let mut preset = compression as u32;
+                if options.lzma_extreme {
+                    preset |= LZMA_PRESET_EXTREME;
+                }
+                let stream = if algorithm == Algorithm::Lzma {
+                    Stream::new_easy_encoder(preset, Check::Crc64)?
+                } else {
+                    Stream::new_lzma_encoder(&LzmaOptions::new_preset(preset)?)?
+                };
+                let mut encoder = xz2::write::XzEncoder::new_stream(output, stream);
+                io::copy(input, &mut encoder)?;
+                encoder.finish()?;
+            }
+            Algorithm::Bzip2 => {
+                let level = bzip2::Compression::new(compression as u32);
+                let mut encoder = bzip2::write::BzEncoder::new(output, level);
+                io::copy(input, &mut encoder)?;
+                encoder.finish()?;
+            }
+            Algorithm::Zlib => {
+                let level = flate2::Compression::new(compression as u32);
+                let mut encoder = flate2::write::ZlibEncoder::new(output, level);
+                io::copy(input, &mut encoder)?;
+                encoder.finish()?;
+            }
+            Algorithm::Snappy => {
+                let mut encoder = snap::write::FrameEncoder::new(output);
+                io::copy(input, &mut encoder)?;
+                encoder.flush()?;
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    })
+}
+
+/// Decompresses a stream written by [`compress_raw`] or by the command line tool of the codec.
+/// Concatenated streams are decompressed one after another.
+pub fn decompress_raw<R: Read + Seek, W: Write + Seek>(
+    input: R,
+    output: W,
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+    options: &CodecOptions,
+) -> anyhow::Result<Measurement> {
+    check_supported(algorithm)?;
+    warn_unsupported_dictionary(algorithm, dict);
+    measure(input, output, |input, output| {
+        let mut input = BufReader::new(input);
+        // The decoders of these codecs stop at the end of the first stream
+        match algorithm {
+            Algorithm::Lz4Frame => return Ok(decompress_lz4_frames(&mut input, output)?),
+            Algorithm::Zlib => return Ok(decompress_zlib_streams(&mut input, output)?),
+            _ => {}
+        }
+        let mut decoder: Box<dyn Read + '_> = match algorithm {
+            Algorithm::Zstd => {
+                let dict = codec::zstd::dictionary_with_id(dict, options.zstd_dict_id)?;
+                let dict = dict.as_deref().unwrap_or_default();
+                let mut decoder = zstd::Decoder::with_dictionary(input, dict)?;
+                if let Some(window_log) = options.zstd_long {
+                    decoder.window_log_max(window_log)?;
+                }
+                Box::new(decoder)
+            }
+            Algorithm::Lzma => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(input)),
+            Algorithm::LzmaAlone => {
+                let stream = Stream::new_lzma_decoder(u64::MAX)?;
+                Box::new(xz2::bufread::XzDecoder::new_stream(input, stream))
+            }
+            Algorithm::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(input)),
+            Algorithm::Snappy => Box::new(snap::read::FrameDecoder::new(input)),
+            _ => unreachable!(),
+        };
+        io::copy(&mut decoder, output)?;
+        output.flush()?;
+        Ok(())
+    })
+}
+
+/// Decompresses lz4 frames one after another until the end of the input.
+/// Fails if the last frame is truncated.
+fn decompress_lz4_frames(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    while !input.fill_buf()?.is_empty() {
+        let mut decoder = lz4::Decoder::new(&mut *input)?;
+        io::copy(&mut decoder, output)?;
+        let (_, result) = decoder.finish();
+        result.map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated lz4 frame"))?;
+    }
+    output.flush()
+}
+
+/// Decompresses zlib streams one after another until the end of the input
+fn decompress_zlib_streams(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    while !input.fill_buf()?.is_empty() {
+        io::copy(&mut flate2::bufread::ZlibDecoder::new(&mut *input), output)?;
+    }
+    output.flush()
+}
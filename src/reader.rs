@@ -0,0 +1,285 @@
+use crate::codec::Decoder;
+use crate::transform::Transform;
+use crate::{scan_frames, seek_position, FrameInfo};
+use std::io;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+/// Number of cache lookups that found or missed the requested chunk
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Keeps the most recently used decoded chunks.
+/// Chunks are ordered from the least to the most recently used one.
+struct ChunkCache {
+    capacity: usize,
+    chunks: Vec<(usize, Vec<u8>)>,
+    stats: CacheStats,
+}
+
+impl ChunkCache {
+    fn new(capacity: usize) -> Self {
+        ChunkCache {
+            capacity: capacity.max(1),
+            chunks: Vec::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Moves the chunk to the most recently used position, if present
+    fn touch(&mut self, index: usize) -> bool {
+        match self.chunks.iter().position(|(i, _)| *i == index) {
+            Some(pos) => {
+                let chunk = self.chunks.remove(pos);
+                self.chunks.push(chunk);
+                self.stats.hits += 1;
+                true
+            }
+            None => {
+                self.stats.misses += 1;
+                false
+            }
+        }
+    }
+
+    /// Returns a buffer for a new chunk, reusing the one of the evicted chunk if the cache is full
+    fn take_buffer(&mut self) -> Vec<u8> {
+        if self.chunks.len() >= self.capacity {
+            self.chunks.remove(0).1
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn most_recent(&self) -> &[u8] {
+        &self.chunks.last().unwrap().1
+    }
+}
+
+/// Provides random access to the uncompressed data of a compressed stream.
+/// Only the chunks covering the requested bytes are decompressed.
+pub struct ChunkReader<R> {
+    input: R,
+    decoder: Box<dyn Decoder>,
+    start_pos: u64,
+    frames: Vec<FrameInfo>,
+    len: u64,
+    pos: u64,
+    src: Vec<u8>,
+    restored: Vec<u8>,
+    cache: ChunkCache,
+}
+
+impl<R: Read + Seek> ChunkReader<R> {
+    /// Creates a reader keeping only the most recently decoded chunk
+    pub fn new(input: R, decoder: Box<dyn Decoder>) -> io::Result<Self> {
+        Self::with_cache_capacity(input, decoder, 1)
+    }
+
+    /// Creates a reader keeping up to `capacity` recently decoded chunks
+    pub fn with_cache_capacity(
+        mut input: R,
+        decoder: Box<dyn Decoder>,
+        capacity: usize,
+    ) -> io::Result<Self> {
+        let start_pos = input.stream_position()?;
+        let frames = scan_frames(&mut input)?;
+        let len = frames
+            .last()
+            .map(|f| f.uncompressed_range().end)
+            .unwrap_or(0);
+        Ok(ChunkReader {
+            input,
+            decoder,
+            start_pos,
+            frames,
+            len,
+            pos: 0,
+            src: Vec::new(),
+            restored: Vec::new(),
+            cache: ChunkCache::new(capacity),
+        })
+    }
+
+    /// Length of the uncompressed data
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats
+    }
+
+    /// Makes the chunk the most recently used one in the cache, decoding it if needed
+    fn load_chunk(&mut self, index: usize) -> io::Result<()> {
+        if self.cache.touch(index) {
+            return Ok(());
+        }
+        let frame = &self.frames[index];
+        self.input.seek(SeekFrom::Start(
+            self.start_pos + frame.compressed_offset + 8,
+        ))?;
+        self.src.resize(frame.compressed_len as usize, 0);
+        self.input.read_exact(&mut self.src)?;
+
+        let mut dest = self.cache.take_buffer();
+        dest.resize(frame.uncompressed_len as usize, 0);
+        let count = if frame.stored {
+            dest.copy_from_slice(&self.src);
+            dest.len()
+        } else {
+            self.decoder.decompress(&self.src, &mut dest)?
+        };
+        if count != dest.len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Decompressed length {} of chunk {} does not match the expected length {}",
+                    count,
+                    index,
+                    dest.len()
+                ),
+            ));
+        }
+        if frame.transform != Transform::None {
+            frame.transform.reverse(&dest, &mut self.restored);
+            std::mem::swap(&mut dest, &mut self.restored);
+        }
+        let checksum = frame.checksum.read(&mut self.input)?;
+        frame
+            .checksum
+            .verify(&dest, checksum)
+            .map_err(|e| io::Error::new(e.kind(), format!("Chunk {}: {}", index, e)))?;
+        self.cache.chunks.push((index, dest));
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let index = self
+            .frames
+            .partition_point(|f| f.uncompressed_range().end <= pos);
+        self.load_chunk(index)?;
+        let offset = (pos - self.frames[index].uncompressed_offset) as usize;
+        let chunk = &self.cache.most_recent()[offset..];
+        let count = chunk.len().min(buf.len());
+        buf[..count].copy_from_slice(&chunk[..count]);
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for ChunkReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(pos, self.pos, self.len)?;
+        Ok(self.pos)
+    }
+}
+
+/// Reads a byte range of the input as if it was the whole input.
+/// Positions are relative to the start of the range.
+pub struct RangeReader<R> {
+    input: R,
+    offset: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Read + Seek> RangeReader<R> {
+    /// Creates a reader of `len` bytes starting at `offset`, or of all bytes after it if `len`
+    /// is not given. The range is cut at the end of the input.
+    /// Fails if the offset is beyond the end of the input.
+    pub fn new(mut input: R, offset: u64, len: Option<u64>) -> io::Result<Self> {
+        let input_len = input.seek(SeekFrom::End(0))?;
+        if offset > input_len {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Offset {} is beyond the end of the input of {} bytes",
+                    offset, input_len
+                ),
+            ));
+        }
+        let available = input_len - offset;
+        input.seek(SeekFrom::Start(offset))?;
+        Ok(RangeReader {
+            input,
+            offset,
+            len: len.map_or(available, |len| len.min(available)),
+            pos: 0,
+        })
+    }
+
+    /// Length of the range
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<R: Read + Seek> Read for RangeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos).min(buf.len() as u64) as usize;
+        let count = self.input.read(&mut buf[..remaining])?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for RangeReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = seek_position(pos, self.pos, self.len)?;
+        // Positions past the end of the range read nothing, like past the end of a file
+        self.input
+            .seek(SeekFrom::Start(self.offset.saturating_add(pos)))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// Reads a stream that can't seek, such as the standard input.
+/// Allows only querying the position, which is the number of bytes read so far.
+pub struct StreamReader<R> {
+    input: R,
+    pos: u64,
+}
+
+impl<R: Read> StreamReader<R> {
+    pub fn new(input: R) -> Self {
+        StreamReader { input, pos: 0 }
+    }
+}
+
+impl<R: Read> Read for StreamReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.input.read(buf)?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read> Seek for StreamReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.pos),
+            _ => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "The input stream is not seekable",
+            )),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Input data generated in memory, for benchmarking without sample files.
+//! The same kind, length and seed always give the same data.
+
+use clap::ValueEnum;
+
+/// Kind of generated data, from the most to the least compressible
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Synthetic {
+    /// Zero bytes only
+    Zeros,
+    /// A random block of 1 KiB repeated, compressible only by finding the repetitions
+    Repeating,
+    /// Random common English words separated by spaces, full stops and line breaks
+    Text,
+    /// Uniformly distributed random bytes, incompressible
+    Random,
+}
+
+/// Length of the block repeated by [`Synthetic::Repeating`]
+const REPEATING_BLOCK_LEN: usize = 1024;
+
+const WORDS: [&str; 48] = [
+    "the", "of", "and", "to", "in", "is", "that", "for", "it", "as", "was", "with", "be", "by",
+    "on", "not", "he", "this", "are", "or", "his", "from", "at", "which", "but", "have", "an",
+    "had", "they", "you", "were", "their", "one", "all", "we", "can", "her", "has", "there",
+    "been", "if", "more", "when", "will", "would", "who", "so", "no",
+];
+
+impl Synthetic {
+    /// Generates `len` bytes of data of this kind.
+    /// The seed selects the random data, it doesn't affect zeros.
+    pub fn generate(&self, len: usize, seed: u64) -> Vec<u8> {
+        let mut rng = SplitMix64(seed);
+        match self {
+            Synthetic::Zeros => vec![0; len],
+            Synthetic::Repeating => {
+                let block = random_bytes(&mut rng, REPEATING_BLOCK_LEN);
+                block.iter().copied().cycle().take(len).collect()
+            }
+            Synthetic::Text => text(&mut rng, len),
+            Synthetic::Random => random_bytes(&mut rng, len),
+        }
+    }
+}
+
+fn random_bytes(rng: &mut SplitMix64, len: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(len + 8);
+    while data.len() < len {
+        data.extend_from_slice(&rng.next_u64().to_le_bytes());
+    }
+    data.truncate(len);
+    data
+}
+
+fn text(rng: &mut SplitMix64, len: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(len + 16);
+    let mut sentence_len = 0;
+    while data.len() < len {
+        let random = rng.next_u64();
+        // The lower of two indexes makes the first words more frequent, as in real text
+        let word_count = WORDS.len() as u64;
+        let index = (random % word_count).min(random / word_count % word_count) as usize;
+        data.extend_from_slice(WORDS[index].as_bytes());
+        sentence_len += 1;
+        if sentence_len >= 5 && random >> 60 == 0 {
+            data.extend_from_slice(b".\n");
+            sentence_len = 0;
+        } else {
+            data.push(b' ');
+        }
+    }
+    data.truncate(len);
+    data
+}
+
+/// Fast pseudorandom generator with good statistical properties, not suitable for cryptography
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
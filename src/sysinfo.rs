@@ -0,0 +1,68 @@
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::thread;
+
+/// Hardware and codec library versions affecting benchmark results
+pub struct SystemInfo {
+    pub cpu_model: String,
+    /// Number of logical CPUs available to the process
+    pub cores: usize,
+    /// Name and version of each codec library, as reported by the library itself
+    pub libraries: Vec<(&'static str, String)>,
+}
+
+impl SystemInfo {
+    pub fn collect() -> SystemInfo {
+        SystemInfo {
+            cpu_model: cpu_model().unwrap_or_else(|| "unknown".to_owned()),
+            cores: thread::available_parallelism().map_or(1, |n| n.get()),
+            libraries: library_versions(),
+        }
+    }
+}
+
+impl Display for SystemInfo {
+    /// Prints one `name: value` pair per line
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "cpu: {}", self.cpu_model)?;
+        write!(f, "cores: {}", self.cores)?;
+        for (name, version) in &self.libraries {
+            write!(f, "\n{}: {}", name, version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the CPU model name on Linux
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "model name")
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+fn library_versions() -> Vec<(&'static str, String)> {
+    let lz4 = lz4::version();
+    let brotli = unsafe { brotlic_sys::BrotliEncoderVersion() };
+    let lzma = unsafe { CStr::from_ptr(lzma_sys::lzma_version_string()) };
+    vec![
+        ("zstd", zstd::zstd_safe::version_string().to_owned()),
+        (
+            "lz4",
+            format!("{}.{}.{}", lz4 / 10000, lz4 / 100 % 100, lz4 % 100),
+        ),
+        (
+            "brotli",
+            format!(
+                "{}.{}.{}",
+                brotli >> 24,
+                brotli >> 12 & 0xfff,
+                brotli & 0xfff
+            ),
+        ),
+        ("lzma", lzma.to_string_lossy().into_owned()),
+    ]
+}
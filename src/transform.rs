@@ -0,0 +1,148 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+
+/// Reversible preprocessing applied to each chunk before compression.
+/// Transforms don't change the length of the data, they only reorder or re-encode bytes
+/// so that the codec finds more redundancy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transform {
+    #[default]
+    None,
+    /// Replaces each byte with its difference from the byte the given number of positions before.
+    /// Turns slowly changing values, e.g. ramps, into runs of small numbers.
+    /// A distance larger than 1 compares corresponding bytes of fixed-size records.
+    Delta(NonZeroU32),
+    /// Treats the chunk as rows of the given number of bytes and stores it column by column.
+    /// Groups corresponding bytes of fixed-size records, e.g. the high bytes of integers, together.
+    /// Trailing bytes not forming a whole row are left in place.
+    Transpose(NonZeroU32),
+}
+
+const ID_NONE: u8 = 0;
+const ID_DELTA: u8 = 1;
+const ID_TRANSPOSE: u8 = 2;
+
+impl Transform {
+    /// Transforms `src` into `dest`, replacing its previous content
+    pub fn apply(&self, src: &[u8], dest: &mut Vec<u8>) {
+        dest.clear();
+        match *self {
+            Transform::None => dest.extend_from_slice(src),
+            Transform::Delta(stride) => {
+                let stride = stride.get() as usize;
+                dest.extend_from_slice(&src[..stride.min(src.len())]);
+                dest.extend(
+                    src.iter()
+                        .zip(src.iter().skip(stride))
+                        .map(|(prev, b)| b.wrapping_sub(*prev)),
+                );
+            }
+            Transform::Transpose(stride) => {
+                let stride = stride.get() as usize;
+                let rows = src.len() / stride;
+                for column in 0..stride {
+                    dest.extend((0..rows).map(|row| src[row * stride + column]));
+                }
+                dest.extend_from_slice(&src[rows * stride..]);
+            }
+        }
+    }
+
+    /// Restores the data passed to [`Transform::apply`] from its result
+    pub fn reverse(&self, src: &[u8], dest: &mut Vec<u8>) {
+        dest.clear();
+        match *self {
+            Transform::None => dest.extend_from_slice(src),
+            Transform::Delta(stride) => {
+                let stride = stride.get() as usize;
+                dest.extend_from_slice(src);
+                for i in stride..dest.len() {
+                    dest[i] = dest[i].wrapping_add(dest[i - stride]);
+                }
+            }
+            Transform::Transpose(stride) => {
+                let stride = stride.get() as usize;
+                let rows = src.len() / stride;
+                dest.resize(src.len(), 0);
+                for (i, &b) in src[..rows * stride].iter().enumerate() {
+                    let (column, row) = (i / rows, i % rows);
+                    dest[row * stride + column] = b;
+                }
+                dest[rows * stride..].copy_from_slice(&src[rows * stride..]);
+            }
+        }
+    }
+
+    /// Serializes the transform for storing it in a header frame
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Transform::None => vec![ID_NONE],
+            // Distance 1 is stored without the distance, as written before it was configurable
+            Transform::Delta(NonZeroU32::MIN) => vec![ID_DELTA],
+            Transform::Delta(stride) => {
+                let mut bytes = vec![ID_DELTA];
+                bytes.extend_from_slice(&stride.get().to_le_bytes());
+                bytes
+            }
+            Transform::Transpose(stride) => {
+                let mut bytes = vec![ID_TRANSPOSE];
+                bytes.extend_from_slice(&stride.get().to_le_bytes());
+                bytes
+            }
+        }
+    }
+
+    /// Reads a transform written by [`Transform::to_bytes`]
+    pub(crate) fn from_bytes(bytes: &[u8]) -> io::Result<Transform> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "Invalid transform in header");
+        let parse_stride = |bytes: &[u8]| {
+            let stride = u32::from_le_bytes(bytes.try_into().map_err(|_| invalid())?);
+            NonZeroU32::new(stride).ok_or_else(invalid)
+        };
+        match bytes.split_first() {
+            Some((&ID_NONE, [])) => Ok(Transform::None),
+            Some((&ID_DELTA, [])) => Ok(Transform::Delta(NonZeroU32::MIN)),
+            Some((&ID_DELTA, stride)) => parse_stride(stride).map(Transform::Delta),
+            Some((&ID_TRANSPOSE, stride)) => parse_stride(stride).map(Transform::Transpose),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    /// Parses `none`, `delta`, `delta:N`, where N is the distance of subtracted bytes,
+    /// or `transpose:N`, where N is the row length in bytes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_stride = |stride: &str| {
+            stride
+                .parse::<NonZeroU32>()
+                .map_err(|_| format!("invalid stride '{}' of transform '{}'", stride, s))
+        };
+        match s.split_once(':') {
+            None if s == "none" => Ok(Transform::None),
+            None if s == "delta" => Ok(Transform::Delta(NonZeroU32::MIN)),
+            Some(("delta", stride)) => parse_stride(stride).map(Transform::Delta),
+            Some(("transpose", stride)) => parse_stride(stride).map(Transform::Transpose),
+            _ => Err(format!(
+                "invalid transform '{}', expected none, delta[:N] or transpose:N",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transform::None => write!(f, "none"),
+            Transform::Delta(NonZeroU32::MIN) => write!(f, "delta"),
+            Transform::Delta(stride) => write!(f, "delta:{}", stride),
+            Transform::Transpose(stride) => write!(f, "transpose:{}", stride),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use clap::ValueEnum;
+use compresto::codec::registry::CODECS;
+use compresto::codec::CodecCapabilities;
+use compresto::{decoder, encoder, Algorithm, CodecOptions, Preset};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn extension_matching_algorithm_is_not_a_conflict() {
+    let path = Path::new("data.csv.LZ4");
+    assert_eq!(Algorithm::Lz4.conflicting_extension(path), None);
+}
+
+#[test]
+fn extension_of_other_algorithm_is_a_conflict() {
+    let path = Path::new("data.csv.lz4");
+    assert_eq!(
+        Algorithm::Zstd.conflicting_extension(path),
+        Some(Algorithm::Lz4)
+    );
+}
+
+#[test]
+fn unknown_extension_is_not_a_conflict() {
+    let path = Path::new("data.csv");
+    assert_eq!(Algorithm::Zstd.conflicting_extension(path), None);
+}
+
+#[test]
+fn strip_extension_keeps_inner_extension() {
+    assert_eq!(
+        Algorithm::Zstd.strip_extension(Path::new("dir/file.tar.zstd")),
+        Some(PathBuf::from("dir/file.tar"))
+    );
+}
+
+#[test]
+fn strip_extension_without_inner_extension() {
+    assert_eq!(
+        Algorithm::Zstd.strip_extension(Path::new("data.ZSTD")),
+        Some(PathBuf::from("data"))
+    );
+}
+
+#[test]
+fn strip_extension_of_other_algorithm() {
+    assert_eq!(Algorithm::Zstd.strip_extension(Path::new("data.lz4")), None);
+    assert_eq!(Algorithm::Zstd.strip_extension(Path::new("zstd")), None);
+}
+
+#[test]
+fn preset_levels_are_accepted_by_the_encoders() {
+    for &algorithm in Algorithm::value_variants() {
+        let range = algorithm.compression_level_range();
+        let levels: Vec<i32> = Preset::value_variants()
+            .iter()
+            .map(|&preset| algorithm.preset_level(preset))
+            .collect();
+        assert!(levels.windows(2).all(|l| l[0] <= l[1]), "{:?}", algorithm);
+        for level in levels {
+            assert!(range.contains(&level), "{:?} {}", algorithm, level);
+            encoder(algorithm, level, None, &CodecOptions::default()).unwrap();
+        }
+    }
+    assert_eq!(Algorithm::Zstd.preset_level(Preset::Best), 19);
+    assert_eq!(Algorithm::Brotli.preset_level(Preset::Best), 11);
+}
+
+#[test]
+fn codecs_report_their_capabilities() {
+    let capabilities =
+        |dictionary, self_describing, max_block_size: Option<usize>| CodecCapabilities {
+            dictionary,
+            self_describing,
+            max_block_size,
+            multi_threaded: false,
+        };
+    let expected = [
+        (Algorithm::Copy, capabilities(false, false, None)),
+        (Algorithm::Lz4, capabilities(true, false, Some(0x7E00_0000))),
+        (Algorithm::Lz4Frame, capabilities(false, true, None)),
+        (Algorithm::Zstd, capabilities(true, true, None)),
+        (Algorithm::Brotli, capabilities(true, false, None)),
+        (
+            Algorithm::Snappy,
+            capabilities(false, false, Some(u32::MAX as usize)),
+        ),
+        (Algorithm::Lzma, capabilities(false, true, None)),
+        (
+            Algorithm::Lzav,
+            capabilities(false, false, Some(i32::MAX as usize)),
+        ),
+        (Algorithm::Bzip2, capabilities(false, true, None)),
+        (Algorithm::Zlib, capabilities(false, true, None)),
+        (Algorithm::LzmaAlone, capabilities(false, true, None)),
+        (Algorithm::Lzo, capabilities(false, false, None)),
+    ];
+    assert_eq!(expected.len(), Algorithm::value_variants().len());
+    let options = CodecOptions::default();
+    for (algorithm, expected) in expected {
+        let level = *algorithm.compression_level_range().start();
+        let encoder = encoder(algorithm, level, None, &options).unwrap();
+        let decoder = decoder(algorithm, None, &options).unwrap();
+        // Compressing lz4 blocks with a dictionary needs the lz4-dict feature
+        let dictionary =
+            expected.dictionary && (algorithm != Algorithm::Lz4 || cfg!(feature = "lz4-dict"));
+        assert_eq!(
+            encoder.capabilities(),
+            CodecCapabilities {
+                dictionary,
+                ..expected
+            },
+            "{:?}",
+            algorithm
+        );
+        assert_eq!(decoder.capabilities(), expected, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn every_algorithm_is_registered_once_with_a_unique_extension() {
+    for &algorithm in Algorithm::value_variants() {
+        let count = CODECS.iter().filter(|c| c.algorithm == algorithm).count();
+        assert_eq!(count, 1, "{:?}", algorithm);
+        let path = PathBuf::from(format!("data.{}", algorithm.extension()));
+        assert_eq!(Algorithm::from_file_name(&path), Some(algorithm));
+    }
+    assert_eq!(CODECS.len(), Algorithm::value_variants().len());
+}
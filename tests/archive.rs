@@ -0,0 +1,97 @@
+use compresto::archive::{TarReader, TarWriter};
+use compresto::{
+    compress_with_options, decoder, decompress, encoder, read_header, Algorithm, CodecOptions,
+    ContainerOptions,
+};
+use std::fs;
+use std::io;
+use std::io::{Cursor, Seek};
+use std::path::{Path, PathBuf};
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("compresto-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn create_files(dir: &Path) {
+    fs::create_dir_all(dir.join("sub/empty")).unwrap();
+    fs::write(dir.join("a.txt"), "some data to compress ".repeat(1000)).unwrap();
+    let numbers: String = (0..100_000).map(|i| format!("{}\n", i)).collect();
+    fs::write(dir.join("sub/b.txt"), numbers).unwrap();
+}
+
+fn assert_same_files(expected: &Path, actual: &Path) {
+    for path in ["a.txt", "sub/b.txt"] {
+        assert_eq!(
+            fs::read(expected.join(path)).unwrap(),
+            fs::read(actual.join(path)).unwrap(),
+            "{}",
+            path
+        );
+    }
+    assert!(actual.join("sub/empty").is_dir());
+}
+
+#[test]
+fn tar_reader_output_unpacks_to_the_same_files() {
+    let dir = test_dir("tar-roundtrip");
+    let source = dir.join("source");
+    create_files(&source);
+    let target = dir.join("target");
+
+    let mut reader = TarReader::new(&source).unwrap();
+    let mut writer = TarWriter::new(&target).unwrap();
+    let len = io::copy(&mut reader, &mut writer).unwrap();
+    assert_eq!(reader.stream_position().unwrap(), len);
+    assert_eq!(writer.stream_position().unwrap(), len);
+    writer.finish().unwrap();
+
+    assert_same_files(&source, &target);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compressed_directory_is_marked_in_header() {
+    let dir = test_dir("tar-header");
+    let source = dir.join("source");
+    create_files(&source);
+    let target = dir.join("target");
+
+    let options = ContainerOptions {
+        tar: true,
+        ..ContainerOptions::default()
+    };
+    let mut encoder = encoder(Algorithm::Lz4, 1, None, &CodecOptions::default()).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_options(
+        TarReader::new(&source).unwrap(),
+        &mut compressed,
+        65536,
+        encoder.as_mut(),
+        options.clone(),
+        |_, _| {},
+    )
+    .unwrap();
+    let compressed = compressed.into_inner();
+    assert_eq!(read_header(compressed.as_slice()).unwrap(), options);
+
+    let mut decoder = decoder(Algorithm::Lz4, None, &CodecOptions::default()).unwrap();
+    let mut writer = TarWriter::new(&target).unwrap();
+    decompress(Cursor::new(&compressed), &mut writer, decoder.as_mut()).unwrap();
+    writer.finish().unwrap();
+
+    assert_same_files(&source, &target);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_archive_fails_to_unpack() {
+    let dir = test_dir("tar-invalid");
+    let mut writer = TarWriter::new(&dir.join("target")).unwrap();
+    // Writing may already fail once the unpacker gives up, but finishing must
+    let written = io::copy(&mut Cursor::new(vec![0x55; 4096]), &mut writer);
+    assert!(written.is_err() || writer.finish().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
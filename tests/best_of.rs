@@ -0,0 +1,38 @@
+use compresto::synthetic::Synthetic;
+use compresto::{compress_best_of, compress_bytes, decompress_bytes, Algorithm};
+
+#[test]
+fn keeps_smallest_output() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let algorithms = [
+        Algorithm::Lz4,
+        Algorithm::Zstd,
+        Algorithm::Lzma,
+        Algorithm::Snappy,
+    ];
+    let best = compress_best_of(&data, &algorithms, None, None).unwrap();
+
+    let sizes: Vec<_> = algorithms
+        .iter()
+        .map(|&a| {
+            let level = *a.get_compression_levels().last().unwrap();
+            let compressed =
+                compress_bytes(&data, a, level, a.recommended_chunk_size(), None).unwrap();
+            (a, compressed.len())
+        })
+        .collect();
+    let smallest = sizes.iter().min_by_key(|(_, len)| *len).unwrap();
+
+    assert_eq!(best.candidates, sizes);
+    assert_eq!(best.algorithm, smallest.0);
+    assert_eq!(best.output.len(), smallest.1);
+    assert_eq!(
+        decompress_bytes(&best.output, best.algorithm, None).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn rejects_empty_candidate_list() {
+    assert!(compress_best_of(b"data", &[], None, None).is_err());
+}
@@ -0,0 +1,164 @@
+use compresto::checksum::Checksum;
+use compresto::reader::ChunkReader;
+use compresto::synthetic::Synthetic;
+use compresto::{
+    compress_with_options, decoder, decompress, encoder, scan_frames, Algorithm, CodecOptions,
+    ContainerOptions,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+fn compress_with_checksum(data: &[u8], algorithm: Algorithm, checksum: Checksum) -> Vec<u8> {
+    let level = *algorithm.compression_level_range().start();
+    let mut encoder = encoder(algorithm, level, None, &CodecOptions::default()).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_options(
+        Cursor::new(data),
+        &mut compressed,
+        16384,
+        encoder.as_mut(),
+        ContainerOptions {
+            checksum,
+            ..ContainerOptions::default()
+        },
+        |_, _| {},
+    )
+    .unwrap();
+    compressed.into_inner()
+}
+
+fn decompress_to_vec(compressed: &[u8], algorithm: Algorithm) -> anyhow::Result<Vec<u8>> {
+    let mut decoder = decoder(algorithm, None, &CodecOptions::default())?;
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress(Cursor::new(compressed), &mut decompressed, decoder.as_mut())?;
+    Ok(decompressed.into_inner())
+}
+
+#[test]
+fn checksummed_streams_roundtrip() {
+    let data = Synthetic::Text.generate(100_000, 1);
+    for checksum in [Checksum::None, Checksum::Crc32, Checksum::Xxh3] {
+        for algorithm in [Algorithm::Copy, Algorithm::Lz4] {
+            let compressed = compress_with_checksum(&data, algorithm, checksum);
+            let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+            assert!(frames.iter().all(|f| f.checksum == checksum));
+            assert_eq!(decompress_to_vec(&compressed, algorithm).unwrap(), data);
+
+            let decoder = decoder(algorithm, None, &CodecOptions::default()).unwrap();
+            let mut reader = ChunkReader::new(Cursor::new(&compressed), decoder).unwrap();
+            reader.seek(SeekFrom::Start(40_000)).unwrap();
+            let mut buf = vec![0; 30_000];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[40_000..70_000]);
+        }
+    }
+}
+
+#[test]
+fn checksum_adds_fixed_bytes_per_frame() {
+    let data = Synthetic::Text.generate(100_000, 1);
+    let plain = compress_with_checksum(&data, Algorithm::Copy, Checksum::None);
+    let frame_count = scan_frames(Cursor::new(&plain)).unwrap().len();
+    for checksum in [Checksum::Crc32, Checksum::Xxh3] {
+        let compressed = compress_with_checksum(&data, Algorithm::Copy, checksum);
+        assert_eq!(
+            compressed.len(),
+            plain.len() + frame_count * checksum.encoded_len()
+        );
+    }
+}
+
+#[test]
+fn detects_corrupted_chunk() {
+    let data = Synthetic::Text.generate(50_000, 1);
+    for checksum in [Checksum::Crc32, Checksum::Xxh3] {
+        // Copy stores the chunks as they are, so the codec can't notice the corruption
+        let mut compressed = compress_with_checksum(&data, Algorithm::Copy, checksum);
+        let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+        let corrupted = (frames[1].compressed_offset + 8 + 100) as usize;
+        compressed[corrupted] ^= 1;
+
+        let error = decompress_to_vec(&compressed, Algorithm::Copy).unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(message.contains("chunk 1"), "{}", message);
+        assert!(message.contains("checksum mismatch"), "{}", message);
+
+        let decoder = decoder(Algorithm::Copy, None, &CodecOptions::default()).unwrap();
+        let mut reader = ChunkReader::new(Cursor::new(&compressed), decoder).unwrap();
+        let mut buf = vec![0; 16384];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(reader.read_exact(&mut buf).is_err());
+    }
+}
+
+#[test]
+fn reads_streams_of_version_3() {
+    let data = Synthetic::Text.generate(50_000, 1);
+    let compressed = compress_with_checksum(&data, Algorithm::Lz4, Checksum::None);
+    // Version 3 headers hold only the version when there is no transform
+    let mut legacy = vec![0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0, 3];
+    legacy.extend_from_slice(&compressed[12..]);
+    assert_eq!(decompress_to_vec(&legacy, Algorithm::Lz4).unwrap(), data);
+    let frames = scan_frames(Cursor::new(&legacy)).unwrap();
+    assert!(frames.iter().all(|f| f.checksum == Checksum::None));
+}
+
+fn compress_with_footer(data: &[u8]) -> Vec<u8> {
+    let mut encoder = encoder(Algorithm::Copy, 0, None, &CodecOptions::default()).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    let options = ContainerOptions {
+        checksum: Checksum::Crc32,
+        footer_checksum: true,
+        ..ContainerOptions::default()
+    };
+    compress_with_options(
+        Cursor::new(data),
+        &mut compressed,
+        16384,
+        encoder.as_mut(),
+        options,
+        |_, _| {},
+    )
+    .unwrap();
+    compressed.into_inner()
+}
+
+#[test]
+fn footer_checksum_roundtrip() {
+    let data = Synthetic::Text.generate(50_000, 1);
+    let compressed = compress_with_footer(&data);
+    assert_eq!(
+        decompress_to_vec(&compressed, Algorithm::Copy).unwrap(),
+        data
+    );
+    let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+    assert_eq!(frames.len(), 4);
+}
+
+#[test]
+fn footer_checksum_detects_reordered_chunks() {
+    let data = Synthetic::Text.generate(50_000, 1);
+    let mut compressed = compress_with_footer(&data);
+    let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+    // Both chunks have the same length and valid chunk checksums, only their order changes
+    let first = frames[0].compressed_offset as usize;
+    let second = frames[1].compressed_offset as usize;
+    let frame_len = frames[0].frame_len() as usize;
+    let first_frame = compressed[first..first + frame_len].to_vec();
+    compressed.copy_within(second..second + frame_len, first);
+    compressed[second..second + frame_len].copy_from_slice(&first_frame);
+
+    let error = decompress_to_vec(&compressed, Algorithm::Copy).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("Footer checksum mismatch"), "{}", message);
+}
+
+#[test]
+fn footer_checksum_detects_truncation() {
+    let data = Synthetic::Text.generate(50_000, 1);
+    let compressed = compress_with_footer(&data);
+    let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+    let truncated = &compressed[..frames[3].compressed_offset as usize];
+    let error = decompress_to_vec(truncated, Algorithm::Copy).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("Missing footer checksum"), "{}", message);
+}
@@ -0,0 +1,2104 @@
+use clap::ValueEnum;
+use compresto::Algorithm;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Creates an empty directory for the files of a single test
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("compresto-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn compresto() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_compresto"))
+}
+
+/// Returns the result rows printed by benchmark-many for a single input, without the summary
+fn result_lines(stdout: &str) -> Vec<&str> {
+    stdout.lines().take_while(|line| !line.is_empty()).collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn output_file_gets_requested_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = test_dir("mode");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+
+    for mode in [0o600, 0o666] {
+        let status = compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["-a", "lz4", "-q", "-f", "--mode", &format!("{:o}", mode)])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let metadata = std::fs::metadata(dir.join("data.txt.lz4")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, mode);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmarking_directory_fails_with_clear_error() {
+    let dir = test_dir("dir-input");
+    let output = compresto()
+        .arg("benchmark")
+        .arg(&dir)
+        .args(["-a", "lz4", "-q"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is a directory"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn status_lines_are_printed_during_compression() {
+    let dir = test_dir("status");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-b", "16k", "-q", "--status-interval", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let status_lines = stderr.lines().filter(|l| l.starts_with("status: ")).count();
+    assert!(status_lines > 1, "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compress_also_writes_output_for_each_algorithm() {
+    let dir = test_dir("also");
+    let input = dir.join("data.txt");
+    let data = "some data to compress ".repeat(10000);
+    std::fs::write(&input, &data).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "--also", "lz4,snappy", "-b", "16k", "-q"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lz4_line = stderr.lines().find(|l| l.starts_with("lz4 ")).unwrap();
+    assert!(lz4_line.contains("220000 => "), "{}", stderr);
+    assert!(lz4_line.ends_with("x)"), "{}", stderr);
+
+    for extension in ["zstd", "lz4", "sz"] {
+        let compressed = dir.join(format!("data.txt.{}", extension));
+        let decompressed = dir.join(format!("data.{}.txt", extension));
+        let status = compresto()
+            .arg("decompress")
+            .arg(&compressed)
+            .arg("-o")
+            .arg(&decompressed)
+            .arg("-q")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&decompressed).unwrap(), data);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compress_also_prints_ratio_of_empty_input() {
+    let dir = test_dir("also-empty");
+    let input = dir.join("empty.txt");
+    std::fs::write(&input, "").unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "--also", "lz4", "-q"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("NaN"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compress_best_of_prints_ratio_of_each_candidate() {
+    let dir = test_dir("best-of");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["--best-of", "lz4,zstd", "-q"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for algorithm in ["lz4", "zstd"] {
+        let line = stderr
+            .lines()
+            .find(|l| l.starts_with(&format!("{} ", algorithm)))
+            .unwrap();
+        assert!(line.contains("220000 => "), "{}", stderr);
+        assert!(line.ends_with("x)"), "{}", stderr);
+    }
+    assert!(stderr.contains("Kept the output of zstd"), "{}", stderr);
+    // Each candidate uses its own recommended chunk size
+    assert!(!stderr.contains("Using chunk size"), "{}", stderr);
+    assert!(dir.join("data.txt.zstd").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_sorts_results_by_ratio() {
+    let dir = test_dir("sort-by");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "copy,zstd,lz4", "--levels", "0,1"])
+        .args(["--sort-by", "ratio"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let names: Vec<&str> = result_lines(&stdout)
+        .into_iter()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    assert_eq!(names.last(), Some(&"copy"), "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_pareto_keeps_frontier_sorted_by_ratio() {
+    let dir = test_dir("pareto");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "copy,lzma", "--levels", "0", "--pareto"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // lzma compresses better, but copy is faster, so none of them dominates the other
+    let names: Vec<&str> = result_lines(&stdout)
+        .into_iter()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    assert_eq!(names, ["lzma", "copy"], "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn remove_source_deletes_input_only_after_success() {
+    let dir = test_dir("remove-source");
+    let input = dir.join("data.txt");
+    let content = "some data to compress ".repeat(1000);
+    std::fs::write(&input, &content).unwrap();
+    let compressed = dir.join("data.txt.lz4");
+
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-q", "--rm"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!input.exists());
+
+    let status = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--remove-source"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!compressed.exists());
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), content);
+
+    std::fs::write(&compressed, "not a compressed file").unwrap();
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--rm"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(compressed.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_records_filter() {
+    let dir = test_dir("filter");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .arg("--no-baseline")
+        .args(["-a", "lz4", "--levels", "1", "--filter", "none,delta:2"])
+        .arg("-r")
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines = result_lines(&stdout);
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(!lines[0].contains("filter"), "{}", stdout);
+    assert!(lines[1].contains("filter: delta:2"), "{}", stdout);
+
+    let report = std::fs::read_to_string(&report).unwrap();
+    assert!(report.lines().next().unwrap().contains(",filter,"));
+    assert!(report.contains(",delta:2,"), "{}", report);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_runs_each_file_unless_combined() {
+    let dir = test_dir("multi-file");
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    std::fs::write(&first, "some data to compress ".repeat(1000)).unwrap();
+    std::fs::write(&second, "other data to compress ".repeat(500)).unwrap();
+    let report = dir.join("report.csv");
+
+    let output = compresto()
+        .arg("benchmark-many")
+        .args([&first, &second])
+        .arg("--no-baseline")
+        .args(["-a", "lz4", "--levels", "1", "-r"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let rows: Vec<String> = std::fs::read_to_string(&report)
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    assert!(rows[0].starts_with("file,"), "{:?}", rows);
+    assert_eq!(rows.len(), 3, "{:?}", rows);
+    assert!(rows[1].contains("first.txt"), "{:?}", rows);
+    assert!(rows[2].contains("second.txt"), "{:?}", rows);
+
+    let output = compresto()
+        .arg("benchmark-many")
+        .args([&first, &second])
+        .arg("--no-baseline")
+        .args(["-a", "lz4", "--levels", "1", "--combine", "-r"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let rows: Vec<String> = std::fs::read_to_string(&report)
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    assert_eq!(rows.len(), 2, "{:?}", rows);
+    assert!(rows[1].contains("first.txt+"), "{:?}", rows);
+    assert!(rows[1].contains(",33500,"), "{:?}", rows);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_report_records_input_file_and_size() {
+    let dir = test_dir("report-file");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "lz4", "--levels", "1", "-r"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let mut reader = csv::Reader::from_path(&report).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let row = reader.records().next().unwrap().unwrap();
+    let field = |name: &str| &row[headers.iter().position(|h| h == name).unwrap()];
+    assert_eq!(field("file"), input.display().to_string());
+    assert_eq!(field("uncompr_len"), "22000");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_report_starts_with_sysinfo_comments() {
+    let dir = test_dir("sysinfo");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .arg("--no-baseline")
+        .args(["-a", "lz4", "--levels", "1", "--sysinfo", "-r"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\ncores: "), "{}", stdout);
+    assert!(stdout.contains("\nzstd: 1."), "{}", stdout);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .from_path(&report)
+        .unwrap();
+    assert_eq!(reader.records().count(), 1);
+    let content = std::fs::read_to_string(&report).unwrap();
+    assert!(content.starts_with("# cpu: "), "{}", content);
+    assert!(content.contains("# lz4: 1."), "{}", content);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn list_algorithms_shows_levels_and_dictionary_support() {
+    let output = compresto().arg("list-algorithms").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let zstd = stdout.lines().find(|l| l.starts_with("zstd ")).unwrap();
+    assert!(zstd.contains(".zstd"), "{}", zstd);
+    assert!(zstd.contains("dictionary: yes"), "{}", zstd);
+    let lz4 = stdout.lines().find(|l| l.starts_with("lz4 ")).unwrap();
+    assert!(lz4.contains("benchmarked: 0..=12"), "{}", lz4);
+
+    let output = compresto()
+        .args(["list-algorithms", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let copy = &json[0];
+    assert_eq!(copy["name"], "copy");
+    assert_eq!(copy["benchmark_levels"], serde_json::json!([0]));
+    assert_eq!(copy["dictionary"], false);
+}
+
+#[test]
+fn compress_rejects_unsupported_level() {
+    let dir = test_dir("unsupported-level");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lzav", "-c", "2", "-q"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("lzav does not support compression level 2"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("data.txt.lzav").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ignored_dictionary_is_reported() {
+    let dir = test_dir("ignored-dict");
+    let input = dir.join("data.txt");
+    let dict = dir.join("dict");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    std::fs::write(&dict, "some data").unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "snappy", "-q", "--dict"])
+        .arg(&dict)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("snappy does not support dictionaries"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stripped_extension_is_restored_on_decompression() {
+    let dir = test_dir("strip-extension");
+    let input = dir.join("data.txt");
+    let content = "some data to compress ".repeat(100);
+    std::fs::write(&input, &content).unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-q", "--strip-extension", "--rm"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let compressed = dir.join("data.lz4");
+    assert!(compressed.exists());
+    assert!(!input.exists());
+
+    let status = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-q")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), content);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn too_large_chunk_size_fails_cleanly() {
+    let dir = test_dir("large-chunk");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "-q", "-b", "5G"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exceeds the maximum"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dry_run_prints_output_paths_without_writing() {
+    let dir = test_dir("dry-run");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "--also", "lz4", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!(
+        "zstd\t{}\nlz4\t{}\n",
+        dir.join("data.txt.zstd").display(),
+        dir.join("data.txt.lz4").display()
+    );
+    assert_eq!(stdout, expected);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "-q", "--rm"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = compresto()
+        .arg("decompress")
+        .arg(dir.join("data.txt.zstd"))
+        .args(["-q", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!("zstd\t{}\n", input.display()));
+    assert!(!input.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn output_prefix_and_suffix_are_added_to_derived_names() {
+    let dir = test_dir("output-prefix-suffix");
+    for name in ["a.txt", "b.csv"] {
+        std::fs::write(dir.join(name), "some data to compress ".repeat(100)).unwrap();
+        let status = compresto()
+            .arg("compress")
+            .arg(dir.join(name))
+            .args(["-a", "zstd", "--also", "lz4", "-q"])
+            .args(["--output-prefix", "compressed_", "--output-suffix", ".v2"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", name);
+    }
+    for name in [
+        "compressed_a.txt.v2.zstd",
+        "compressed_a.txt.v2.lz4",
+        "compressed_b.csv.v2.zstd",
+        "compressed_b.csv.v2.lz4",
+    ] {
+        assert!(dir.join(name).exists(), "{}", name);
+    }
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(dir.join("compressed_a.txt.v2.zstd"))
+        .args(["--output-prefix", "restored_", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = dir.join("restored_compressed_a.txt.v2");
+    assert_eq!(stdout, format!("zstd\t{}\n", expected.display()));
+
+    for option in ["--output-prefix", "--output-suffix"] {
+        let output = compresto()
+            .arg("compress")
+            .arg(dir.join("a.txt"))
+            .args(["-a", "zstd", "--dry-run", option, "sub/name"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", option);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("must not contain path separators"),
+            "{}",
+            stderr
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn existing_output_is_overwritten_only_with_force() {
+    let dir = test_dir("force");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    let compressed = dir.join("data.txt.zstd");
+    std::fs::write(&compressed, "precious").unwrap();
+    let compress = |args: &[&str]| {
+        compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["-a", "zstd", "-q"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    for args in [&[][..], &["--dry-run"]] {
+        let output = compress(args);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("already exists, use --force"), "{}", stderr);
+        assert_eq!(std::fs::read(&compressed).unwrap(), b"precious");
+    }
+    assert!(compress(&["--force"]).status.success());
+    assert_ne!(std::fs::read(&compressed).unwrap(), b"precious");
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-q")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let status = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--force"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn decompress_dry_run_checks_the_stream_without_writing() {
+    let dir = test_dir("decompress-dry-run");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(2000)).unwrap();
+    let compressed = dir.join("data.txt.lz4");
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-b", "4k", "-q", "-o"])
+        .arg(&compressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::remove_file(&input).unwrap();
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("OK, 44000 bytes uncompressed"),
+        "{}",
+        stderr
+    );
+    assert!(!input.exists());
+
+    let mut data = std::fs::read(&compressed).unwrap();
+    data.truncate(data.len() - 100);
+    std::fs::write(&compressed, &data).unwrap();
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The error tells where the stream breaks
+    assert!(stderr.contains("Failed to decompress chunk"), "{}", stderr);
+    assert!(stderr.contains("at offset"), "{}", stderr);
+    assert!(!stderr.contains("OK"), "{}", stderr);
+    assert!(!input.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_verify_passes_for_all_codecs() {
+    let dir = test_dir("benchmark-verify");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "copy,zstd,lz4,snappy", "--levels", "1", "--verify"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let output = compresto()
+        .args(["benchmark", "-a", "zstd", "--verify"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn directory_is_compressed_as_tar_and_unpacked() {
+    let dir = test_dir("directory");
+    let input = dir.join("photos");
+    std::fs::create_dir_all(input.join("2024")).unwrap();
+    std::fs::write(input.join("a.txt"), "some data to compress ".repeat(1000)).unwrap();
+    std::fs::write(input.join("2024/b.txt"), "more data ".repeat(1000)).unwrap();
+    let status = compresto()
+        .args(["compress", "-q", "-a", "zstd"])
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let compressed = dir.join("photos.tar.zstd");
+    assert!(compressed.is_file());
+
+    // The directory is unpacked next to the compressed file, which refuses to overwrite it
+    let output = compresto()
+        .args(["decompress", "-q"])
+        .arg(&compressed)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    std::fs::rename(&input, dir.join("original")).unwrap();
+    let status = compresto()
+        .args(["decompress", "-q"])
+        .arg(&compressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for path in ["a.txt", "2024/b.txt"] {
+        assert_eq!(
+            std::fs::read(input.join(path)).unwrap(),
+            std::fs::read(dir.join("original").join(path)).unwrap()
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_step_thins_out_levels() {
+    let dir = test_dir("step");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "lz4,zlib", "--step", "4"])
+        .arg("--no-baseline")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let runs: Vec<(&str, &str)> = result_lines(&stdout)
+        .into_iter()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            Some((words.next()?, words.nth(1)?.trim_end_matches(':')))
+        })
+        .collect();
+    // lz4 levels 0 to 12 and zlib levels 1 to 9
+    let expected = [
+        ("lz4", "0"),
+        ("lz4", "4"),
+        ("lz4", "8"),
+        ("lz4", "12"),
+        ("zlib", "1"),
+        ("zlib", "5"),
+        ("zlib", "9"),
+    ];
+    assert_eq!(runs, expected, "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_summarizes_best_results() {
+    let dir = test_dir("summary");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "copy,lzma", "--levels", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout.split_once("\nSummary:\n").unwrap().1;
+    assert!(
+        summary.contains("best ratio:            lzma level 0: "),
+        "{}",
+        stdout
+    );
+    assert!(summary.contains("fastest compression:   "), "{}", stdout);
+    assert!(summary.contains("fastest decompression: "), "{}", stdout);
+
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "copy,lzma", "--levels", "0", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let input_results = &json[0];
+    assert_eq!(input_results["file"], input.display().to_string());
+    assert_eq!(input_results["results"].as_array().unwrap().len(), 2);
+    assert_eq!(input_results["summary"]["best_ratio"]["algorithm"], "Lzma");
+    assert!(input_results["summary"]["fastest_compression"]["compr_mbps"].is_number());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_pins_to_available_cpu_core_only() {
+    let dir = test_dir("cpu-affinity");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .args(["benchmark", "-a", "lz4", "--cpu-affinity", "1000000"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("CPU core 1000000 is not available"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_reports_chunk_latency_percentiles() {
+    let dir = test_dir("chunk-latency");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .args(["benchmark", "-a", "zstd", "-b", "16Ki", "--chunk-latency"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (_, latency) = stdout.split_once(", chunk p50/p95/p99: ").unwrap();
+    let percentiles: Vec<f64> = latency
+        .trim()
+        .trim_end_matches(" ms")
+        .split('/')
+        .map(|p| p.parse().unwrap())
+        .collect();
+    assert_eq!(percentiles.len(), 3);
+    assert!(percentiles.windows(2).all(|p| p[0] <= p[1]), "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn train_dict_with_cover_reports_holdout_ratio() {
+    let dir = test_dir("train-cover");
+    let samples = dir.join("samples");
+    std::fs::create_dir_all(&samples).unwrap();
+    for i in 0..100 {
+        let record = format!(
+            "{{\"id\": {}, \"name\": \"user{}\", \"email\": \"user{}@example.com\"}}",
+            i,
+            i * 7919 % 1000,
+            i
+        );
+        std::fs::write(samples.join(format!("{}.json", i)), record).unwrap();
+    }
+    let dict = dir.join("dict");
+    let output = compresto()
+        .arg("train-dict")
+        .arg(&samples)
+        .arg("-o")
+        .arg(&dict)
+        .args(["--dict-size", "4Ki", "--cover-k", "64", "--cover-d", "8"])
+        .args(["--holdout", "10"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("COVER parameters: k=64 d=8"), "{}", stderr);
+    assert!(stderr.contains("from 90 samples"), "{}", stderr);
+    assert!(stderr.contains("10 held out samples: "), "{}", stderr);
+    assert!(!std::fs::read(&dict).unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_generates_synthetic_input() {
+    let output = compresto()
+        .args([
+            "benchmark",
+            "-a",
+            "lz4",
+            "--synthetic",
+            "repeating:1M",
+            "--seed",
+            "5",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 MB"), "{}", stdout);
+
+    let output = compresto()
+        .args(["benchmark", "--synthetic", "noise:1Mi"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid synthetic data noise:1Mi"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn benchmark_many_saves_results_and_compressed_outputs_to_directory() {
+    let dir = test_dir("output-dir");
+    let input = dir.join("data.txt");
+    let data = "some data to compress ".repeat(10000);
+    std::fs::write(&input, &data).unwrap();
+    let output_dir = dir.join("results");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .arg("--no-baseline")
+        .args([
+            "-a",
+            "zstd,lz4",
+            "--levels",
+            "1",
+            "--transform",
+            "none,delta:2",
+        ])
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("--keep-compressed")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let csv = std::fs::read_to_string(output_dir.join("results.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 5, "{}", csv);
+    let summary = std::fs::read_to_string(output_dir.join("lz4.txt")).unwrap();
+    assert_eq!(summary.lines().count(), 3, "{}", summary);
+    assert!(
+        summary.lines().skip(1).all(|l| l.starts_with("lz4 ")),
+        "{}",
+        summary
+    );
+
+    let compressed = output_dir.join("data.txt.zstd-1-delta_2.zstd");
+    let decompressed = dir.join("decompressed.txt");
+    let status = compresto()
+        .args(["decompress", "-q", "-a", "zstd"])
+        .arg(&compressed)
+        .arg("-o")
+        .arg(&decompressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read_to_string(&decompressed).unwrap(), data);
+    assert!(output_dir.join("data.txt.lz4-1.lz4").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keep_compressed_tells_apart_inputs_of_the_same_name() {
+    let dir = test_dir("keep-compressed-names");
+    for (subdir, content) in [("a", "some data"), ("b", "other data")] {
+        std::fs::create_dir_all(dir.join(subdir)).unwrap();
+        std::fs::write(dir.join(subdir).join("data.txt"), content.repeat(1000)).unwrap();
+    }
+    let output = compresto()
+        .current_dir(&dir)
+        .args([
+            "benchmark-many",
+            "a/data.txt",
+            "b/data.txt",
+            "--no-baseline",
+        ])
+        .args(["-a", "lz4", "--levels", "1", "--output-dir", "results"])
+        .arg("--keep-compressed")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let results = dir.join("results");
+    for name in ["a_data.txt.lz4-1.lz4", "b_data.txt.lz4-1.lz4"] {
+        assert!(results.join(name).exists(), "{}", name);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compression_level_can_be_a_preset() {
+    let dir = test_dir("preset");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let output = compresto()
+        .args(["benchmark", "-a", "brotli", "-c", "best"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("lev.  11:"), "{}", stdout);
+
+    let output = compresto()
+        .args(["benchmark", "-c", "fastest"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("expected a number or one of fast, default, best"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_expands_algorithm_groups() {
+    let dir = test_dir("algorithm-groups");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let names = |algorithms: &str| {
+        let output = compresto()
+            .arg("benchmark-many")
+            .arg(&input)
+            .args(["-a", algorithms, "--step", "100"])
+            .arg("--no-baseline")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        result_lines(&stdout)
+            .into_iter()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    let all = names("all");
+    assert_eq!(all.len(), Algorithm::value_variants().len(), "{:?}", all);
+    assert!(all.contains(&"copy".to_owned()));
+
+    let mut groups = names("all-fast,all-strong");
+    groups.push("copy".to_owned());
+    groups.sort();
+    let mut sorted_all = all.clone();
+    sorted_all.sort();
+    assert_eq!(groups, sorted_all);
+
+    // Algorithms given both alone and in a group are benchmarked once
+    assert_eq!(names("zstd,all-strong").len(), names("all-strong").len());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_runs_copy_baseline_first() {
+    let dir = test_dir("baseline");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let run = |extra_args: &[&str]| {
+        let output = compresto()
+            .arg("benchmark-many")
+            .arg(&input)
+            .args(["-a", "lz4", "--levels", "1"])
+            .args(extra_args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let names = |stdout: &str| -> Vec<String> {
+        result_lines(stdout)
+            .into_iter()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    let stdout = run(&[]);
+    assert_eq!(names(&stdout), ["copy", "lz4"], "{}", stdout);
+    // Copying is always the fastest, so the summary compares only the compressing algorithms
+    let summary = stdout.split("Summary:").nth(1).unwrap();
+    assert!(!summary.contains("copy"), "{}", stdout);
+
+    let stdout = run(&["--no-baseline"]);
+    assert_eq!(names(&stdout), ["lz4"], "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compress_reads_only_the_given_range() {
+    let dir = test_dir("range");
+    let input = dir.join("data.txt");
+    let content: String = (0..10000).map(|i| format!("line {}\n", i)).collect();
+    std::fs::write(&input, &content).unwrap();
+    let compressed = dir.join("range.lz4");
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args([
+            "-a", "lz4", "--offset", "1000", "--length", "5000", "-q", "-o",
+        ])
+        .arg(&compressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let decompressed = dir.join("range.txt");
+    let status = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-q")
+        .arg("-o")
+        .arg(&decompressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let decompressed = std::fs::read(&decompressed).unwrap();
+    assert_eq!(decompressed, &content.as_bytes()[1000..6000]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_prints_binary_units_on_request() {
+    let run = |units: &str| {
+        let output = compresto()
+            .args(["benchmark", "-a", "lz4", "--synthetic", "text:1Mi"])
+            .args(["--units", units])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let stdout = run("iec");
+    assert!(stdout.contains(" 1 MiB "), "{}", stdout);
+    assert!(stdout.contains(" MiB/s"), "{}", stdout);
+    let stdout = run("si");
+    assert!(stdout.contains(" 1 MB "), "{}", stdout);
+    assert!(
+        stdout.contains(" MB/s") && !stdout.contains("MiB/s"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn compress_and_decompress_print_json_results() {
+    let dir = test_dir("json-result");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let compressed = dir.join("data.txt.zstd");
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-q", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["input_len"], 22000);
+    assert_eq!(
+        result["output_len"],
+        std::fs::metadata(&compressed).unwrap().len()
+    );
+    assert!(result["ratio"].as_f64().unwrap() < 0.1, "{}", result);
+    assert!(result["mbps"].is_number());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("=>"));
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--json", "-o"])
+        .arg(dir.join("restored.txt"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["output_len"], 22000);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn decompress_accepts_brotli_large_window() {
+    let dir = test_dir("brotli-large-window");
+    let input = dir.join("data.txt");
+    let content = "some data to compress ".repeat(1000);
+    std::fs::write(&input, &content).unwrap();
+    let compress = |compressed: &PathBuf, args: &[&str]| {
+        let status = compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["-a", "brotli", "--brotli-window", "26", "-q", "-o"])
+            .arg(compressed)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    let decompress = |compressed: &PathBuf, args: &[&str]| {
+        compresto()
+            .arg("decompress")
+            .arg(compressed)
+            .args(["-q", "--force", "-o"])
+            .arg(dir.join("restored.txt"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // The header records the large window
+    let container = dir.join("data.txt.br");
+    compress(&container, &[]);
+    let output = decompress(&container, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let restored = std::fs::read_to_string(dir.join("restored.txt")).unwrap();
+    assert_eq!(restored, content);
+
+    // A single block has no header, so the large window must be enabled explicitly
+    let block = dir.join("data.block.br");
+    compress(&block, &["--single-block"]);
+    let output = decompress(&block, &["--single-block"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("brotli error"), "{}", stderr);
+
+    // The window log doesn't need to be known to decompress
+    let output = decompress(&block, &["--single-block", "--brotli-large-window"]);
+    assert!(output.status.success(), "{:?}", output);
+    let restored = std::fs::read_to_string(dir.join("restored.txt")).unwrap();
+    assert_eq!(restored, content);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_decompress_measures_existing_file() {
+    let dir = test_dir("benchmark-decompress");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = compresto()
+        .arg("benchmark-decompress")
+        .arg(dir.join("data.txt.lz4"))
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["output_len"], 22000);
+    assert!(result["mbps"].is_number());
+    // Nothing is written next to the compressed file
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+    // Options of decompress writing or removing files are not accepted
+    for option in ["-o", "--force", "--rm", "--dry-run"] {
+        let output = compresto()
+            .arg("benchmark-decompress")
+            .arg(dir.join("data.txt.lz4"))
+            .arg(option)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", option);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_uses_chunk_sizes_of_algorithms() {
+    let dir = test_dir("chunk-sizes");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-a", "lz4,zstd", "--levels", "1", "-b", "4Ki,zstd=16Ki"])
+        .arg("-r")
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let mut reader = csv::Reader::from_path(&report).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+    let chunk_sizes: Vec<(String, String)> = reader
+        .records()
+        .map(|row| {
+            let row = row.unwrap();
+            (
+                row[column("algorithm")].to_owned(),
+                row[column("chunk_size")].to_owned(),
+            )
+        })
+        .collect();
+    let size = |algorithm: &str| {
+        let (_, size) = chunk_sizes
+            .iter()
+            .find(|(a, _)| a.eq_ignore_ascii_case(algorithm))
+            .unwrap();
+        size.as_str()
+    };
+    assert_eq!(size("lz4"), "4096");
+    assert_eq!(size("zstd"), "16384");
+    assert_eq!(size("copy"), "4096");
+
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .args(["-b", "4Ki,8Ki"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn size_suffixes_multiply_the_chunk_size() {
+    let sizes = [
+        ("16k", 16_000),
+        ("2m", 2_000_000),
+        ("1g", 1_000_000_000),
+        ("16ki", 16 << 10),
+        ("4mi", 4 << 20),
+        ("1gi", 1 << 30),
+    ];
+    for (chunk_size, expected) in sizes {
+        let output = compresto()
+            .args(["benchmark", "-a", "copy", "-c", "0"])
+            .args(["--synthetic", "text:64k"])
+            .args(["-b", chunk_size, "--json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", chunk_size, output);
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(json["chunk_size"], expected, "{}", chunk_size);
+    }
+    for chunk_size in ["4x", "-4k", "4.5k", "k"] {
+        let output = compresto()
+            .args(["benchmark", "-a", "copy", "-c", "0"])
+            .args(["--synthetic", "text:64k"])
+            .arg(format!("--chunk-size={}", chunk_size))
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", chunk_size);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("invalid size"),
+            "{}: {}",
+            chunk_size,
+            stderr
+        );
+    }
+}
+
+#[test]
+fn sizes_accept_decimal_and_binary_suffixes() {
+    for chunk_size in ["16384", "16Ki", "16KiB", "16kib", "16k", "16kB"] {
+        let output = compresto()
+            .args(["benchmark", "-a", "lz4", "--synthetic", "text:1MB"])
+            .args(["-b", chunk_size])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", chunk_size, output);
+    }
+    for chunk_size in ["16b", "16kx", "16KiBB"] {
+        let output = compresto()
+            .args(["benchmark", "-a", "lz4", "--synthetic", "text:1MB"])
+            .args(["-b", chunk_size])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", chunk_size);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("invalid size suffix"), "{}", stderr);
+    }
+}
+
+#[test]
+fn standard_input_is_compressed_with_size_hint() {
+    let dir = test_dir("stdin");
+    let data = "some data to compress ".repeat(10000);
+    let compressed = dir.join("data.lz4");
+    let mut child = compresto()
+        .args(["compress", "-", "-a", "lz4", "-b", "16k", "-q"])
+        .args(["--input-size", "110k", "--status-interval", "0"])
+        .arg("-o")
+        .arg(&compressed)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(data.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("status: 16 kB of 110 kB"), "{}", stderr);
+
+    let decompressed = dir.join("data.txt");
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-o")
+        .arg(&decompressed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&decompressed).unwrap(), data);
+
+    let output = compresto()
+        .args(["compress", "-", "-a", "lz4"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--output is required"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn size_hint_limits_chunks_of_standard_input() {
+    let dir = test_dir("stdin_size_hint");
+    let data = "some data to compress ".repeat(10000);
+    let compressed = dir.join("data.lz4");
+    let mut child = compresto()
+        .args(["compress", "-", "-a", "lz4", "-b", "1m", "-q"])
+        .args(["--input-size", "100k", "--status-interval", "0"])
+        .arg("-o")
+        .arg(&compressed)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(data.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    // The input is longer than the hint, so it takes more than one chunk
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("status: 100 kB of 100 kB"), "{}", stderr);
+    assert!(stderr.contains("status: 220 kB of 100 kB"), "{}", stderr);
+
+    let decompressed = dir.join("data.txt");
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-o")
+        .arg(&decompressed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&decompressed).unwrap(), data);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn size_hint_is_ignored_for_files() {
+    let dir = test_dir("file_size_hint");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let output = compresto()
+        .args(["compress", "-a", "lz4", "-q", "--input-size", "1k"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--input-size applies only to the standard input"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_compares_chunk_sizes_of_two_algorithms() {
+    let output = compresto()
+        .args(["benchmark", "--synthetic", "zeros:64Ki", "-b", "16Ki"])
+        .args(["-a", "copy", "-c", "0", "--compare-with", "lz4"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 6, "{}", stdout);
+    let columns: Vec<&str> = lines[1].split_whitespace().collect();
+    assert_eq!(columns[..4], ["0", "0", "16384", "16384"]);
+    assert_eq!(columns[5], "lz4");
+    assert_eq!(
+        lines[5],
+        "copy smaller in 0 chunks, lz4 smaller in 4 chunks, equal in 0 chunks"
+    );
+}
+
+#[test]
+fn append_checks_algorithm_of_existing_output() {
+    let dir = test_dir("append-algorithm");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let compressed = dir.join("data.lz4");
+    let compress = |algorithm: &str| {
+        compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["-a", algorithm, "-q", "--append", "-o"])
+            .arg(&compressed)
+            .output()
+            .unwrap()
+    };
+    assert!(compress("lz4").status.success());
+
+    let output = compress("zstd");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("compressed with lz4, not with zstd"),
+        "{}",
+        stderr
+    );
+
+    assert!(compress("lz4").status.success());
+    let decompressed = dir.join("data.out");
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-o")
+        .arg(&decompressed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(&decompressed).unwrap(),
+        "some data to compress ".repeat(2000)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn append_checks_transform_and_checksum_of_existing_output() {
+    let dir = test_dir("append-options");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let compressed = dir.join("data.lz4");
+    let compress = |options: &[&str]| {
+        compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["-a", "lz4", "-q", "--append", "-o"])
+            .arg(&compressed)
+            .args(options)
+            .output()
+            .unwrap()
+    };
+    assert!(compress(&["--checksum", "crc32"]).status.success());
+
+    let output = compress(&["--checksum", "xxh3"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("checksum crc32, not xxh3"), "{}", stderr);
+
+    let output = compress(&["--checksum", "crc32", "--transform", "delta"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not delta"), "{}", stderr);
+
+    assert!(compress(&["--checksum", "crc32"]).status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_samples_start_of_input() {
+    let dir = test_dir("sample-bytes");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .arg("--no-baseline")
+        .args(["-a", "lz4", "--levels", "1", "--sample-bytes", "4Ki", "-r"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(result_lines(&stdout)[0].contains(", sampled"), "{}", stdout);
+
+    let mut reader = csv::Reader::from_path(&report).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let row = reader.records().next().unwrap().unwrap();
+    let column = |name: &str| &row[headers.iter().position(|h| h == name).unwrap()];
+    assert_eq!(column("uncompr_len"), "4096");
+    assert_eq!(column("sampled"), "true");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_report_is_sorted_by_settings() {
+    let dir = test_dir("report-order");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    // Timing dependent columns differ between runs
+    let timing = [
+        "compr_mbps",
+        "decompr_mbps",
+        "compr_cpu_secs",
+        "decompr_cpu_secs",
+        "peak_memory_bytes",
+    ];
+    let run = |name: &str| {
+        let report = dir.join(name);
+        let output = compresto()
+            .arg("benchmark-many")
+            .arg(&input)
+            .args(["-a", "zstd,lz4", "--levels", "3,1", "-j", "4"])
+            .args(["--sort-by", "compress-speed", "-r"])
+            .arg(&report)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let mut reader = csv::Reader::from_path(&report).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        reader
+            .records()
+            .map(|row| {
+                let row = row.unwrap();
+                headers
+                    .iter()
+                    .zip(row.iter())
+                    .filter(|(header, _)| !timing.contains(header))
+                    .map(|(_, value)| value.to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let first = run("first.csv");
+    assert_eq!(first, run("second.csv"));
+    let settings: Vec<(&str, &str)> = first
+        .iter()
+        .map(|row| (row[1].as_str(), row[2].as_str()))
+        .collect();
+    assert_eq!(
+        settings,
+        [
+            ("Copy", "0"),
+            ("Lz4", "1"),
+            ("Lz4", "3"),
+            ("Zstd", "1"),
+            ("Zstd", "3")
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn force_does_not_ignore_container_version() {
+    let dir = test_dir("ignore-version");
+    let input = dir.join("data.txt");
+    let data = "some data to compress ".repeat(100);
+    std::fs::write(&input, &data).unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let compressed = dir.join("data.txt.lz4");
+    let mut bytes = std::fs::read(&compressed).unwrap();
+    // The version is the payload of the header frame at the start of the stream
+    bytes[8] += 1;
+    std::fs::write(&compressed, bytes).unwrap();
+    std::fs::write(&input, "precious").unwrap();
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--force"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Unsupported container version"),
+        "{}",
+        stderr
+    );
+
+    let status = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .args(["-q", "--force", "--ignore-version"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), data);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupt_removes_partial_output() {
+    let dir = test_dir("interrupt");
+    let output_path = dir.join("data.lz4");
+    let mut child = compresto()
+        .args(["compress", "-", "-a", "lz4", "-q", "-o"])
+        .arg(&output_path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // The input is kept open, so compression waits for more until interrupted
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&[b'a'; 100_000]).unwrap();
+    while !output_path.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+    assert!(!output_path.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_decompression_removes_partial_output() {
+    let dir = test_dir("failed-run");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(10000)).unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-b", "16k", "-q", "--rm"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let compressed = dir.join("data.txt.lz4");
+    let len = std::fs::metadata(&compressed).unwrap().len();
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&compressed)
+        .unwrap();
+    file.set_len(len - 10).unwrap();
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-q")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!input.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_unpacking_removes_created_directory() {
+    let dir = test_dir("failed-unpack");
+    let input = dir.join("tree");
+    std::fs::create_dir(&input).unwrap();
+    std::fs::write(
+        input.join("data.txt"),
+        "some data to compress ".repeat(10000),
+    )
+    .unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-b", "16k", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::remove_dir_all(&input).unwrap();
+    let compressed = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let len = std::fs::metadata(&compressed).unwrap().len();
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&compressed)
+        .unwrap();
+    file.set_len(len - 10).unwrap();
+
+    let output = compresto()
+        .arg("decompress")
+        .arg(&compressed)
+        .arg("-q")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!input.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_raw_compression_keeps_existing_output() {
+    let dir = test_dir("raw-invalid");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100)).unwrap();
+    for (algorithm, level) in [("lz4", "0"), ("zstd", "30")] {
+        let compressed = dir.join(format!("data.txt.{}", algorithm));
+        std::fs::write(&compressed, "precious").unwrap();
+        let output = compresto()
+            .arg("compress")
+            .arg(&input)
+            .args(["--raw", "-a", algorithm, "-c", level, "-q", "--force"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert_eq!(std::fs::read(&compressed).unwrap(), b"precious");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_reports_cpu_time_only_without_parallel_runs() {
+    let dir = test_dir("cpu-time");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    for (jobs, reported) in [("1", true), ("2", false)] {
+        let report = dir.join(format!("report-{}.csv", jobs));
+        let output = compresto()
+            .arg("benchmark-many")
+            .arg(&input)
+            .args(["-a", "lz4", "--levels", "1", "-j", jobs, "-r"])
+            .arg(&report)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let mut reader = csv::Reader::from_path(&report).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        for row in reader.records() {
+            let row = row.unwrap();
+            for name in ["compr_cpu_secs", "decompr_cpu_secs"] {
+                let value = &row[headers.iter().position(|h| h == name).unwrap()];
+                assert_eq!(!value.is_empty(), reported, "-j {} {}", jobs, name);
+            }
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn json_throughput_is_in_megabytes_whatever_the_units() {
+    let dir = test_dir("json-units");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(100_000)).unwrap();
+    let output = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-q", "--json", "--units", "iec", "-o"])
+        .arg(dir.join("data.zstd"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let input_len = result["input_len"].as_f64().unwrap();
+    let elapsed = result["elapsed_secs"].as_f64().unwrap();
+    let mbps = input_len / elapsed / 1_000_000.0;
+    let reported = result["mbps"].as_f64().unwrap();
+    assert!((reported - mbps).abs() <= 0.05, "{} MB/s: {}", mbps, result);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn no_header_decompresses_container_written_before_headers() {
+    let dir = test_dir("no-header");
+    let input = dir.join("data.txt");
+    let data = "some data to compress ".repeat(1000);
+    std::fs::write(&input, &data).unwrap();
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "lz4", "-b", "4k", "-q", "--rm"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Streams written before the container had headers start directly with a data frame
+    let compressed = dir.join("data.txt.lz4");
+    let bytes = std::fs::read(&compressed).unwrap();
+    let header_len = 8 + u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let headerless = dir.join("headerless.bin");
+    std::fs::write(&headerless, &bytes[header_len..]).unwrap();
+
+    let decompress = |path: &PathBuf, args: &[&str]| {
+        compresto()
+            .arg("decompress")
+            .arg(path)
+            .args(["-q", "-o"])
+            .arg(&input)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = decompress(&headerless, &["--no-header"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--algorithm"), "{}", stderr);
+
+    let output = decompress(&headerless, &["--legacy", "-a", "lz4"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), data);
+
+    // A stream with a header is not taken for a legacy one
+    let output = decompress(&compressed, &["--no-header", "-a", "lz4", "--force"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("expected to have none"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn decompress_detects_algorithm_from_header() {
+    let dir = test_dir("header-algorithm");
+    let input = dir.join("data.txt");
+    let data = "some data to compress ".repeat(1000);
+    std::fs::write(&input, &data).unwrap();
+    // The extension doesn't tell the algorithm
+    let compressed = dir.join("data.bin");
+    let status = compresto()
+        .arg("compress")
+        .arg(&input)
+        .args(["-a", "zstd", "-q", "-o"])
+        .arg(&compressed)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let decompress = |args: &[&str]| {
+        compresto()
+            .arg("decompress")
+            .arg(&compressed)
+            .args(["-q", "--force", "-o"])
+            .arg(&input)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = decompress(&[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), data);
+
+    let output = decompress(&["-a", "lz4", "--strict"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("which records zstd"), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn benchmark_many_sweeps_lz4_accelerations() {
+    let dir = test_dir("accelerations");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(1000)).unwrap();
+    let report = dir.join("report.csv");
+    let output = compresto()
+        .arg("benchmark-many")
+        .arg(&input)
+        .arg("--no-baseline")
+        .args([
+            "-a",
+            "lz4,zstd",
+            "--levels",
+            "0,1",
+            "--acceleration",
+            "1,4,8",
+        ])
+        .arg("-r")
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(result_lines(&stdout).len(), 6, "{}", stdout);
+    assert!(stdout.contains(", acceleration: 8"), "{}", stdout);
+
+    let mut reader = csv::Reader::from_path(&report).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+    let settings: Vec<(String, String, String)> = reader
+        .records()
+        .map(|row| {
+            let row = row.unwrap();
+            (
+                row[column("algorithm")].to_owned(),
+                row[column("level")].to_owned(),
+                row[column("acceleration")].to_owned(),
+            )
+        })
+        .collect();
+    let expected = [
+        ("Lz4", "0", "1"),
+        ("Lz4", "0", "4"),
+        ("Lz4", "0", "8"),
+        ("Lz4", "1", ""),
+        ("Zstd", "0", ""),
+        ("Zstd", "1", ""),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(a, l, x)| (a.to_string(), l.to_string(), x.to_string()))
+        .collect();
+    assert_eq!(settings, expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn map_offsets_lists_frames_overlapping_each_range() {
+    let dir = test_dir("map_offsets");
+    let input = dir.join("data.txt");
+    std::fs::write(&input, "some data to compress ".repeat(200)).unwrap();
+    let compressed = dir.join("data.lz4");
+    let output = compresto()
+        .args(["compress", "-a", "lz4", "-b", "1000", "-q"])
+        .arg(&input)
+        .arg("-o")
+        .arg(&compressed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = compresto()
+        .arg("map-offsets")
+        .arg(&compressed)
+        .args(["-r", "500-2500,4000-4001"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let frames: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split(", uncompressed ").last().unwrap())
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            "500-2500:",
+            "0-1000",
+            "1000-2000",
+            "2000-3000",
+            "4000-4001:",
+            "4000-4400"
+        ],
+        "{}",
+        stdout
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recommended_chunk_size_is_reported_once() {
+    let output = compresto()
+        .args(["benchmark", "-a", "lz4", "--synthetic", "text:64k"])
+        .args(["--compare-with", "zstd"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let notice = "Using chunk size of";
+    assert_eq!(stderr.matches(notice).count(), 1, "{}", stderr);
+
+    let output = compresto()
+        .args([
+            "benchmark",
+            "-a",
+            "lz4",
+            "-b",
+            "16k",
+            "--synthetic",
+            "text:64k",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains(notice), "{}", stderr);
+}
@@ -0,0 +1,58 @@
+use compresto::compare::Compare;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+
+#[test]
+fn identical_data_has_no_mismatch() {
+    let mut c = Compare::new(b"hello world");
+    c.write_all(b"hello ").unwrap();
+    c.write_all(b"world").unwrap();
+    assert_eq!(c.first_mismatch(), None);
+}
+
+#[test]
+fn reports_first_differing_byte() {
+    let mut c = Compare::new(b"hello world");
+    c.write_all(b"hello ").unwrap();
+    c.write_all(b"wOrld").unwrap();
+    assert_eq!(c.first_mismatch(), Some(7));
+}
+
+#[test]
+fn reports_length_mismatch() {
+    let mut shorter = Compare::new(b"hello world");
+    shorter.write_all(b"hello").unwrap();
+    assert_eq!(shorter.first_mismatch(), Some(5));
+
+    let mut longer = Compare::new(b"hello");
+    longer.write_all(b"hello world").unwrap();
+    assert_eq!(longer.first_mismatch(), Some(5));
+}
+
+#[test]
+fn seek_to_negative_position_fails() {
+    let mut c = Compare::new(&[0; 10]);
+    c.write_all(&[0; 10]).unwrap();
+    for pos in [SeekFrom::Current(-11), SeekFrom::End(-11)] {
+        let error = c.seek(pos).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+    // A failed seek leaves the position unchanged
+    assert_eq!(c.stream_position().unwrap(), 10);
+}
+
+#[test]
+fn seeking_past_the_end_does_not_extend_the_output() {
+    let mut c = Compare::new(b"hello world");
+    c.write_all(b"hello").unwrap();
+    c.seek(SeekFrom::Start(11)).unwrap();
+    assert_eq!(c.seek(SeekFrom::End(0)).unwrap(), 5);
+    assert_eq!(c.first_mismatch(), Some(5));
+
+    // Rewinding and overwriting a part, like when patching a header, keeps the end
+    c.seek(SeekFrom::Start(5)).unwrap();
+    c.write_all(b" world").unwrap();
+    c.rewind().unwrap();
+    c.write_all(b"he").unwrap();
+    assert_eq!(c.seek(SeekFrom::End(0)).unwrap(), 11);
+    assert_eq!(c.first_mismatch(), None);
+}
@@ -0,0 +1,79 @@
+use compresto::discard::Discard;
+use compresto::{compress, decoder, decompress, encoder, Algorithm, CodecOptions};
+use std::io::{Cursor, ErrorKind, Seek, SeekFrom, Write};
+use std::time::Instant;
+
+#[test]
+fn first_write_time_ignores_empty_writes() {
+    let mut sink = Discard::default();
+    sink.write_all(&[]).unwrap();
+    assert!(sink.first_write_time().is_none());
+    sink.write_all(b"data").unwrap();
+    let first = sink.first_write_time().unwrap();
+    sink.write_all(b"more").unwrap();
+    assert_eq!(sink.first_write_time(), Some(first));
+}
+
+#[test]
+fn time_to_first_byte_is_less_than_decompression_time() {
+    let data: Vec<u8> = (0..4_000_000u32).map(|i| (i % 251) as u8).collect();
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Zstd, 3, None, &options).unwrap();
+    let mut decoder = decoder(Algorithm::Zstd, None, &options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress(Cursor::new(&data), &mut compressed, 16384, encoder.as_mut()).unwrap();
+    compressed.rewind().unwrap();
+
+    let mut sink = Discard::default();
+    let start_time = Instant::now();
+    decompress(compressed, &mut sink, decoder.as_mut()).unwrap();
+    let total = start_time.elapsed();
+    let ttfb = sink.first_write_time().unwrap() - start_time;
+    assert!(
+        ttfb < total,
+        "TTFB {:?} not less than total {:?}",
+        ttfb,
+        total
+    );
+}
+
+#[test]
+fn seeks_relative_to_start_current_and_end() {
+    let mut sink = Discard::default();
+    sink.write_all(&[0; 100]).unwrap();
+    assert_eq!(sink.seek(SeekFrom::Start(10)).unwrap(), 10);
+    assert_eq!(sink.seek(SeekFrom::Current(5)).unwrap(), 15);
+    assert_eq!(sink.seek(SeekFrom::Current(-15)).unwrap(), 0);
+    assert_eq!(sink.seek(SeekFrom::End(0)).unwrap(), 100);
+    assert_eq!(sink.seek(SeekFrom::End(-30)).unwrap(), 70);
+}
+
+#[test]
+fn seek_to_negative_position_fails() {
+    let mut sink = Discard::default();
+    sink.write_all(&[0; 10]).unwrap();
+    for pos in [SeekFrom::Current(-11), SeekFrom::End(-11)] {
+        let error = sink.seek(pos).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+    // A failed seek leaves the position unchanged
+    assert_eq!(sink.stream_position().unwrap(), 10);
+}
+
+#[test]
+fn end_is_the_furthest_written_position() {
+    let mut sink = Discard::default();
+    sink.write_all(&[0; 100]).unwrap();
+    // Rewind and overwrite a part, like when patching a header
+    sink.rewind().unwrap();
+    sink.write_all(&[0; 8]).unwrap();
+    assert_eq!(sink.stream_position().unwrap(), 8);
+    assert_eq!(sink.seek(SeekFrom::End(0)).unwrap(), 100);
+
+    // Seeking past the end without writing doesn't extend the output
+    sink.seek(SeekFrom::Start(500)).unwrap();
+    assert_eq!(sink.seek(SeekFrom::End(0)).unwrap(), 100);
+    sink.seek(SeekFrom::Start(500)).unwrap();
+    sink.write_all(&[0; 10]).unwrap();
+    assert_eq!(sink.seek(SeekFrom::End(0)).unwrap(), 510);
+}
@@ -0,0 +1,39 @@
+use compresto::entropy::entropy;
+use compresto::synthetic::Synthetic;
+use compresto::{compress_with_observer, encoder, Algorithm, CodecOptions};
+use std::io::Cursor;
+
+#[test]
+fn entropy_of_constant_and_uniform_data() {
+    assert_eq!(entropy(&[]), 0.0);
+    assert_eq!(entropy(&[7; 1000]), 0.0);
+    let uniform: Vec<u8> = (0..=255).cycle().take(256 * 16).collect();
+    assert!((entropy(&uniform) - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn high_entropy_chunks_compress_worse() {
+    let mut data = Synthetic::Random.generate(16384, 1);
+    data.extend(b"abcabcabd".iter().cycle().take(16384));
+
+    let mut encoder = encoder(Algorithm::Zstd, 3, None, &CodecOptions::default()).unwrap();
+    let mut chunks = Vec::new();
+    compress_with_observer(
+        Cursor::new(&data),
+        Cursor::new(Vec::new()),
+        16384,
+        encoder.as_mut(),
+        |chunk, compressed_len| {
+            chunks.push((entropy(chunk), compressed_len as f64 / chunk.len() as f64))
+        },
+    )
+    .unwrap();
+
+    assert_eq!(chunks.len(), 2);
+    let (random_entropy, random_ratio) = chunks[0];
+    let (repetitive_entropy, repetitive_ratio) = chunks[1];
+    assert!(random_entropy > 7.9);
+    assert!(random_ratio > 0.95);
+    assert!(repetitive_entropy < 2.0);
+    assert!(repetitive_ratio < 0.1);
+}
@@ -0,0 +1,123 @@
+//! Exact bytes of the container format, so changes breaking existing files don't go unnoticed.
+//! The copy codec makes the frame payloads equal to the input.
+
+use compresto::checksum::Checksum;
+use compresto::transform::Transform;
+use compresto::{
+    compress_bytes, compress_with_options, decompress_bytes, encoder, read_header, Algorithm,
+    CodecOptions, ContainerOptions,
+};
+use std::io::{Cursor, ErrorKind};
+use std::num::NonZeroU32;
+
+const INPUT: &[u8] = b"hello, world! hello";
+
+#[rustfmt::skip]
+const PLAIN: &[u8] = &[
+    // Header frame: marker, payload length, version 6, no checksum, no flags, copy algorithm
+    0xff, 0xff, 0xff, 0xff, 4, 0, 0, 0, 6, 0, 0, 1,
+    // Data frames: uncompressed length, compressed length with the stored flag, data
+    8, 0, 0, 0, 8, 0, 0, 0x80, b'h', b'e', b'l', b'l', b'o', b',', b' ', b'w',
+    8, 0, 0, 0, 8, 0, 0, 0x80, b'o', b'r', b'l', b'd', b'!', b' ', b'h', b'e',
+    3, 0, 0, 0, 3, 0, 0, 0x80, b'l', b'l', b'o',
+];
+
+#[rustfmt::skip]
+const WITH_OPTIONS: &[u8] = &[
+    // Header frame: marker, payload length, version 6, crc32 checksums,
+    // flags of the footer checksum and the original extension, copy algorithm,
+    // extension length, extension, delta transform
+    0xff, 0xff, 0xff, 0xff, 9, 0, 0, 0, 6, 1, 3, 1, 3, b't', b'x', b't', 1,
+    // Data frames with the delta of each byte from the previous one,
+    // followed by the crc32 of the uncompressed chunk
+    8, 0, 0, 0, 8, 0, 0, 0x80, 104, 253, 7, 0, 3, 189, 244, 87, 164, 202, 161, 149,
+    8, 0, 0, 0, 8, 0, 0, 0x80, 111, 3, 250, 248, 189, 255, 72, 253, 19, 72, 165, 180,
+    3, 0, 0, 0, 3, 0, 0, 0x80, 108, 0, 3, 52, 179, 201, 170,
+    // Footer frame: marker, payload length, xxh3 of the whole input
+    0xfe, 0xff, 0xff, 0xff, 8, 0, 0, 0, 124, 61, 128, 86, 190, 217, 121, 63,
+];
+
+#[test]
+fn plain_container_layout() {
+    let compressed = compress_bytes(INPUT, Algorithm::Copy, 0, 8, None).unwrap();
+    assert_eq!(compressed, PLAIN);
+    assert_eq!(
+        decompress_bytes(PLAIN, Algorithm::Copy, None).unwrap(),
+        INPUT
+    );
+}
+
+#[test]
+fn container_layout_with_all_header_fields() {
+    let options = ContainerOptions {
+        transform: Transform::Delta(NonZeroU32::new(1).unwrap()),
+        checksum: Checksum::Crc32,
+        footer_checksum: true,
+        original_extension: Some("txt".to_owned()),
+        tar: false,
+        algorithm: Some(Algorithm::Copy),
+        brotli_large_window: false,
+    };
+    let mut encoder = encoder(Algorithm::Copy, 0, None, &CodecOptions::default()).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_options(
+        Cursor::new(INPUT),
+        &mut compressed,
+        8,
+        encoder.as_mut(),
+        options,
+        |_, _| {},
+    )
+    .unwrap();
+    assert_eq!(compressed.into_inner(), WITH_OPTIONS);
+    assert_eq!(
+        decompress_bytes(WITH_OPTIONS, Algorithm::Copy, None).unwrap(),
+        INPUT
+    );
+}
+
+#[test]
+fn original_extension_must_be_a_file_name() {
+    for extension in ["", ".", "..", "/etc", "a/../../b", "..\\x", "a\0b"] {
+        let mut header = vec![0xff, 0xff, 0xff, 0xff];
+        header.extend((5 + extension.len() as u32).to_le_bytes());
+        header.extend([6, 0, 2, 0, extension.len() as u8]);
+        header.extend(extension.as_bytes());
+        let err = read_header(header.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{:?}", extension);
+    }
+}
+
+#[test]
+fn reads_version_5_headers_without_algorithm() {
+    #[rustfmt::skip]
+    let header = [
+        // Header frame: marker, payload length, version 5, crc32 checksums,
+        // flag of the original extension, extension length, extension
+        0xff, 0xff, 0xff, 0xff, 7, 0, 0, 0, 5, 1, 2, 3, b't', b'x', b't',
+    ];
+    let options = read_header(header.as_slice()).unwrap();
+    assert_eq!(options.algorithm, None);
+    assert_eq!(options.checksum, Checksum::Crc32);
+    assert_eq!(options.original_extension.as_deref(), Some("txt"));
+}
+
+#[test]
+fn rejects_unknown_algorithm_ids() {
+    let header = [0xff, 0xff, 0xff, 0xff, 4, 0, 0, 0, 6, 0, 0, 200];
+    let err = read_header(header.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn brotli_large_window_flag_needs_version_6() {
+    // Header frame: marker, payload length, version, no checksum, large window flag, brotli
+    let header = [0xff, 0xff, 0xff, 0xff, 4, 0, 0, 0, 6, 0, 8, 5];
+    let options = read_header(header.as_slice()).unwrap();
+    assert_eq!(options.algorithm, Some(Algorithm::Brotli));
+    assert!(options.brotli_large_window);
+
+    let header = [0xff, 0xff, 0xff, 0xff, 3, 0, 0, 0, 5, 0, 8];
+    let err = read_header(header.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
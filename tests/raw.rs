@@ -0,0 +1,184 @@
+use clap::ValueEnum;
+use compresto::raw::{compress_raw, decompress_raw, supports_raw};
+use compresto::synthetic::Synthetic;
+use compresto::{compress_bytes, Algorithm, CodecOptions};
+use std::io::{Cursor, Read};
+
+fn compress_to_vec(data: &[u8], algorithm: Algorithm, options: &CodecOptions) -> Vec<u8> {
+    let mut compressed = Cursor::new(Vec::new());
+    let range = algorithm.compression_level_range();
+    let level = 1.clamp(*range.start(), *range.end());
+    compress_raw(
+        Cursor::new(data),
+        &mut compressed,
+        algorithm,
+        level,
+        None,
+        options,
+    )
+    .unwrap();
+    compressed.into_inner()
+}
+
+#[test]
+fn raw_streams_roundtrip() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let options = CodecOptions::default();
+    let algorithms = Algorithm::value_variants().iter().copied();
+    for algorithm in algorithms.filter(|&a| supports_raw(a)) {
+        let compressed = compress_to_vec(&data, algorithm, &options);
+        assert!(compressed.len() < data.len(), "{}", algorithm.name());
+        let mut decompressed = Cursor::new(Vec::new());
+        let result = decompress_raw(
+            Cursor::new(&compressed),
+            &mut decompressed,
+            algorithm,
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result.input_len, compressed.len() as u64);
+        assert_eq!(decompressed.into_inner(), data, "{}", algorithm.name());
+    }
+}
+
+#[test]
+fn raw_streams_are_readable_by_codec_libraries() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let options = CodecOptions::default();
+
+    let compressed = compress_to_vec(&data, Algorithm::Zstd, &options);
+    assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), data);
+
+    let compressed = compress_to_vec(&data, Algorithm::Lzma, &options);
+    let mut decompressed = Vec::new();
+    xz2::read::XzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, data);
+
+    let compressed = compress_to_vec(&data, Algorithm::Zlib, &options);
+    let mut decompressed = Vec::new();
+    flate2::read::ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn raw_zstd_supports_long_distance_matching() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let options = CodecOptions {
+        zstd_long: Some(24),
+        ..CodecOptions::default()
+    };
+    let compressed = compress_to_vec(&data, Algorithm::Zstd, &options);
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress_raw(
+        Cursor::new(&compressed),
+        &mut decompressed,
+        Algorithm::Zstd,
+        None,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(decompressed.into_inner(), data);
+}
+
+#[test]
+fn raw_concatenated_streams_decompress_to_concatenation() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let (first, second) = data.split_at(data.len() / 3);
+    let options = CodecOptions::default();
+    for algorithm in [Algorithm::Lz4Frame, Algorithm::Zlib] {
+        let mut compressed = compress_to_vec(first, algorithm, &options);
+        let first_len = compressed.len();
+        compressed.extend(compress_to_vec(second, algorithm, &options));
+        let mut decompressed = Cursor::new(Vec::new());
+        let result = decompress_raw(
+            Cursor::new(&compressed),
+            &mut decompressed,
+            algorithm,
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result.input_len, compressed.len() as u64);
+        assert_eq!(decompressed.into_inner(), data, "{}", algorithm.name());
+
+        // A truncated second stream is an error, not a shorter output
+        compressed.truncate(first_len + 20);
+        let result = decompress_raw(
+            Cursor::new(&compressed),
+            Cursor::new(Vec::new()),
+            algorithm,
+            None,
+            &options,
+        );
+        assert!(result.is_err(), "{}", algorithm.name());
+    }
+}
+
+#[test]
+fn raw_fails_for_algorithms_without_stream_format() {
+    let options = CodecOptions::default();
+    for algorithm in [
+        Algorithm::Lz4,
+        Algorithm::Brotli,
+        Algorithm::Lzav,
+        Algorithm::Lzo,
+    ] {
+        assert!(!supports_raw(algorithm));
+        let result = compress_raw(
+            Cursor::new(b"data"),
+            Cursor::new(Vec::new()),
+            algorithm,
+            1,
+            None,
+            &options,
+        );
+        let Err(error) = result else {
+            panic!("{} should fail", algorithm.name());
+        };
+        let message = error.to_string();
+        assert!(message.contains("no standard stream format"), "{}", message);
+    }
+}
+
+#[test]
+fn raw_zstd_uses_redundancy_across_chunks() {
+    // A random block repeated many times: each chunk alone is incompressible,
+    // but a single stream finds the repetitions within its window
+    let block = Synthetic::Random.generate(100_000, 1);
+    let data = block.repeat(10);
+
+    let chunked = compress_bytes(&data, Algorithm::Zstd, 3, block.len(), None).unwrap();
+    let options = CodecOptions::default();
+    let mut streamed = Cursor::new(Vec::new());
+    compress_raw(
+        Cursor::new(&data),
+        &mut streamed,
+        Algorithm::Zstd,
+        3,
+        None,
+        &options,
+    )
+    .unwrap();
+    let streamed = streamed.into_inner();
+    assert!(chunked.len() > data.len() * 9 / 10);
+    assert!(streamed.len() < chunked.len() / 5);
+}
+
+#[test]
+fn raw_lzma_alone_is_readable_by_liblzma() {
+    let data = Synthetic::Text.generate(200_000, 1);
+    let compressed = compress_to_vec(&data, Algorithm::LzmaAlone, &CodecOptions::default());
+    // The legacy format starts with the properties byte of the default lc=3, lp=0, pb=2
+    assert_eq!(compressed[0], 0x5d);
+    let stream = xz2::stream::Stream::new_auto_decoder(u64::MAX, 0).unwrap();
+    let mut decompressed = Vec::new();
+    xz2::read::XzDecoder::new_stream(compressed.as_slice(), stream)
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, data);
+}
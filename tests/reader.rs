@@ -0,0 +1,88 @@
+use compresto::reader::{CacheStats, ChunkReader, RangeReader};
+use compresto::synthetic::Synthetic;
+use compresto::{compress_bytes, decoder, Algorithm, CodecOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+fn reader(data: &[u8], chunk_size: usize, cache_capacity: usize) -> ChunkReader<Cursor<Vec<u8>>> {
+    let compressed = compress_bytes(data, Algorithm::Lz4, 1, chunk_size, None).unwrap();
+    let decoder = decoder(Algorithm::Lz4, None, &CodecOptions::default()).unwrap();
+    ChunkReader::with_cache_capacity(Cursor::new(compressed), decoder, cache_capacity).unwrap()
+}
+
+fn read_at(reader: &mut ChunkReader<Cursor<Vec<u8>>>, offset: u64, len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    reader.seek(SeekFrom::Start(offset)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn reads_whole_stream() {
+    let data = Synthetic::Text.generate(10_000, 1);
+    let mut reader = reader(&data, 1000, 1);
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).unwrap();
+    assert_eq!(reader.len(), data.len() as u64);
+    assert_eq!(output, data);
+}
+
+#[test]
+fn reads_across_chunk_boundaries() {
+    let data = Synthetic::Text.generate(10_000, 1);
+    let mut reader = reader(&data, 1000, 2);
+    assert_eq!(read_at(&mut reader, 2990, 1020), &data[2990..4010]);
+    reader.seek(SeekFrom::End(-5)).unwrap();
+    let mut tail = Vec::new();
+    reader.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, &data[9995..]);
+}
+
+#[test]
+fn second_read_of_chunk_hits_cache() {
+    let data = Synthetic::Text.generate(10_000, 1);
+    let mut reader = reader(&data, 1000, 4);
+    assert_eq!(read_at(&mut reader, 5100, 10), &data[5100..5110]);
+    assert_eq!(reader.cache_stats(), CacheStats { hits: 0, misses: 1 });
+    assert_eq!(read_at(&mut reader, 5500, 10), &data[5500..5510]);
+    assert_eq!(reader.cache_stats(), CacheStats { hits: 1, misses: 1 });
+}
+
+#[test]
+fn least_recently_used_chunk_is_evicted() {
+    let data = Synthetic::Text.generate(10_000, 1);
+    let mut reader = reader(&data, 1000, 2);
+    read_at(&mut reader, 0, 1);
+    read_at(&mut reader, 1000, 1);
+    read_at(&mut reader, 0, 1);
+    read_at(&mut reader, 2000, 1);
+    assert_eq!(reader.cache_stats(), CacheStats { hits: 1, misses: 3 });
+    read_at(&mut reader, 0, 1);
+    assert_eq!(reader.cache_stats(), CacheStats { hits: 2, misses: 3 });
+    read_at(&mut reader, 1000, 1);
+    assert_eq!(reader.cache_stats(), CacheStats { hits: 2, misses: 4 });
+}
+
+#[test]
+fn range_reader_reads_only_the_range() {
+    let data = Synthetic::Text.generate(1000, 1);
+    let mut reader = RangeReader::new(Cursor::new(&data), 100, Some(300)).unwrap();
+    assert_eq!(reader.len(), 300);
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).unwrap();
+    assert_eq!(output, &data[100..400]);
+
+    assert_eq!(reader.seek(SeekFrom::End(-10)).unwrap(), 290);
+    output.clear();
+    reader.read_to_end(&mut output).unwrap();
+    assert_eq!(output, &data[390..400]);
+}
+
+#[test]
+fn range_reader_is_cut_at_the_end_of_the_input() {
+    let data = Synthetic::Text.generate(1000, 1);
+    let reader = RangeReader::new(Cursor::new(&data), 900, Some(300)).unwrap();
+    assert_eq!(reader.len(), 100);
+    let reader = RangeReader::new(Cursor::new(&data), 900, None).unwrap();
+    assert_eq!(reader.len(), 100);
+    assert!(RangeReader::new(Cursor::new(&data), 1001, None).is_err());
+}
@@ -0,0 +1,740 @@
+use clap::ValueEnum;
+use compresto::codec::brotli::BrotliMode;
+use compresto::codec::copy::Copy;
+use compresto::codec::{Decoder, Encoder};
+use compresto::synthetic::Synthetic;
+use compresto::{
+    adaptive_chunk_sizes, check_single_block_len, compress, compress_adaptive, compress_bytes,
+    compress_single_block, decoder, decompress, decompress_bytes, decompress_single_block,
+    decompress_single_block_with_options, decompress_with_options, decompress_with_version_check,
+    encoder, read_header, scan_frames, Algorithm, CodecOptions, ContainerOptions,
+    DecompressionOptions, Measurement, Ratio, Units, DEFAULT_READ_BUFFER_SIZE, FORMAT_VERSION,
+    MAX_FRAME_LEN,
+};
+use std::io;
+use std::io::{Cursor, Seek, SeekFrom};
+use std::time::Duration;
+
+/// Generates a mix of incompressible and highly repetitive data
+fn test_data(len: usize) -> Vec<u8> {
+    let text = b"the quick brown fox jumps over the lazy dog ";
+    let mut data = Vec::with_capacity(len + 3000);
+    for random in Synthetic::Random.generate(len, 1).chunks(1000) {
+        data.extend_from_slice(random);
+        data.extend(text.iter().cycle().take(3000));
+    }
+    data.truncate(len);
+    data
+}
+
+fn roundtrip(algorithm: Algorithm, level: i32, chunk_size: usize, data: &[u8]) -> Vec<u8> {
+    let options = CodecOptions::default();
+    let mut encoder = encoder(algorithm, level, None, &options).unwrap();
+    let mut decoder = decoder(algorithm, None, &options).unwrap();
+
+    let mut compressed = Cursor::new(Vec::new());
+    compress(
+        Cursor::new(data),
+        &mut compressed,
+        chunk_size,
+        encoder.as_mut(),
+    )
+    .unwrap();
+    compressed.rewind().unwrap();
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress(compressed, &mut decompressed, decoder.as_mut()).unwrap();
+    decompressed.into_inner()
+}
+
+#[test]
+fn roundtrip_all_algorithms() {
+    let data = test_data(100_000);
+    for algorithm in Algorithm::value_variants() {
+        let levels = algorithm.get_compression_levels();
+        let level = levels[levels.len() / 2];
+        let output = roundtrip(*algorithm, level, 16384, &data);
+        assert!(
+            output == data,
+            "{} level {} output differs from input",
+            algorithm.name(),
+            level
+        );
+    }
+}
+
+#[test]
+fn roundtrip_empty_input() {
+    for algorithm in Algorithm::value_variants() {
+        let levels = algorithm.get_compression_levels();
+        assert!(roundtrip(*algorithm, levels[0], 16384, &[]).is_empty());
+    }
+}
+
+#[test]
+fn roundtrip_single_partial_chunk() {
+    let data = test_data(1000);
+    let output = roundtrip(Algorithm::Zstd, 3, 16384, &data);
+    assert_eq!(output, data);
+}
+
+#[test]
+fn roundtrip_bytes_all_algorithms() {
+    let data = test_data(100_000);
+    for algorithm in Algorithm::value_variants() {
+        let levels = algorithm.get_compression_levels();
+        let level = levels[levels.len() / 2];
+        let compressed = compress_bytes(&data, *algorithm, level, 16384, None).unwrap();
+        let header = read_header(Cursor::new(&compressed)).unwrap();
+        assert_eq!(header.algorithm, Some(*algorithm));
+        let output = decompress_bytes(&compressed, *algorithm, None).unwrap();
+        assert!(
+            output == data,
+            "{} level {} output differs from input",
+            algorithm.name(),
+            level
+        );
+    }
+}
+
+#[test]
+fn appended_streams_decompress_to_concatenation() {
+    let first = test_data(50_000);
+    let second = test_data(30_000);
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Zstd, 1, None, &options).unwrap();
+    let mut decoder = decoder(Algorithm::Zstd, None, &options).unwrap();
+
+    let mut compressed = Cursor::new(Vec::new());
+    compress(
+        Cursor::new(&first),
+        &mut compressed,
+        16384,
+        encoder.as_mut(),
+    )
+    .unwrap();
+    compressed.rewind().unwrap();
+    scan_frames(&mut compressed).unwrap();
+    compressed.seek(SeekFrom::End(0)).unwrap();
+    compress(
+        Cursor::new(&second),
+        &mut compressed,
+        16384,
+        encoder.as_mut(),
+    )
+    .unwrap();
+
+    compressed.rewind().unwrap();
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress(compressed, &mut decompressed, decoder.as_mut()).unwrap();
+    assert_eq!(decompressed.into_inner(), [first, second].concat());
+}
+
+/// An encoder claiming to produce more output than fits in the buffer
+struct OverflowingEncoder;
+
+impl Encoder for OverflowingEncoder {
+    fn compressed_len_bound(&mut self, _uncompressed_len: usize) -> usize {
+        16
+    }
+
+    fn compress(&mut self, _src: &[u8], dest: &mut [u8]) -> io::Result<usize> {
+        Ok(dest.len() + 1)
+    }
+}
+
+#[test]
+fn compress_rejects_output_exceeding_bound() {
+    let data = test_data(1000);
+    let result = compress(
+        Cursor::new(&data),
+        Cursor::new(Vec::new()),
+        1000,
+        &mut OverflowingEncoder,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn zlib_bound_fits_incompressible_input() {
+    let data = Synthetic::Random.generate(200_000, 1);
+    let options = CodecOptions::default();
+    for level in Algorithm::Zlib.compression_level_range() {
+        let mut encoder = encoder(Algorithm::Zlib, level, None, &options).unwrap();
+        for len in [0, 1, 100, 65_535, 65_536, 200_000] {
+            let bound = encoder.compressed_len_bound(len);
+            let mut compressed = vec![0; bound];
+            let compressed_len = encoder
+                .compress(&data[..len], &mut compressed)
+                .unwrap_or_else(|e| panic!("level {} length {}: {}", level, len, e));
+            assert!(compressed_len <= bound);
+        }
+    }
+}
+
+#[test]
+fn copy_reports_too_small_output_buffer() {
+    let data = test_data(1000);
+    let mut buffer = vec![0; data.len() - 1];
+    let error = Copy.compress(&data, &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    let error = Copy.decompress(&data, &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn brotli_reports_too_small_output_buffer() {
+    let data = test_data(100_000);
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Brotli, 5, None, &options).unwrap();
+    let mut compressed = vec![0; encoder.compressed_len_bound(data.len())];
+    let len = encoder.compress(&data, &mut compressed).unwrap();
+    compressed.truncate(len);
+
+    let mut decoder = decoder(Algorithm::Brotli, None, &options).unwrap();
+    let mut buffer = vec![0; 100];
+    let error = decoder.decompress(&compressed, &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(error.to_string(), "Output buffer too small");
+}
+
+#[test]
+fn brotli_reports_corrupted_input() {
+    // A stream starting with the large window marker is invalid without the extension
+    let corrupted = [0x11, 0x56, 0x7b, 0x2c, 0xff, 0x00, 0x13, 0x37];
+    let mut decoder = decoder(Algorithm::Brotli, None, &CodecOptions::default()).unwrap();
+    let mut buffer = vec![0; 1000];
+    let error = decoder.decompress(&corrupted, &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let message = error.to_string();
+    assert!(
+        message.starts_with("Failed to decompress: brotli error ERROR_FORMAT_"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn lzav_reports_error_codes() {
+    let options = CodecOptions::default();
+    let mut decoder = decoder(Algorithm::Lzav, None, &options).unwrap();
+    let mut buffer = vec![0; 1000];
+    let error = decoder.decompress(&[0; 16], &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "lzav decompress failed: unknown stream format"
+    );
+
+    let data = test_data(10_000);
+    let mut encoder = encoder(Algorithm::Lzav, 0, None, &options).unwrap();
+    let mut compressed = vec![0; encoder.compressed_len_bound(data.len())];
+    let len = encoder.compress(&data, &mut compressed).unwrap();
+    let error = decoder
+        .decompress(&compressed[..len], &mut buffer)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "lzav decompress failed: the output buffer is too small"
+    );
+}
+
+#[test]
+fn lzo_reports_errors() {
+    let options = CodecOptions::default();
+    let data = test_data(10_000);
+    let mut encoder = encoder(Algorithm::Lzo, 0, None, &options).unwrap();
+    let mut buffer = vec![0; 1000];
+    let error = encoder.compress(&data, &mut buffer).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(error.to_string(), "Output buffer too small");
+
+    let mut compressed = vec![0; encoder.compressed_len_bound(data.len())];
+    let len = encoder.compress(&data, &mut compressed).unwrap();
+    let mut decoder = decoder(Algorithm::Lzo, None, &options).unwrap();
+    let error = decoder
+        .decompress(&compressed[..len], &mut buffer)
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "Failed to decompress: the output buffer is too small"
+    );
+}
+
+#[test]
+fn rejects_unsupported_container_version() {
+    let data = test_data(50_000);
+    let mut compressed = compress_bytes(&data, Algorithm::Lz4, 1, 16384, None).unwrap();
+    // The version is the payload of the header frame at the start of the stream
+    compressed[8] = FORMAT_VERSION + 1;
+    let error = decompress_bytes(&compressed, Algorithm::Lz4, None).unwrap_err();
+    assert!(format!("{:#}", error).contains("Unsupported container version"));
+    assert!(scan_frames(Cursor::new(&compressed)).is_err());
+
+    let mut decoder = decoder(Algorithm::Lz4, None, &CodecOptions::default()).unwrap();
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress_with_version_check(
+        Cursor::new(&compressed),
+        &mut decompressed,
+        decoder.as_mut(),
+        false,
+    )
+    .unwrap();
+    assert_eq!(decompressed.into_inner(), data);
+}
+
+#[test]
+fn decompresses_stream_without_header() {
+    let data = test_data(50_000);
+    let compressed = compress_bytes(&data, Algorithm::Lz4, 1, 16384, None).unwrap();
+    let legacy = &compressed[12..];
+    assert_eq!(
+        decompress_bytes(legacy, Algorithm::Lz4, None).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn incompressible_chunks_are_stored() {
+    let data: Vec<u8> = test_data(100_000)
+        .into_iter()
+        .filter(|&b| b != b' ' && !b.is_ascii_lowercase())
+        .collect();
+    for (algorithm, level) in [
+        (Algorithm::Lz4, 1),
+        (Algorithm::Zstd, 1),
+        (Algorithm::Copy, 0),
+    ] {
+        let compressed = compress_bytes(&data, algorithm, level, 4096, None).unwrap();
+        let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+        assert!(frames.iter().all(|f| f.stored), "{}", algorithm.name());
+        assert_eq!(compressed.len(), 12 + frames.len() * 8 + data.len());
+        assert_eq!(
+            decompress_bytes(&compressed, algorithm, None).unwrap(),
+            data
+        );
+    }
+}
+
+#[test]
+fn adaptive_chunks_double_up_to_max_size() {
+    let sizes: Vec<usize> = adaptive_chunk_sizes(20_000).take(5).collect();
+    assert_eq!(sizes, [4096, 8192, 16384, 20_000, 20_000]);
+    assert_eq!(adaptive_chunk_sizes(1000).next(), Some(1000));
+
+    let data = test_data(100_000);
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Lz4, 1, None, &options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_adaptive(
+        Cursor::new(&data),
+        &mut compressed,
+        32768,
+        encoder.as_mut(),
+        ContainerOptions::default(),
+        |_, _| {},
+    )
+    .unwrap();
+    let frames = scan_frames(Cursor::new(compressed.get_ref())).unwrap();
+    let frame_lens: Vec<u64> = frames.iter().map(|f| f.uncompressed_len).collect();
+    assert_eq!(frame_lens, [4096, 8192, 16384, 32768, 32768, 5792]);
+    assert_eq!(
+        decompress_bytes(compressed.get_ref(), Algorithm::Lz4, None).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn lz4_acceleration_applies_only_to_fast_mode() {
+    let data = test_data(100_000);
+    let accelerated = CodecOptions {
+        lz4_acceleration: Some(50),
+        ..CodecOptions::default()
+    };
+    let mut lz4 = encoder(Algorithm::Lz4, 0, None, &accelerated).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress(Cursor::new(&data), &mut compressed, 16384, lz4.as_mut()).unwrap();
+    assert_eq!(
+        decompress_bytes(compressed.get_ref(), Algorithm::Lz4, None).unwrap(),
+        data
+    );
+    let default = compress_bytes(&data, Algorithm::Lz4, 0, 16384, None).unwrap();
+    assert!(compressed.get_ref().len() > default.len());
+
+    assert!(encoder(Algorithm::Lz4, 9, None, &accelerated).is_err());
+    let error = encoder(Algorithm::Lz4, -3, None, &CodecOptions::default()).err();
+    assert!(format!("{:#}", error.unwrap()).contains("does not support compression level -3"));
+}
+
+#[test]
+fn encoder_rejects_levels_out_of_range() {
+    for algorithm in Algorithm::value_variants() {
+        let range = algorithm.compression_level_range();
+        for level in [*range.start() - 1, *range.end() + 1] {
+            let Err(error) = encoder(*algorithm, level, None, &CodecOptions::default()) else {
+                panic!("{} accepted level {}", algorithm.name(), level);
+            };
+            let message = format!("{:#}", error);
+            assert!(
+                message.contains("does not support compression level"),
+                "{}",
+                message
+            );
+        }
+    }
+}
+
+#[cfg(feature = "lz4-dict")]
+#[test]
+fn lz4_uses_dictionary() {
+    let records: Vec<String> = (0..2000)
+        .map(|i| format!("id={} name=user{} active=true\n", i, i * 7))
+        .collect();
+    let dict = records[..200].concat().into_bytes();
+    let data = records[200..].concat().into_bytes();
+    for level in [0, 9] {
+        // Small chunks compress poorly on their own, so the dictionary should help
+        let plain = compress_bytes(&data, Algorithm::Lz4, level, 256, None).unwrap();
+        let with_dict = compress_bytes(&data, Algorithm::Lz4, level, 256, Some(&dict)).unwrap();
+        assert!(with_dict.len() < plain.len(), "level {}", level);
+        assert_eq!(
+            decompress_bytes(&with_dict, Algorithm::Lz4, Some(&dict)).unwrap(),
+            data
+        );
+        assert!(decompress_bytes(&with_dict, Algorithm::Lz4, None).map_or(true, |d| d != data));
+    }
+
+    // The accelerated fast mode uses the dictionary as well
+    let options = CodecOptions {
+        lz4_acceleration: Some(4),
+        ..CodecOptions::default()
+    };
+    let mut encoder = encoder(Algorithm::Lz4, 0, Some(&dict), &options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress(Cursor::new(&data), &mut compressed, 256, encoder.as_mut()).unwrap();
+    let compressed = compressed.into_inner();
+    let plain = compress_bytes(&data, Algorithm::Lz4, 0, 256, None).unwrap();
+    assert!(compressed.len() < plain.len());
+    assert_eq!(
+        decompress_bytes(&compressed, Algorithm::Lz4, Some(&dict)).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn brotli_window_and_mode_roundtrip() {
+    let data = test_data(200_000);
+    let settings = [
+        (Some(16), BrotliMode::Text),
+        (Some(26), BrotliMode::Generic),
+        (None, BrotliMode::Font),
+    ];
+    for (brotli_window, brotli_mode) in settings {
+        let options = CodecOptions {
+            brotli_window,
+            brotli_mode,
+            ..CodecOptions::default()
+        };
+        let mut encoder = encoder(Algorithm::Brotli, 5, None, &options).unwrap();
+        let mut decoder = decoder(Algorithm::Brotli, None, &options).unwrap();
+        let mut compressed = Cursor::new(Vec::new());
+        compress(Cursor::new(&data), &mut compressed, 65536, encoder.as_mut()).unwrap();
+        compressed.rewind().unwrap();
+        let mut decompressed = Cursor::new(Vec::new());
+        decompress(compressed, &mut decompressed, decoder.as_mut()).unwrap();
+        assert_eq!(decompressed.into_inner(), data, "{:?}", brotli_window);
+    }
+}
+
+#[test]
+fn brotli_window_and_mode_change_output() {
+    let data = test_data(200_000);
+    let compress_chunk = |brotli_window, brotli_mode| {
+        let options = CodecOptions {
+            brotli_window,
+            brotli_mode,
+            ..CodecOptions::default()
+        };
+        let mut encoder = encoder(Algorithm::Brotli, 9, None, &options).unwrap();
+        let mut compressed = vec![0; encoder.compressed_len_bound(data.len())];
+        let len = encoder.compress(&data, &mut compressed).unwrap();
+        compressed.truncate(len);
+        compressed
+    };
+    let default = compress_chunk(None, BrotliMode::Generic);
+    assert_ne!(compress_chunk(Some(16), BrotliMode::Generic), default);
+    assert_ne!(compress_chunk(None, BrotliMode::Font), default);
+}
+
+#[test]
+fn brotli_large_window_needs_large_window_decoder() {
+    let data = test_data(200_000);
+    let large_window = CodecOptions {
+        brotli_window: Some(26),
+        ..CodecOptions::default()
+    };
+    let mut encoder = encoder(Algorithm::Brotli, 5, None, &large_window).unwrap();
+    let mut compressed = vec![0; encoder.compressed_len_bound(data.len())];
+    let len = encoder.compress(&data, &mut compressed).unwrap();
+    compressed.truncate(len);
+
+    let mut decompressed = vec![0; data.len()];
+    let mut default = decoder(Algorithm::Brotli, None, &CodecOptions::default()).unwrap();
+    let error = default
+        .decompress(&compressed, &mut decompressed)
+        .unwrap_err();
+    assert!(error.to_string().contains("brotli error"), "{}", error);
+
+    let mut large = decoder(Algorithm::Brotli, None, &large_window).unwrap();
+    assert_eq!(
+        large.decompress(&compressed, &mut decompressed).unwrap(),
+        data.len()
+    );
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn chunk_size_is_limited_by_frame_length() {
+    let options = CodecOptions::default();
+    let copy = encoder(Algorithm::Copy, 0, None, &options).unwrap();
+    assert_eq!(copy.max_chunk_size(), MAX_FRAME_LEN);
+    assert_eq!(MAX_FRAME_LEN, (1 << 31) - 1);
+
+    for chunk_size in [MAX_FRAME_LEN + 1, u32::MAX as usize + 1] {
+        let error = compress_bytes(b"data", Algorithm::Copy, 0, chunk_size, None).unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(
+            message.contains("supported by the container format"),
+            "{}",
+            message
+        );
+    }
+    // lz4 has a lower limit of its own
+    let lz4 = encoder(Algorithm::Lz4, 0, None, &options).unwrap();
+    let error = compress_bytes(b"data", Algorithm::Lz4, 0, MAX_FRAME_LEN, None).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("supported by the codec"), "{}", message);
+    assert!(lz4.max_chunk_size() < MAX_FRAME_LEN);
+}
+
+#[test]
+fn decompresses_with_any_read_buffer_size() {
+    let data = test_data(100_000);
+    let compressed = compress_bytes(&data, Algorithm::Zstd, 1, 16384, None).unwrap();
+    // Frames larger than the buffer, straddling its end and fitting many times
+    for buffer_size in [0, 1000, 20_000, DEFAULT_READ_BUFFER_SIZE] {
+        let mut decoder = decoder(Algorithm::Zstd, None, &CodecOptions::default()).unwrap();
+        let mut decompressed = Cursor::new(Vec::new());
+        let options = DecompressionOptions {
+            read_buffer_size: buffer_size,
+            ..DecompressionOptions::default()
+        };
+        decompress_with_options(
+            Cursor::new(&compressed),
+            &mut decompressed,
+            decoder.as_mut(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed.into_inner(), data, "{}", buffer_size);
+    }
+}
+
+#[test]
+fn rejects_frames_longer_than_the_limit() {
+    let data = test_data(100_000);
+    let compressed = compress_bytes(&data, Algorithm::Zstd, 1, 16384, None).unwrap();
+    let decompress_to_vec = |compressed: &[u8], max_frame_len| {
+        let mut decoder = decoder(Algorithm::Zstd, None, &CodecOptions::default()).unwrap();
+        let options = DecompressionOptions {
+            max_frame_len,
+            ..DecompressionOptions::default()
+        };
+        let mut decompressed = Cursor::new(Vec::new());
+        decompress_with_options(
+            Cursor::new(compressed),
+            &mut decompressed,
+            decoder.as_mut(),
+            &options,
+        )
+        .map(|_| decompressed.into_inner())
+    };
+    assert_eq!(decompress_to_vec(&compressed, 16384).unwrap(), data);
+    let error = decompress_to_vec(&compressed, 10_000).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("chunk 0"), "{}", message);
+    assert!(
+        message.contains("exceeds the maximum frame length 10000"),
+        "{}",
+        message
+    );
+
+    // A corrupted uncompressed length of the first data frame
+    let frames = scan_frames(Cursor::new(&compressed)).unwrap();
+    let mut corrupted = compressed.clone();
+    let offset = frames[0].compressed_offset as usize;
+    corrupted[offset..offset + 4].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+    let error = decompress_to_vec(&corrupted, MAX_FRAME_LEN).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains("Uncompressed length 4294967280 exceeds"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn default_frame_limit_is_256_mib() {
+    assert_eq!(DecompressionOptions::default().max_frame_len, 256 << 20);
+}
+
+#[test]
+fn rejects_header_frames_longer_than_64_kib() {
+    let mut compressed = compress_bytes(&test_data(1000), Algorithm::Lz4, 1, 16384, None).unwrap();
+    // The payload length of the header frame at the start of the stream
+    compressed[4..8].copy_from_slice(&(1u32 << 30).to_le_bytes());
+    let error = read_header(compressed.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(
+        error.to_string().contains("Header frame length"),
+        "{}",
+        error
+    );
+    assert!(scan_frames(Cursor::new(&compressed)).is_err());
+    let error = decompress_bytes(&compressed, Algorithm::Lz4, None).unwrap_err();
+    assert!(format!("{:#}", error).contains("Header frame length"));
+}
+
+#[test]
+fn rejects_single_blocks_longer_than_the_limit() {
+    let decompress_to_vec = |block: &[u8], max_frame_len| {
+        let mut decoder = decoder(Algorithm::Lz4, None, &CodecOptions::default()).unwrap();
+        let options = DecompressionOptions {
+            max_frame_len,
+            ..DecompressionOptions::default()
+        };
+        let mut decompressed = Cursor::new(Vec::new());
+        decompress_single_block_with_options(
+            Cursor::new(block),
+            &mut decompressed,
+            decoder.as_mut(),
+            &options,
+        )
+        .map(|_| decompressed.into_inner())
+    };
+    // A corrupted uncompressed length is rejected before allocating the output
+    let mut block = 0x7fff_ffffu32.to_le_bytes().to_vec();
+    block.extend([0; 100]);
+    let error = decompress_to_vec(&block, 1000).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains("Uncompressed length 2147483647 exceeds"),
+        "{}",
+        message
+    );
+
+    // A stored block longer than the limit is not read to the end
+    let mut block = (2000u32 | 1 << 31).to_le_bytes().to_vec();
+    block.extend([0; 2000]);
+    let error = decompress_to_vec(&block, 1000).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains("exceeds the maximum frame length 1000"),
+        "{}",
+        message
+    );
+    assert_eq!(decompress_to_vec(&block, 2000).unwrap(), [0; 2000]);
+}
+
+#[test]
+fn ratio_is_shown_as_percent_and_factor() {
+    let ratio = Ratio::new(250, 1000);
+    assert_eq!(ratio.as_percent(), 25.0);
+    assert_eq!(ratio.as_factor(), 4.0);
+    let measurement = Measurement {
+        input_len: 1000,
+        output_len: 250,
+        elapsed: Duration::from_secs(1),
+        cpu_time: Duration::from_secs(1),
+    };
+    assert_eq!(measurement.compression_ratio(), ratio);
+    assert_eq!(
+        measurement.format_compression(),
+        "1000 => 250 (25.0%, 4.00x)"
+    );
+    assert_eq!(format!("({:>13})", ratio), "( 25.0%, 4.00x)");
+}
+
+#[cfg(unix)]
+#[test]
+fn compression_measures_cpu_time() {
+    let data = test_data(4_000_000);
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Zstd, 19, None, &options).unwrap();
+    let measurement = compress(
+        Cursor::new(&data),
+        Cursor::new(Vec::new()),
+        1 << 20,
+        encoder.as_mut(),
+    )
+    .unwrap();
+    assert!(measurement.cpu_time > Duration::ZERO);
+}
+
+#[test]
+fn units_format_sizes_and_throughputs() {
+    assert_eq!(Units::Si.format_size(999.0), "999 B");
+    assert_eq!(Units::Si.format_size(1500.0), "1.5 kB");
+    assert_eq!(Units::Iec.format_size(1536.0), "1.5 KiB");
+    assert_eq!(Units::Si.format_size(1_048_576.0), "1 MB");
+    assert_eq!(Units::Iec.format_size(1_048_576.0), "1 MiB");
+    assert_eq!(Units::Si.format_throughput(2_500_000.0), "2.5 MB/s");
+    assert_eq!(Units::Iec.format_throughput(2_621_440.0), "2.5 MiB/s");
+}
+
+fn single_block_roundtrip(algorithm: Algorithm, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let options = CodecOptions::default();
+    let level = algorithm.get_compression_levels()[0];
+    let mut encoder = encoder(algorithm, level, None, &options).unwrap();
+    let mut decoder = decoder(algorithm, None, &options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_single_block(Cursor::new(data), &mut compressed, encoder.as_mut()).unwrap();
+    let compressed = compressed.into_inner();
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress_single_block(
+        Cursor::new(&compressed),
+        &mut decompressed,
+        decoder.as_mut(),
+    )
+    .unwrap();
+    (compressed, decompressed.into_inner())
+}
+
+#[test]
+fn single_block_roundtrip_all_algorithms() {
+    let data = test_data(50_000);
+    for algorithm in Algorithm::value_variants() {
+        for len in [0, 100, data.len()] {
+            let (_, output) = single_block_roundtrip(*algorithm, &data[..len]);
+            assert!(output == data[..len], "{} length {}", algorithm.name(), len);
+        }
+    }
+}
+
+#[test]
+fn single_block_length_is_checked_before_reading_input() {
+    let encoder = encoder(Algorithm::Lz4, 0, None, &CodecOptions::default()).unwrap();
+    let max_len = encoder.max_chunk_size() as u64;
+    assert!(check_single_block_len(max_len, encoder.as_ref()).is_ok());
+    let error = check_single_block_len(max_len + 1, encoder.as_ref()).unwrap_err();
+    assert!(error.to_string().contains("doesn't fit in a single block"));
+}
+
+#[test]
+fn single_block_adds_only_the_length() {
+    let data = test_data(1000);
+    let (compressed, _) = single_block_roundtrip(Algorithm::Copy, &data);
+    assert_eq!(compressed.len(), data.len() + 4);
+    let chunked = compress_bytes(&data, Algorithm::Copy, 0, 16384, None).unwrap();
+    assert!(chunked.len() > compressed.len());
+}
@@ -0,0 +1,36 @@
+use clap::ValueEnum;
+use compresto::synthetic::Synthetic;
+use compresto::{compress_bytes, Algorithm};
+
+#[test]
+fn generates_the_same_data_for_the_same_seed() {
+    for &kind in Synthetic::value_variants() {
+        let data = kind.generate(100_001, 7);
+        assert_eq!(data.len(), 100_001, "{:?}", kind);
+        assert_eq!(data, kind.generate(100_001, 7), "{:?}", kind);
+    }
+    assert_ne!(
+        Synthetic::Random.generate(1000, 1),
+        Synthetic::Random.generate(1000, 2)
+    );
+    assert!(Synthetic::Zeros.generate(1000, 1).iter().all(|&b| b == 0));
+    assert!(Synthetic::Text.generate(1000, 1).is_ascii());
+}
+
+#[test]
+fn kinds_are_ordered_from_most_to_least_compressible() {
+    let compressed_lens: Vec<usize> = Synthetic::value_variants()
+        .iter()
+        .map(|kind| {
+            let data = kind.generate(1 << 20, 0);
+            compress_bytes(&data, Algorithm::Zstd, 3, 1 << 18, None)
+                .unwrap()
+                .len()
+        })
+        .collect();
+    assert!(
+        compressed_lens.windows(2).all(|lens| lens[0] < lens[1]),
+        "{:?}",
+        compressed_lens
+    );
+}
@@ -0,0 +1,163 @@
+use compresto::reader::ChunkReader;
+use compresto::synthetic::Synthetic;
+use compresto::transform::Transform;
+use compresto::{
+    compress_with_options, decoder, decompress, encoder, read_header, Algorithm, CodecOptions,
+    ContainerOptions,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::num::NonZeroU32;
+use std::str::FromStr;
+
+/// Slowly increasing values, with pseudo-random steps of 0 to 3
+fn ramp(len: usize) -> Vec<u8> {
+    let mut value = 0u8;
+    Synthetic::Random
+        .generate(len, 1)
+        .iter()
+        .map(|random| {
+            value = value.wrapping_add(random % 4);
+            value
+        })
+        .collect()
+}
+
+fn compress_transformed(data: &[u8], transform: Transform) -> Vec<u8> {
+    let options = CodecOptions::default();
+    let mut encoder = encoder(Algorithm::Zstd, 3, None, &options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_options(
+        Cursor::new(data),
+        &mut compressed,
+        16384,
+        encoder.as_mut(),
+        ContainerOptions {
+            transform,
+            ..ContainerOptions::default()
+        },
+        |_, _| {},
+    )
+    .unwrap();
+    compressed.into_inner()
+}
+
+#[test]
+fn inverse_restores_original_bytes() {
+    let transforms = [
+        Transform::None,
+        Transform::from_str("delta").unwrap(),
+        Transform::from_str("delta:3").unwrap(),
+        Transform::from_str("transpose:1").unwrap(),
+        Transform::from_str("transpose:4").unwrap(),
+        Transform::from_str("transpose:7").unwrap(),
+    ];
+    for transform in transforms {
+        for len in [0, 1, 5, 28, 1000] {
+            let data = ramp(len);
+            let mut transformed = Vec::new();
+            let mut restored = Vec::new();
+            transform.apply(&data, &mut transformed);
+            assert_eq!(transformed.len(), data.len());
+            transform.reverse(&transformed, &mut restored);
+            assert_eq!(restored, data, "{:?} of {} bytes", transform, len);
+        }
+    }
+}
+
+#[test]
+fn transpose_groups_bytes_of_records() {
+    let mut transformed = Vec::new();
+    Transform::from_str("transpose:2")
+        .unwrap()
+        .apply(b"a1b2c3x", &mut transformed);
+    assert_eq!(transformed, b"abc123x");
+}
+
+#[test]
+fn delta_subtracts_byte_at_given_distance() {
+    let mut transformed = Vec::new();
+    Transform::from_str("delta:2")
+        .unwrap()
+        .apply(&[10, 20, 11, 22, 13, 25, 7], &mut transformed);
+    assert_eq!(transformed, [10, 20, 1, 2, 2, 3, 250]);
+}
+
+#[test]
+fn delta_improves_ratio_on_ramp() {
+    let data = ramp(200_000);
+    let plain = compress_transformed(&data, Transform::None);
+    let delta = compress_transformed(&data, Transform::from_str("delta").unwrap());
+    assert!(
+        delta.len() < plain.len() / 2,
+        "delta: {}, none: {}",
+        delta.len(),
+        plain.len()
+    );
+}
+
+#[test]
+fn decompress_reverses_transform_from_header() {
+    let data = ramp(100_000);
+    for transform in [
+        Transform::from_str("delta:2").unwrap(),
+        Transform::from_str("transpose:4").unwrap(),
+    ] {
+        let compressed = compress_transformed(&data, transform);
+        let options = CodecOptions::default();
+        let mut zstd = decoder(Algorithm::Zstd, None, &options).unwrap();
+        let mut decompressed = Cursor::new(Vec::new());
+        decompress(Cursor::new(&compressed), &mut decompressed, zstd.as_mut()).unwrap();
+        assert_eq!(decompressed.into_inner(), data);
+
+        let mut reader = ChunkReader::new(Cursor::new(&compressed), zstd).unwrap();
+        reader.seek(SeekFrom::Start(50_000)).unwrap();
+        let mut buf = vec![0; 1000];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[50_000..51_000]);
+    }
+}
+
+#[test]
+fn parses_transform_names() {
+    assert_eq!(Transform::from_str("none"), Ok(Transform::None));
+    assert_eq!(
+        Transform::from_str("delta"),
+        Ok(Transform::Delta(NonZeroU32::MIN))
+    );
+    assert_eq!(
+        Transform::from_str("delta:4"),
+        Ok(Transform::Delta(NonZeroU32::new(4).unwrap()))
+    );
+    assert!(Transform::from_str("delta:0").is_err());
+    assert!(Transform::from_str("transpose:0").is_err());
+    assert!(Transform::from_str("transpose").is_err());
+    assert!(Transform::from_str("rle").is_err());
+}
+
+#[test]
+fn header_records_original_extension_with_transform() {
+    let data = ramp(50_000);
+    let options = ContainerOptions {
+        transform: Transform::from_str("delta").unwrap(),
+        original_extension: Some("csv".to_owned()),
+        ..ContainerOptions::default()
+    };
+    let mut zstd = encoder(Algorithm::Zstd, 3, None, &CodecOptions::default()).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress_with_options(
+        Cursor::new(&data),
+        &mut compressed,
+        16384,
+        zstd.as_mut(),
+        options.clone(),
+        |_, _| {},
+    )
+    .unwrap();
+    let compressed = compressed.into_inner();
+    assert_eq!(read_header(compressed.as_slice()).unwrap(), options);
+
+    let mut zstd = decoder(Algorithm::Zstd, None, &CodecOptions::default()).unwrap();
+    let mut decompressed = Cursor::new(Vec::new());
+    decompress(Cursor::new(&compressed), &mut decompressed, zstd.as_mut()).unwrap();
+    assert_eq!(decompressed.into_inner(), data);
+}
@@ -0,0 +1,63 @@
+use compresto::{compress, decoder, decompress, encoder, Algorithm, CodecOptions};
+use std::io::{Cursor, Seek};
+use zstd::zstd_safe;
+
+fn samples() -> Vec<Vec<u8>> {
+    (0..500)
+        .map(|i| {
+            format!(
+                "{{\"id\": {}, \"name\": \"user{}\", \"active\": {}}}",
+                i,
+                i * 7,
+                i % 2 == 0
+            )
+        })
+        .map(String::into_bytes)
+        .collect()
+}
+
+fn compress_with(data: &[u8], dict: &[u8], options: &CodecOptions) -> Vec<u8> {
+    let mut encoder = encoder(Algorithm::Zstd, 3, Some(dict), options).unwrap();
+    let mut compressed = Cursor::new(Vec::new());
+    compress(Cursor::new(data), &mut compressed, 1024, encoder.as_mut()).unwrap();
+    compressed.into_inner()
+}
+
+#[test]
+fn frame_header_carries_overridden_dict_id() {
+    let samples = samples();
+    let dict = zstd::dict::from_samples(&samples, 4096).unwrap();
+    let data = samples.concat();
+    let options = CodecOptions {
+        zstd_dict_id: Some(123456),
+        ..CodecOptions::default()
+    };
+    let mut compressed = Cursor::new(compress_with(&data, &dict, &options));
+
+    // Skip the container header frame and the header of the first data frame
+    let first_frame = &compressed.get_ref()[12 + 8..];
+    let dict_id = zstd_safe::get_dict_id_from_frame(first_frame).map(|id| id.get());
+    assert_eq!(dict_id, Some(123456));
+
+    let mut decoder = decoder(Algorithm::Zstd, Some(&dict), &options).unwrap();
+    let mut decompressed = Cursor::new(Vec::new());
+    compressed.rewind().unwrap();
+    decompress(&mut compressed, &mut decompressed, decoder.as_mut()).unwrap();
+    assert_eq!(decompressed.into_inner(), data);
+}
+
+#[test]
+fn dict_id_requires_zstd_dictionary() {
+    let options = CodecOptions {
+        zstd_dict_id: Some(1),
+        ..CodecOptions::default()
+    };
+    assert!(encoder(
+        Algorithm::Zstd,
+        3,
+        Some(b"raw content dictionary"),
+        &options
+    )
+    .is_err());
+    assert!(encoder(Algorithm::Zstd, 3, None, &options).is_err());
+}